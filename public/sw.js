@@ -0,0 +1,89 @@
+// Caches the app shell so the installed remote opens straight away, and shows
+// offline.html when the server can't be reached. Media, API calls and the live
+// update socket always go to the network.
+
+// Pages delete this cache themselves before reloading for a new build (`SHELL_CACHE` in socket.rs)
+const CACHE = "player-shell-v2";
+
+// The build itself, which must always match the server, so is only served from the cache offline
+const BUILD = [
+  "pkg/player.js",
+  "pkg/player_bg.wasm",
+  "pkg/player.css"
+].map((path) => new URL(path, self.registration.scope).href);
+
+const SHELL = BUILD.concat([
+  "manifest.webmanifest",
+  "offline.html",
+  "icons/icon-192.png",
+  "icons/icon-512.png"
+].map((path) => new URL(path, self.registration.scope).href));
+
+const OFFLINE = new URL("offline.html", self.registration.scope).href;
+
+self.addEventListener("install", (event) => {
+  event.waitUntil(caches.open(CACHE).then((cache) => cache.addAll(SHELL)).then(() => self.skipWaiting()));
+});
+
+self.addEventListener("activate", (event) => {
+  event.waitUntil(
+    caches.keys()
+      .then((keys) => Promise.all(keys.filter((key) => key !== CACHE).map((key) => caches.delete(key))))
+      .then(() => self.clients.claim())
+  );
+});
+
+self.addEventListener("fetch", (event) => {
+  const request = event.request;
+
+  if (request.method !== "GET") {
+    return;
+  }
+
+  // Pages are rendered by the server, so fetch them fresh, falling back to the offline page
+  if (request.mode === "navigate") {
+    event.respondWith(fetch(request).catch(() => caches.match(OFFLINE)));
+    return;
+  }
+
+  // Fetch the build fresh, so a new one is never paired with a stale page, keeping a copy for offline
+  if (BUILD.includes(request.url)) {
+    event.respondWith(
+      caches.open(CACHE).then((cache) =>
+        fetch(request)
+          .then((response) => {
+            if (response.ok) {
+              cache.put(request, response.clone());
+            }
+            return response;
+          })
+          .catch(() => cache.match(request))
+      )
+    );
+    return;
+  }
+
+  // Serve the rest of the shell from the cache, refreshing it in the background for next time
+  if (SHELL.includes(request.url)) {
+    event.respondWith(
+      caches.open(CACHE).then((cache) =>
+        cache.match(request).then((cached) => {
+          const fresh = fetch(request)
+            .then((response) => {
+              if (response.ok) {
+                cache.put(request, response.clone());
+              }
+              return response;
+            });
+
+          if (cached) {
+            event.waitUntil(fresh.catch(() => {}));
+            return cached;
+          }
+
+          return fresh;
+        })
+      )
+    );
+  }
+});
@@ -1,6 +1,9 @@
 use crate::display::Display;
 use crate::error::DisplayError;
-use crate::remote::Remote;
+use crate::pair::Pair;
+use crate::party::Party;
+use crate::remote::{Devices, Profiles, Remote, RemoteLayout, Schedules};
+use crate::socket::{provide_server_signals, BASE_PATH};
 use leptos::*;
 use leptos_meta::*;
 use leptos_router::*;
@@ -8,21 +11,29 @@ use leptos_router::*;
 #[component]
 pub fn App(cx: Scope) -> impl IntoView {
     provide_meta_context(cx);
-    leptos_server_signal::provide_websocket(cx, "ws://localhost:3000/ws").unwrap();
+    provide_server_signals(cx);
 
     view! {
         cx,
-        <Html attributes=AdditionalAttributes::from(vec![("data-bs-theme", "dark")])/>
-        <Stylesheet href="https://cdn.jsdelivr.net/npm/bootstrap@5.3.1/dist/css/bootstrap.min.css"/>
-        <Stylesheet href="https://unpkg.com/boxicons@2.1.4/css/boxicons.min.css"/>
-        <Stylesheet href="/pkg/player.css"/>
-        <Script src="https://cdn.jsdelivr.net/npm/bootstrap@5.3.1/dist/js/bootstrap.bundle.min.js"></Script>
+        <Stylesheet href=format!("{}/pkg/player.css", BASE_PATH)/>
         <Title text="Player"/>
+        <Link rel="manifest" href=format!("{}/manifest.webmanifest", BASE_PATH)/>
+        <Link rel="apple-touch-icon" href=format!("{}/icons/icon-180.png", BASE_PATH)/>
+        <Meta name="theme-color" content="#212529"/>
+        <Meta name="apple-mobile-web-app-capable" content="yes"/>
+        <Meta name="apple-mobile-web-app-status-bar-style" content="black-translucent"/>
         <Body class="vh-100"/>
         <Router fallback=|cx| { view! { cx, <DisplayError error="Not Found" status_code=http::status::StatusCode::NOT_FOUND/> } }>
             <main class="h-100 overflow-hidden">
                 <Routes>
-                    <Route path="" view=|cx| view! { cx, <Remote/> }/>
+                    <Route path="" view=|cx| view! { cx, <RemoteLayout/> }>
+                        <Route path="" view=|cx| view! { cx, <Remote/> }/>
+                        <Route path="schedules" view=|cx| view! { cx, <Schedules/> }/>
+                        <Route path="devices" view=|cx| view! { cx, <Devices/> }/>
+                        <Route path="profiles" view=|cx| view! { cx, <Profiles/> }/>
+                    </Route>
+                    <Route path="pair" view=|cx| view! { cx, <Pair/> }/>
+                    <Route path="party/:party" view=|cx| view! { cx, <Party/> }/>
                     <Route path="tv" view=|cx| view! { cx, <Display/> }/>
                 </Routes>
             </main>
@@ -0,0 +1,423 @@
+use cfg_if::cfg_if;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+/// The parts of the server config the remote needs.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub struct RemoteSettings {
+    /// Seconds the rewind button jumps back
+    pub skip_back: f64,
+    /// Seconds the fast forward button jumps ahead
+    pub skip_forward: f64
+}
+
+impl Default for RemoteSettings {
+    fn default() -> Self {
+        Self {
+            skip_back: 60.0,
+            skip_forward: 60.0
+        }
+    }
+}
+
+#[server(GetRemoteSettings, "/api")]
+pub async fn get_remote_settings(cx: Scope) -> Result<RemoteSettings, ServerFnError> {
+    let config = expect_context::<Config>(cx);
+    let profile = crate::profiles::current_profile(cx).map(|profile| profile.settings).unwrap_or_default();
+
+    Ok(RemoteSettings {
+        skip_back: profile.skip_back.unwrap_or(config.skip.back),
+        skip_forward: profile.skip_forward.unwrap_or(config.skip.forward)
+    })
+}
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use crate::displays::DEFAULT_DISPLAY;
+    use crate::parties::PARTY_PREFIX;
+    use crate::player::Instruction;
+    use crate::schedule::Cron;
+    use std::collections::HashSet;
+    use std::net::IpAddr;
+    use std::path::{Path, PathBuf};
+
+    /// Server configuration, read from a TOML file. Every field has a default, so an empty
+    /// (or missing) file gives a working setup serving `dev-media`.
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct Config {
+        pub server: ServerConfig,
+        pub data_dir: PathBuf,
+        #[serde(rename = "library")]
+        pub libraries: Vec<LibraryConfig>,
+        /// File extensions to list and play, without the dot. Everything is listed if empty.
+        pub extensions: Vec<String>,
+        pub skip: SkipConfig,
+        /// Displays that are known before they first connect
+        pub displays: Vec<String>,
+        /// Displays that can play together, in sync
+        #[serde(rename = "group")]
+        pub groups: Vec<GroupConfig>,
+        pub sync: SyncConfig,
+        /// Schedules that can't be changed from a remote, alongside those added there
+        #[serde(rename = "schedule")]
+        pub schedules: Vec<ScheduleConfig>,
+        pub auth: AuthConfig,
+        pub kodi: KodiConfig,
+        pub tls: TlsConfig
+    }
+
+    /// Overrides the leptos `site-addr` where set.
+    #[derive(Clone, Debug, Default, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct ServerConfig {
+        pub address: Option<IpAddr>,
+        pub port: Option<u16>
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct LibraryConfig {
+        /// Used in media paths and URLs. Derived from the name if not set.
+        #[serde(default)]
+        pub id: Option<String>,
+        pub name: String,
+        pub path: PathBuf,
+        /// Allow symlinks that point outside the library
+        #[serde(default)]
+        pub follow_symlinks: bool
+    }
+
+    impl LibraryConfig {
+        pub fn id(&self) -> String {
+            match &self.id {
+                Some(id) => id.clone(),
+                None => self.name
+                    .to_lowercase()
+                    .split(|c: char| !c.is_ascii_alphanumeric())
+                    .filter(|part| !part.is_empty())
+                    .collect::<Vec<_>>()
+                    .join("-")
+            }
+        }
+    }
+
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct SkipConfig {
+        pub back: f64,
+        pub forward: f64
+    }
+
+    /// Displays that follow whichever of them leads, for the same film in two rooms. The first
+    /// online member leads.
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct GroupConfig {
+        pub name: String,
+        pub displays: Vec<String>
+    }
+
+    /// Sends `action` to `display` whenever `cron` matches, like a schedule added from a remote.
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(deny_unknown_fields)]
+    pub struct ScheduleConfig {
+        pub name: String,
+        /// Minute, hour, day of month, month and day of week
+        pub cron: String,
+        pub display: String,
+        pub action: Instruction
+    }
+
+    /// How closely grouped displays are kept together.
+    #[derive(Clone, Copy, Debug, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct SyncConfig {
+        /// Seconds a display can drift from its group's leader before it's nudged back, by
+        /// playing slightly faster or slower
+        pub tolerance: f64,
+        /// Seconds of drift beyond which a display seeks to the leader instead
+        pub seek_after: f64
+    }
+
+    #[derive(Clone, Debug, Default, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct AuthConfig {
+        /// Require remotes to pair before they can control anything
+        pub enabled: bool,
+        /// PIN for the admin page, 4 to 8 digits
+        pub admin_pin: Option<String>
+    }
+
+    /// Kodi's JSON-RPC API, for remote apps built for Kodi. Off unless enabled.
+    #[derive(Clone, Debug, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct KodiConfig {
+        pub enabled: bool,
+        /// The display Kodi remotes control
+        pub display: String,
+        /// Kodi's web server password, which remotes send as basic auth with any username
+        pub password: Option<String>
+    }
+
+    /// HTTPS, from certificate files or a generated self-signed certificate. Off unless one is set.
+    #[derive(Clone, Debug, Default, Deserialize)]
+    #[serde(default, deny_unknown_fields)]
+    pub struct TlsConfig {
+        /// PEM certificate chain
+        pub cert: Option<PathBuf>,
+        /// PEM private key
+        pub key: Option<PathBuf>,
+        /// Generate a certificate into `data_dir/tls` on first run and reuse it after
+        pub self_signed: bool,
+        /// Names the self-signed certificate covers, besides localhost and the server address
+        pub hostnames: Vec<String>,
+        /// Also listen for plain HTTP on this port, redirecting to HTTPS
+        pub redirect_port: Option<u16>
+    }
+
+    impl TlsConfig {
+        pub fn is_enabled(&self) -> bool {
+            self.self_signed || self.cert.is_some()
+        }
+    }
+
+    impl Default for Config {
+        fn default() -> Self {
+            Self {
+                server: ServerConfig::default(),
+                data_dir: PathBuf::from("data"),
+                libraries: vec![LibraryConfig {
+                    id: None,
+                    name: "Media".to_string(),
+                    path: PathBuf::from("dev-media"),
+                    follow_symlinks: false
+                }],
+                extensions: vec![],
+                skip: SkipConfig::default(),
+                displays: vec![],
+                groups: vec![],
+                sync: SyncConfig::default(),
+                schedules: vec![],
+                auth: AuthConfig::default(),
+                kodi: KodiConfig::default(),
+                tls: TlsConfig::default()
+            }
+        }
+    }
+
+    impl Default for SkipConfig {
+        fn default() -> Self {
+            let settings = RemoteSettings::default();
+
+            Self {
+                back: settings.skip_back,
+                forward: settings.skip_forward
+            }
+        }
+    }
+
+    impl Default for SyncConfig {
+        fn default() -> Self {
+            Self {
+                tolerance: 0.2,
+                seek_after: 2.0
+            }
+        }
+    }
+
+    impl Default for KodiConfig {
+        fn default() -> Self {
+            Self {
+                enabled: false,
+                display: DEFAULT_DISPLAY.to_string(),
+                password: None
+            }
+        }
+    }
+
+    impl Config {
+        /// Reads the config file at `path`, or the defaults if there isn't one.
+        pub fn load(path: Option<&Path>) -> Result<Self, String> {
+            let Some(path) = path else {
+                return Ok(Self::default());
+            };
+
+            let toml = std::fs::read_to_string(path)
+                .map_err(|e| format!("Couldn't read config file {}: {}", path.display(), e))?;
+
+            toml::from_str(&toml).map_err(|e| format!("Invalid config file {}: {}", path.display(), e))
+        }
+
+        /// Checks the config is usable, reporting every problem at once, and normalises
+        /// extensions to lowercase without a leading dot.
+        pub fn validate(mut self) -> Result<Self, String> {
+            let mut errors = vec![];
+
+            if self.libraries.is_empty() {
+                errors.push("At least one [[library]] is required".to_string());
+            }
+
+            let mut names = HashSet::new();
+            let mut ids = HashSet::new();
+
+            for library in &self.libraries {
+                let id = library.id();
+
+                if library.name.trim().is_empty() {
+                    errors.push(format!("Library at {} has an empty name", library.path.display()));
+                } else if !names.insert(library.name.clone()) {
+                    errors.push(format!("Library name '{}' is used more than once", library.name));
+                }
+
+                if id.is_empty() || !id.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_') {
+                    errors.push(format!("Library '{}' needs an id of letters, numbers, - and _", library.name));
+                } else if !ids.insert(id.clone()) {
+                    errors.push(format!("Library id '{}' is used more than once", id));
+                }
+
+                if !library.path.is_dir() {
+                    errors.push(format!("Library '{}' path {} is not a directory", library.name, library.path.display()));
+                }
+            }
+
+            if self.data_dir.exists() && !self.data_dir.is_dir() {
+                errors.push(format!("data_dir {} is not a directory", self.data_dir.display()));
+            }
+
+            self.extensions = self.extensions
+                .iter()
+                .map(|ext| ext.trim().trim_start_matches('.').to_lowercase())
+                .collect();
+
+            if self.extensions.iter().any(|ext| ext.is_empty()) {
+                errors.push("extensions can't contain an empty extension".to_string());
+            }
+
+            for (name, seconds) in [("skip.back", self.skip.back), ("skip.forward", self.skip.forward)] {
+                if !seconds.is_finite() || seconds <= 0.0 {
+                    errors.push(format!("{} must be a positive number of seconds, found {}", name, seconds));
+                }
+            }
+
+            let mut displays = HashSet::new();
+
+            for display in &self.displays {
+                if display.trim().is_empty() {
+                    errors.push("displays can't contain an empty name".to_string());
+                } else if !displays.insert(display.clone()) {
+                    errors.push(format!("Display '{}' is listed more than once", display));
+                }
+            }
+
+            let mut groups = HashSet::new();
+
+            for group in &self.groups {
+                if group.name.trim().is_empty() {
+                    errors.push("Groups need a name".to_string());
+                } else if !groups.insert(group.name.clone()) {
+                    errors.push(format!("Group name '{}' is used more than once", group.name));
+                } else if displays.contains(&group.name) {
+                    errors.push(format!("Group '{}' has the same name as a display", group.name));
+                }
+
+                let members = group.displays.iter().collect::<HashSet<_>>();
+
+                if members.len() < 2 || members.len() != group.displays.len() {
+                    errors.push(format!("Group '{}' needs at least two different displays", group.name));
+                }
+            }
+
+            for group in &self.groups {
+                if let Some(member) = group.displays.iter().find(|member| groups.contains(*member)) {
+                    errors.push(format!("Group '{}' can't contain the group '{}'", group.name, member));
+                }
+            }
+
+            let sync = self.sync;
+
+            if !sync.tolerance.is_finite() || sync.tolerance <= 0.0 {
+                errors.push(format!("sync.tolerance must be a positive number of seconds, found {}", sync.tolerance));
+            }
+
+            if !sync.seek_after.is_finite() || sync.seek_after <= sync.tolerance {
+                errors.push(format!("sync.seek_after must be more seconds than sync.tolerance, found {}", sync.seek_after));
+            }
+
+            for schedule in &self.schedules {
+                if schedule.name.trim().is_empty() {
+                    errors.push("Schedules need a name".to_string());
+                }
+
+                if let Err(e) = Cron::parse(&schedule.cron) {
+                    errors.push(format!("Schedule '{}' has an invalid cron: {}", schedule.name, e));
+                }
+
+                if let Err(e) = schedule.action.check() {
+                    errors.push(format!("Schedule '{}' has an invalid action: {}", schedule.name, e));
+                }
+            }
+
+            match &self.auth.admin_pin {
+                Some(pin) if !(4..=8).contains(&pin.len()) || !pin.chars().all(|c| c.is_ascii_digit()) => {
+                    errors.push("auth.admin_pin must be 4 to 8 digits".to_string());
+                },
+                None if self.auth.enabled => {
+                    errors.push("auth.admin_pin is required when auth is enabled".to_string());
+                },
+                _ => ()
+            }
+
+            let kodi = &self.kodi;
+
+            if kodi.enabled && (kodi.display.trim().is_empty() || kodi.display.starts_with(PARTY_PREFIX)) {
+                errors.push(format!("kodi.display '{}' isn't a display name", kodi.display));
+            }
+
+            if kodi.enabled && self.auth.enabled && kodi.password.as_deref().map(str::is_empty).unwrap_or(true) {
+                errors.push("kodi.password is required when auth is enabled".to_string());
+            }
+
+            let tls = &self.tls;
+
+            match (&tls.cert, &tls.key) {
+                (Some(_), Some(_)) if tls.self_signed => {
+                    errors.push("tls.self_signed can't be used with tls.cert and tls.key".to_string());
+                },
+                (Some(cert), Some(key)) => {
+                    for (name, path) in [("tls.cert", cert), ("tls.key", key)] {
+                        if !path.is_file() {
+                            errors.push(format!("{} {} is not a file", name, path.display()));
+                        }
+                    }
+                },
+                (None, None) => (),
+                _ => {
+                    errors.push("tls.cert and tls.key must be set together".to_string());
+                }
+            }
+
+            if tls.redirect_port.is_some() && !tls.is_enabled() {
+                errors.push("tls.redirect_port needs TLS to be enabled".to_string());
+            }
+
+            // `server.port` is filled in from Leptos's `site_addr` by now, if it wasn't set
+            if tls.redirect_port.is_some() && tls.redirect_port == self.server.port {
+                errors.push("tls.redirect_port must differ from server.port".to_string());
+            }
+
+            if errors.is_empty() {
+                Ok(self)
+            } else {
+                Err(errors.join("\n"))
+            }
+        }
+    }
+
+    //
+    //
+    //
+}}
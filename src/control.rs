@@ -0,0 +1,137 @@
+use cfg_if::cfg_if;
+#[allow(unused_imports)]
+use crate::error::PlayerError;
+use crate::player::*;
+use leptos::*;
+
+/// Identifies unpaired remotes, which have no device to go by.
+pub static REMOTE_COOKIE : &'static str = "player_remote";
+/// The name an unpaired remote has given itself.
+pub static REMOTE_NAME_COOKIE : &'static str = "player_remote_name";
+
+/// This remote, as other remotes see it in the activity feed.
+#[server(GetRemote, "/api")]
+pub async fn get_remote(cx: Scope) -> Result<Remote, ServerFnError> {
+    Ok(current_remote(cx))
+}
+
+/// Names this remote. Paired devices are renamed, and anything else remembers the name in a cookie.
+#[server(SetRemoteName, "/api")]
+pub async fn set_remote_name(cx: Scope, name: String) -> Result<(), ServerFnError> {
+    let name = name.trim();
+
+    if name.is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+        return Err(PlayerError::InvalidInput(format!("Names need 1 to {} characters", MAX_NAME_LENGTH)).into());
+    }
+
+    match current_device(cx) {
+        Some(device) => {
+            let devices = expect_context::<Devices>(cx);
+            devices.rename(device.id, name).map_err(PlayerError::from)?;
+        },
+        None => {
+            let encoded = url::form_urlencoded::byte_serialize(name.as_bytes()).collect::<String>();
+            set_cookie(cx, REMOTE_NAME_COOKIE, &encoded);
+        }
+    }
+
+    Ok(())
+}
+
+/// Gives this remote control of a display, so no other remote can control it until released.
+#[server(LockControl, "/api")]
+pub async fn lock_control(cx: Scope, display: String) -> Result<(), ServerFnError> {
+    let displays = expect_context::<Displays>(cx);
+    let remote = current_remote(cx);
+
+    displays.control(&display, &remote, "took control", |session| session.lock(&remote))?;
+
+    Ok(())
+}
+
+/// Lets every remote control a display again. Only the remote holding control can release
+/// it, along with admins, in case it's been left locked.
+#[server(ReleaseControl, "/api")]
+pub async fn release_control(cx: Scope, display: String) -> Result<(), ServerFnError> {
+    let displays = expect_context::<Displays>(cx);
+    let remote = current_remote(cx);
+
+    let is_holder = displays.with(&display, |session| session.controller().map(|holder| holder.id == remote.id))?;
+
+    match is_holder {
+        None => return Ok(()),
+        Some(false) => {
+            crate::devices::require_admin(cx)?;
+        },
+        Some(true) => ()
+    }
+
+    displays.with(&display, |session| {
+        session.release();
+        session.record(&remote, "released control");
+    })?;
+
+    Ok(())
+}
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use crate::devices::{current_device, request_cookie, set_cookie, Devices};
+    use crate::displays::Displays;
+    use crate::profiles::current_profile;
+    use rand::Rng;
+    use std::collections::hash_map::RandomState;
+    use std::hash::{BuildHasher, Hash, Hasher};
+    use std::sync::OnceLock;
+
+    static MAX_NAME_LENGTH : usize = 40;
+    /// Keys the hashes remotes are known by, fresh each run like control itself
+    static ID_KEY : OnceLock<RandomState> = OnceLock::new();
+
+    /// Who sent the current request. Paired devices go by their device, and other remotes
+    /// by a cookie given out the first time they're asked about. Unnamed remotes take the
+    /// name of their profile.
+    pub fn current_remote(cx: Scope) -> Remote {
+        if let Some(device) = current_device(cx) {
+            return Remote {
+                id: opaque_id("device", &device.id.to_string()),
+                name: device.name
+            };
+        }
+
+        let id = request_cookie(cx, REMOTE_COOKIE).unwrap_or_else(|| {
+            let mut rng = rand::thread_rng();
+            let id = (0..8).map(|_| format!("{:02x}", rng.gen::<u8>())).collect::<String>();
+
+            set_cookie(cx, REMOTE_COOKIE, &id);
+            id
+        });
+
+        // Names are form encoded, as cookies can't hold every character
+        let name = request_cookie(cx, REMOTE_NAME_COOKIE)
+            .and_then(|name| url::form_urlencoded::parse(name.as_bytes()).next().map(|(name, _)| name.to_string()))
+            .or_else(|| current_profile(cx).map(|profile| profile.name))
+            .unwrap_or_else(|| "A remote".to_string());
+
+        Remote {
+            id: opaque_id("remote", &id),
+            name
+        }
+    }
+
+    /// An id for a remote that every remote can be sent, as it can't be turned back into the
+    /// cookie it stands for.
+    fn opaque_id(kind: &str, id: &str) -> String {
+        let mut hasher = ID_KEY.get_or_init(RandomState::new).build_hasher();
+        id.hash(&mut hasher);
+
+        format!("{}-{:016x}", kind, hasher.finish())
+    }
+
+    //
+    //
+    //
+}}
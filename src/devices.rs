@@ -0,0 +1,510 @@
+use cfg_if::cfg_if;
+#[allow(unused_imports)]
+use crate::error::PlayerError;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+pub static TOKEN_COOKIE : &'static str = "player_token";
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum DeviceKind {
+    /// Paired with the PIN shown on a display
+    Remote,
+    /// Paired with the admin PIN, and can manage other devices
+    Admin,
+    /// Paired with the admin PIN to show media, which shows the pairing PIN to remotes but
+    /// can't manage devices
+    Display
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Device {
+    pub id: u64,
+    pub name: String,
+    pub kind: DeviceKind,
+    pub paired: String,
+    pub last_seen: Option<String>
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PairingCode {
+    pub pin: String,
+    /// SVG QR code linking to the pairing page with the PIN filled in
+    pub qr: String
+}
+
+/// Pairs this browser using a display's PIN or the admin PIN, setting its token cookie.
+/// Displays pair with the admin PIN, as a display device.
+#[server(PairDevice, "/api")]
+pub async fn pair_device(cx: Scope, pin: String, name: String, display: bool) -> Result<(), ServerFnError> {
+    use crate::config::Config;
+
+    let config = expect_context::<Config>(cx);
+    let devices = expect_context::<Devices>(cx);
+
+    let kind = devices
+        .check_pin(client(cx), &pin, config.auth.admin_pin.as_deref())?;
+
+    let kind = match (display, kind) {
+        (false, kind) => kind,
+        (true, DeviceKind::Admin) => DeviceKind::Display,
+        (true, _) => return Err(PlayerError::Forbidden("Displays are paired with the admin PIN".to_string()).into())
+    };
+
+    let name = match name.trim() {
+        "" => "Unnamed device",
+        name => name
+    };
+
+    let token = devices
+        .add(name, kind)
+        .map_err(PlayerError::from)?;
+
+    // Long lived, as re-pairing means walking over to the TV
+    set_cookie(cx, TOKEN_COOKIE, &token);
+
+    Ok(())
+}
+
+/// The current pairing PIN, and a QR code for `url` followed by the PIN. `None` if auth is off.
+/// Only displays and admins are given it, so remotes can't pair more remotes.
+#[server(GetPairingCode, "/api")]
+pub async fn get_pairing_code(cx: Scope, url: String) -> Result<Option<PairingCode>, ServerFnError> {
+    use crate::config::Config;
+    use qrcode::render::svg;
+
+    let config = expect_context::<Config>(cx);
+    let devices = expect_context::<Devices>(cx);
+
+    if !config.auth.enabled {
+        return Ok(None);
+    }
+
+    match current_device(cx).map(|device| device.kind) {
+        Some(DeviceKind::Display | DeviceKind::Admin) => (),
+        _ => return Err(PlayerError::Forbidden("Only displays and admins can show the pairing PIN".to_string()).into())
+    }
+
+    let pin = devices.pairing_pin();
+
+    let qr = qrcode::QrCode::new(format!("{}{}", url, pin).as_bytes())
+        .map_err(|e| PlayerError::Internal(e.to_string()))?
+        .render::<svg::Color>()
+        .min_dimensions(240, 240)
+        .build();
+
+    Ok(Some(PairingCode { pin, qr }))
+}
+
+#[server(ListDevices, "/api")]
+pub async fn list_devices(cx: Scope) -> Result<Vec<Device>, ServerFnError> {
+    let devices = require_admin(cx)?;
+    Ok(devices.list())
+}
+
+#[server(RevokeDevice, "/api")]
+pub async fn revoke_device(cx: Scope, id: u64) -> Result<(), ServerFnError> {
+    let devices = require_admin(cx)?;
+
+    devices
+        .revoke(id)
+        .map_err(|e| PlayerError::from(e).into())
+}
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use crate::config::Config;
+    use crate::store::{self, Migration};
+    use chrono::Local;
+    use http::header::{HeaderMap, HeaderValue, COOKIE, SET_COOKIE};
+    use rand::Rng;
+    use std::collections::HashMap;
+    use std::net::{IpAddr, Ipv4Addr};
+    use std::path::PathBuf;
+    use std::sync::{Arc, Mutex, PoisonError, RwLock};
+    use std::time::{Duration, Instant};
+
+    static PIN_LIFETIME : Duration = Duration::from_secs(10 * 60);
+    static MAX_FAILURES : u32 = 5;
+    /// How long a client's wrong attempts are remembered after its last one
+    static FORGET_FAILURES : Duration = Duration::from_secs(60 * 60);
+
+    static MIGRATIONS : &'static [Migration] = &[store::unversioned];
+
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    struct PairedDevice {
+        #[serde(flatten)]
+        device: Device,
+        token: String,
+        /// The profile last picked on the device, kept here so clearing cookies doesn't drop it
+        #[serde(default)]
+        profile: Option<u64>
+    }
+
+    #[derive(Debug)]
+    struct Pairing {
+        pin: String,
+        expires: Instant
+    }
+
+    impl Pairing {
+        fn new() -> Self {
+            Self {
+                pin: format!("{:06}", rand::thread_rng().gen_range(0..1_000_000)),
+                expires: Instant::now() + PIN_LIFETIME
+            }
+        }
+    }
+
+    /// The address a request came from, which wrong PINs and passwords are counted against.
+    #[derive(Clone, Copy, Debug)]
+    pub struct Client(pub IpAddr);
+
+    /// The kinds of secret a client can guess at, each counted separately.
+    #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+    pub enum Secret {
+        /// A display's pairing PIN, which changes every ten minutes
+        Pairing,
+        /// The admin PIN, or an adult profile's, which never change on their own
+        Admin,
+        /// Kodi's web server password
+        Kodi
+    }
+
+    impl Secret {
+        /// How long a client is locked out after too many wrong attempts.
+        fn lockout(self) -> Duration {
+            match self {
+                Self::Pairing => Duration::from_secs(60),
+                Self::Admin | Self::Kodi => Duration::from_secs(15 * 60)
+            }
+        }
+    }
+
+    #[derive(Debug)]
+    struct Failures {
+        count: u32,
+        last: Instant,
+        locked_until: Option<Instant>
+    }
+
+    /// Paired devices, persisted as JSON when one is added or revoked. Last seen times are
+    /// only kept in memory between those writes.
+    #[derive(Clone, Debug)]
+    pub struct Devices {
+        path: PathBuf,
+        devices: Arc<RwLock<Vec<PairedDevice>>>,
+        pairing: Arc<Mutex<Pairing>>,
+        failures: Arc<Mutex<HashMap<(IpAddr, Secret), Failures>>>
+    }
+
+    impl Devices {
+        /// Loads devices from `path`, starting empty if it doesn't exist yet.
+        pub fn load(path: PathBuf) -> Result<Self, String> {
+            let devices = store::load(&path, MIGRATIONS)?;
+
+            Ok(Self {
+                path,
+                devices: Arc::new(RwLock::new(devices)),
+                pairing: Arc::new(Mutex::new(Pairing::new())),
+                failures: Arc::new(Mutex::new(HashMap::new()))
+            })
+        }
+
+        pub fn list(&self) -> Vec<Device> {
+            self.devices.read().unwrap_or_else(PoisonError::into_inner).iter().map(|paired| paired.device.clone()).collect()
+        }
+
+        /// The device holding `token`, which is marked as seen now. Only takes the write lock
+        /// when the minute it was last seen changes, as every request comes through here.
+        pub fn authenticate(&self, token: &str) -> Option<Device> {
+            let now = now();
+
+            {
+                let devices = self.devices.read().unwrap_or_else(PoisonError::into_inner);
+                let paired = devices.iter().find(|paired| secrets_match(token, &paired.token))?;
+
+                if paired.device.last_seen.as_ref() == Some(&now) {
+                    return Some(paired.device.clone());
+                }
+            }
+
+            let mut devices = self.devices.write().unwrap_or_else(PoisonError::into_inner);
+            let paired = devices.iter_mut().find(|paired| secrets_match(token, &paired.token))?;
+
+            paired.device.last_seen = Some(now);
+            Some(paired.device.clone())
+        }
+
+        /// The PIN displays show, replaced every ten minutes.
+        pub fn pairing_pin(&self) -> String {
+            let mut pairing = self.pairing.lock().unwrap_or_else(PoisonError::into_inner);
+
+            if Instant::now() > pairing.expires {
+                *pairing = Pairing::new();
+            }
+
+            pairing.pin.clone()
+        }
+
+        /// Checks a PIN entered on a remote. A display PIN can only be used once. Wrong PINs
+        /// count against the client for both kinds, so a locked out client can't keep guessing
+        /// the admin PIN while pairing is open to it, or the other way round.
+        pub fn check_pin(&self, client: IpAddr, pin: &str, admin_pin: Option<&str>) -> Result<DeviceKind, PlayerError> {
+            let admin_locked = admin_pin.is_none() || self.is_locked(client, Secret::Admin);
+            let pairing_locked = self.is_locked(client, Secret::Pairing);
+
+            if admin_locked && pairing_locked {
+                return Err(too_many_attempts());
+            }
+
+            if !admin_locked && admin_pin.map(|admin_pin| secrets_match(pin, admin_pin)).unwrap_or(false) {
+                self.record(client, Secret::Admin, true);
+                return Ok(DeviceKind::Admin);
+            }
+
+            if !pairing_locked {
+                let mut pairing = self.pairing.lock().unwrap_or_else(PoisonError::into_inner);
+
+                if Instant::now() <= pairing.expires && secrets_match(pin, &pairing.pin) {
+                    *pairing = Pairing::new();
+                    drop(pairing);
+
+                    self.record(client, Secret::Pairing, true);
+                    return Ok(DeviceKind::Remote);
+                }
+            }
+
+            self.record(client, Secret::Pairing, false);
+
+            if admin_pin.is_some() {
+                self.record(client, Secret::Admin, false);
+            }
+
+            Err(PlayerError::Unauthorized("Wrong or expired PIN".to_string()))
+        }
+
+        /// Runs `check` on a secret `client` gave, unless it's been locked out for giving too
+        /// many wrong ones of that kind, counting any error as a wrong attempt.
+        pub fn attempt<T>(
+            &self,
+            client: IpAddr,
+            secret: Secret,
+            check: impl FnOnce() -> Result<T, PlayerError>
+        ) -> Result<T, PlayerError> {
+            if self.is_locked(client, secret) {
+                return Err(too_many_attempts());
+            }
+
+            let result = check();
+            self.record(client, secret, result.is_ok());
+
+            result
+        }
+
+        fn is_locked(&self, client: IpAddr, secret: Secret) -> bool {
+            let failures = self.failures.lock().unwrap_or_else(PoisonError::into_inner);
+
+            failures
+                .get(&(client, secret))
+                .and_then(|failures| failures.locked_until)
+                .map(|until| Instant::now() < until)
+                .unwrap_or(false)
+        }
+
+        /// Counts a right or wrong attempt, locking the client out after too many wrong ones.
+        fn record(&self, client: IpAddr, secret: Secret, matched: bool) {
+            let mut failures = self.failures.lock().unwrap_or_else(PoisonError::into_inner);
+            let now = Instant::now();
+
+            // Forget clients that stopped guessing, so the map can't grow without bound
+            failures.retain(|_, failures| {
+                now.duration_since(failures.last) < FORGET_FAILURES
+                    || failures.locked_until.map(|until| now < until).unwrap_or(false)
+            });
+
+            if matched {
+                failures.remove(&(client, secret));
+                return;
+            }
+
+            let entry = failures.entry((client, secret)).or_insert(Failures {
+                count: 0,
+                last: now,
+                locked_until: None
+            });
+
+            entry.count += 1;
+            entry.last = now;
+
+            if entry.count >= MAX_FAILURES {
+                entry.count = 0;
+                entry.locked_until = Some(now + secret.lockout());
+            }
+        }
+
+        /// Adds a device, returning its new token.
+        pub fn add(&self, name: &str, kind: DeviceKind) -> std::io::Result<String> {
+            let mut rng = rand::thread_rng();
+            let token = (0..32).map(|_| format!("{:02x}", rng.gen::<u8>())).collect::<String>();
+
+            self.update(|devices| {
+                let id = devices.iter().map(|paired| paired.device.id).max().unwrap_or(0) + 1;
+
+                devices.push(PairedDevice {
+                    device: Device {
+                        id,
+                        name: name.to_string(),
+                        kind,
+                        paired: now(),
+                        last_seen: Some(now())
+                    },
+                    token: token.clone(),
+                    profile: None
+                });
+            })?;
+
+            Ok(token)
+        }
+
+        pub fn rename(&self, id: u64, name: &str) -> std::io::Result<()> {
+            self.update(|devices| {
+                if let Some(paired) = devices.iter_mut().find(|paired| paired.device.id == id) {
+                    paired.device.name = name.to_string();
+                }
+            })
+        }
+
+        /// The profile picked on a device, if any.
+        pub fn profile(&self, id: u64) -> Option<u64> {
+            self.devices
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .find(|paired| paired.device.id == id)
+                .and_then(|paired| paired.profile)
+        }
+
+        pub fn set_profile(&self, id: u64, profile: u64) -> std::io::Result<()> {
+            self.update(|devices| {
+                if let Some(paired) = devices.iter_mut().find(|paired| paired.device.id == id) {
+                    paired.profile = Some(profile);
+                }
+            })
+        }
+
+        pub fn revoke(&self, id: u64) -> std::io::Result<()> {
+            self.update(|devices| devices.retain(|paired| paired.device.id != id))
+        }
+
+        fn update(&self, f: impl FnOnce(&mut Vec<PairedDevice>)) -> std::io::Result<()> {
+            let mut devices = self.devices.write().unwrap_or_else(PoisonError::into_inner);
+            f(&mut devices);
+
+            store::save(&self.path, MIGRATIONS.len(), &*devices)
+        }
+    }
+
+    /// Compares a secret a client gave with the real one, taking as long whichever character
+    /// differs, so the time taken gives nothing away.
+    pub fn secrets_match(given: &str, expected: &str) -> bool {
+        let (given, expected) = (given.as_bytes(), expected.as_bytes());
+
+        let difference = (0..given.len().max(expected.len())).fold(given.len() ^ expected.len(), |difference, ix| {
+            let a = given.get(ix).copied().unwrap_or(0);
+            let b = expected.get(ix).copied().unwrap_or(0);
+
+            difference | (a ^ b) as usize
+        });
+
+        difference == 0
+    }
+
+    /// The address the request a server function is handling came from.
+    pub fn client(cx: Scope) -> IpAddr {
+        use_context::<Client>(cx)
+            .map(|client| client.0)
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED))
+    }
+
+    fn too_many_attempts() -> PlayerError {
+        PlayerError::Unauthorized("Too many wrong attempts, try again later".to_string())
+    }
+
+    /// The device token from a request's cookies.
+    pub fn token_from_headers(headers: &HeaderMap) -> Option<&str> {
+        cookie(headers, TOKEN_COOKIE)
+    }
+
+    pub fn cookie<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+        headers
+            .get_all(COOKIE)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|cookies| cookies.split(';'))
+            .filter_map(|cookie| cookie.trim().split_once('='))
+            .find(|(cookie, _)| *cookie == name)
+            .map(|(_, value)| value)
+    }
+
+    /// Sets a ten year, script inaccessible cookie on a server function's response, which is
+    /// only sent back over HTTPS when that's on.
+    pub fn set_cookie(cx: Scope, name: &str, value: &str) {
+        let secure = use_context::<Config>(cx).map(|config| config.tls.is_enabled()).unwrap_or(false);
+        let cookie = format!(
+            "{}={}; Path=/; Max-Age=315360000; HttpOnly; SameSite=Lax{}",
+            name,
+            value,
+            if secure { "; Secure" } else { "" }
+        );
+
+        if let (Some(response), Ok(cookie)) = (use_context::<leptos_axum::ResponseOptions>(cx), HeaderValue::from_str(&cookie)) {
+            response.append_header(SET_COOKIE, cookie);
+        }
+    }
+
+    /// A cookie from the request a server function is handling.
+    pub fn request_cookie(cx: Scope, name: &str) -> Option<String> {
+        use_context::<leptos_axum::RequestParts>(cx).and_then(|parts| cookie(&parts.headers, name).map(str::to_string))
+    }
+
+    /// The paired device the request a server function is handling came from.
+    pub fn current_device(cx: Scope) -> Option<Device> {
+        let devices = expect_context::<Devices>(cx);
+        request_cookie(cx, TOKEN_COOKIE).and_then(|token| devices.authenticate(&token))
+    }
+
+    /// The device store, if the request comes from an admin device or auth is off, and not
+    /// from a kid profile.
+    pub fn require_admin(cx: Scope) -> Result<Devices, ServerFnError> {
+        crate::profiles::require_unrestricted(cx)?;
+
+        let config = expect_context::<Config>(cx);
+        let devices = expect_context::<Devices>(cx);
+
+        if !config.auth.enabled {
+            return Ok(devices);
+        }
+
+        let is_admin = current_device(cx)
+            .map(|device| device.kind == DeviceKind::Admin)
+            .unwrap_or(false);
+
+        if is_admin {
+            Ok(devices)
+        } else {
+            Err(PlayerError::Forbidden("Only devices paired with the admin PIN can do that".to_string()).into())
+        }
+    }
+
+    fn now() -> String {
+        Local::now().format("%Y-%m-%d %H:%M").to_string()
+    }
+
+    //
+    //
+    //
+}}
@@ -1,23 +1,115 @@
 mod fns;
 
 use crate::display::fns::*;
+use crate::displays::DEFAULT_DISPLAY;
+use crate::error::PlayerError;
+use crate::icons::Icon;
+use crate::library::list_subtitles;
+use crate::pair::PairingCard;
+use crate::photos::*;
 use crate::player::*;
+use crate::profiles::ProfileSettings;
+use crate::socket::reload_for_update;
+use crate::tags::*;
 use leptos::*;
+use leptos_router::use_query_map;
 
 static MEDIA_ROOT : &'static str = "/play/";
 static VIDEO_STOPPED_SRC : &'static str = "https://video.is.stopped/";
 
+/// Plays whatever the named display is told to, the name coming from `?name=` when not given.
 #[component]
-pub fn Display(cx: Scope) -> impl IntoView {
+pub fn Display(
+    cx: Scope,
+    #[prop(optional)]
+    name: Option<String>,
+    /// Parties have no use for pairing, so leave the card off when idle
+    #[prop(optional)]
+    hide_pairing: bool
+) -> impl IntoView {
     let video = create_node_ref::<leptos::html::Video>(cx);
+    let name = name
+        .or_else(|| use_query_map(cx).with_untracked(|query| query.get("name").cloned()))
+        .unwrap_or_else(|| DEFAULT_DISPLAY.to_string());
+    let name = store_value(cx, name);
     let (heartbeat, tick) = create_signal(cx, 0);
+    let (current, set_current) = create_signal::<Option<String>>(cx, None);
+    let (slide_settings, set_slide_settings) = create_signal(cx, SlideSettings::default());
+    let slide = create_rw_signal::<Option<Slide>>(cx, None);
+    let playback = store_value(cx, Playback::default());
+    let on_media = move |event| playback.update_value(|playback| playback.apply(event));
+
+    let kind = create_memo(cx, move |_| current.with(|current| current.as_deref().map(MediaKind::of)));
+    let is_audio = move || kind.get() == Some(MediaKind::Audio);
+    let hide_video = move || kind.get().map(|kind| kind != MediaKind::Video).unwrap_or(false);
+
+    // Only changes when a new photo is shown, so the transition plays once per photo
+    let shown = create_memo(
+        cx,
+        move |_| slide.with(|slide| slide.as_ref().map(|s| (s.path.clone(), s.settings.transition)))
+    );
+
+    let track = create_local_resource(
+        cx,
+        move || current.get().filter(|path| MediaKind::of(path) == MediaKind::Audio),
+        move |path| async move {
+            match path {
+                Some(path) => get_track_info(cx, path).await.ok().map(|info| (path, info)),
+                None => None
+            }
+        }
+    );
+
+    let subtitles = create_local_resource(
+        cx,
+        move || current.get().filter(|path| MediaKind::of(path) == MediaKind::Video),
+        move |path| async move {
+            match path {
+                Some(path) => list_subtitles(cx, path).await.unwrap_or_default(),
+                None => vec![]
+            }
+        }
+    );
+
+    // Whoever started this media may have changed, so fetch their languages each time
+    let playback = create_local_resource(
+        cx,
+        move || current.get(),
+        move |_| async move { get_playback_settings(cx, name.get_value()).await.unwrap_or_default() }
+    );
+
+    let apply_languages = move || {
+        if let (Some(video), Some(settings)) = (video.get_untracked(), playback.read(cx)) {
+            // After any new subtitle tracks have been added to the video
+            request_animation_frame(move || set_languages(&video, &settings));
+        }
+    };
+
+    create_effect(cx, move |_| {
+        subtitles.track();
+        apply_languages();
+    });
+
+    let set_current_path = move |path: Option<&String>| {
+        if current.get_untracked().as_ref() != path {
+            set_current.set(path.cloned());
+        }
+    };
 
     let instruction = create_local_resource(
         cx,
         heartbeat,
-        move |_| get_instruction(cx)
+        move |_| get_instruction(cx, name.get_value(), PROTOCOL_VERSION)
     );
 
+    create_effect(cx, move |_| {
+        if let Some(Err(e)) = instruction.read(cx) {
+            if let PlayerError::Outdated { .. } = PlayerError::from(e) {
+                reload_for_update();
+            }
+        }
+    });
+
     create_effect(
         cx,
         move |_| {
@@ -25,15 +117,53 @@ pub fn Display(cx: Scope) -> impl IntoView {
                 if let Some(Ok(Some(instruction))) = instruction.read(cx) {
                     match instruction {
                         Instruction::Stop => {
+                            set_current_path(None);
+                            slide.set(None);
                             video.set_src(VIDEO_STOPPED_SRC);
+                            video.set_volume(1.0);
                         },
-                        Instruction::Play(src, from) => {
+                        Instruction::Volume { volume } => {
+                            video.set_volume(volume.clamp(0.0, 1.0));
+                        },
+                        Instruction::Seek { at } => {
+                            if slide.get_untracked().is_none() {
+                                video.set_current_time(at);
+                            }
+                        },
+                        Instruction::Rate { rate } => {
+                            video.set_playback_rate(rate.clamp(MIN_RATE, MAX_RATE));
+                        },
+                        // Queues, folders and slideshows are expanded into `Play`/`Show` instructions by the server
+                        Instruction::Queue { .. } | Instruction::PlayFolder { .. } | Instruction::Slideshow(_) => {},
+                        Instruction::Show { path: src, settings } => {
+                            set_current_path(Some(&src));
+                            set_slide_settings.set(settings);
+                            slide.set(Some(Slide::new(src, settings, 0.0, false)));
+                            video.set_src(VIDEO_STOPPED_SRC);
+                        },
+                        Instruction::Play { path: src, from } if MediaKind::of(&src) == MediaKind::Image => {
+                            set_current_path(Some(&src));
+                            slide.set(Some(Slide::new(src, slide_settings.get_untracked(), from, false)));
+                            video.set_src(VIDEO_STOPPED_SRC);
+                        },
+                        Instruction::Pause { path: src, at } if MediaKind::of(&src) == MediaKind::Image => {
+                            set_current_path(Some(&src));
+                            slide.set(Some(Slide::new(src, slide_settings.get_untracked(), at, true)));
+                            video.set_src(VIDEO_STOPPED_SRC);
+                        },
+                        Instruction::Play { path: src, from } => {
+                            set_current_path(Some(&src));
+                            slide.set(None);
+                            on_media(MediaEvent::Load { play: true });
                             let url = format!("{}{}", MEDIA_ROOT, src);
                             video.set_src(url.as_str());
                             video.set_current_time(from);
                             let _ = video.play();
                         },
-                        Instruction::Pause(src, at) => {
+                        Instruction::Pause { path: src, at } => {
+                            set_current_path(Some(&src));
+                            slide.set(None);
+                            on_media(MediaEvent::Load { play: false });
                             let url = format!("{}{}", MEDIA_ROOT, src);
                             video.set_src(url.as_str());
                             video.set_current_time(at);
@@ -44,7 +174,6 @@ pub fn Display(cx: Scope) -> impl IntoView {
     
                 let src = video.src();
                 let current_time = video.current_time();
-                let paused = video.paused();
 
                 let duration = video.duration();
                 let duration = if duration.is_nan() { 0.0 } else { duration };
@@ -55,16 +184,22 @@ pub fn Display(cx: Scope) -> impl IntoView {
                     src.clone()
                 };
     
-                let state = if src.as_str() == VIDEO_STOPPED_SRC || src.as_str() == "" {
+                let state = if let Some(slide) = slide.get_untracked() {
+                    slide.state()
+                } else if src.as_str() == VIDEO_STOPPED_SRC || src.as_str() == "" {
                     PlayerState::Idle
-                } else if paused {
-                    PlayerState::Media(MediaState::Playing(path, duration, current_time))
                 } else {
-                    PlayerState::Media(MediaState::Paused(path, duration, current_time))
+                    PlayerState::Media(MediaState {
+                        path,
+                        phase: playback.with_value(|playback| playback.phase().clone()),
+                        duration,
+                        position: current_time,
+                        buffered: buffered(&video)
+                    })
                 };
     
                 spawn_local(async move {
-                    let _ = set_player_state(cx, state).await;
+                    let _ = set_player_state(cx, name.get_value(), state).await;
                 })
             }
         }
@@ -74,13 +209,238 @@ pub fn Display(cx: Scope) -> impl IntoView {
     set_interval(
         move || {
             tick.update(|c| *c += 1);
+
+            let mut finished = false;
+            slide.update(|slide| {
+                if let Some(slide) = slide {
+                    finished = slide.tick(0.1);
+                }
+            });
+
+            if finished {
+                spawn_local(async move {
+                    let _ = advance(cx, name.get_value()).await;
+                })
+            }
         },
         std::time::Duration::from_millis(100)
     );
 
     view! { cx,
         <div class="video-wrapper">
-            <video class="video" autoplay=true node_ref=video/>
+            <video
+                class="video"
+                class:invisible=hide_video
+                autoplay=true
+                node_ref=video
+                on:loadedmetadata=move |_| apply_languages()
+                on:play=move |_| on_media(MediaEvent::Play)
+                on:playing=move |_| on_media(MediaEvent::Playing)
+                on:pause=move |_| on_media(MediaEvent::Pause)
+                on:waiting=move |_| on_media(MediaEvent::Waiting)
+                on:canplay=move |_| on_media(MediaEvent::CanPlay)
+                on:seeking=move |_| on_media(MediaEvent::Seeking)
+                on:seeked=move |_| on_media(MediaEvent::Seeked)
+                on:error=move |_| {
+                    // Stopping points the video at a source that always fails
+                    let is_playable = matches!(kind.get_untracked(), Some(MediaKind::Video | MediaKind::Audio));
+
+                    if let (true, Some(video)) = (is_playable, video.get_untracked()) {
+                        on_media(MediaEvent::Error(media_error(&video)));
+                    }
+                }
+                on:ended=move |_| {
+                    on_media(MediaEvent::Ended);
+                    spawn_local(async move {
+                        let _ = advance(cx, name.get_value()).await;
+                    })
+                }
+            >
+                {move || {
+                    subtitles
+                        .read(cx)
+                        .unwrap_or_default()
+                        .into_iter()
+                        .map(|subtitle| {
+                            let src = format!("{}{}", MEDIA_ROOT, subtitle.path);
+                            let language = subtitle.language.unwrap_or_default();
+
+                            view! { cx, <track kind="subtitles" src=src srclang=language.clone() label=language/> }
+                        })
+                        .collect::<Vec<_>>()
+                }}
+            </video>
+            {move || {
+                is_audio().then(|| {
+                    match track.read(cx).flatten() {
+                        Some((path, info)) => view! { cx, <AudioLayout path=path info=info/> }.into_view(cx),
+                        None => ().into_view(cx)
+                    }
+                })
+            }}
+            {move || {
+                shown.get().map(|(path, transition)| {
+                    view! { cx, <PhotoLayout path=path transition=transition/> }
+                })
+            }}
+            <Show when=move || !hide_pairing && current.with(|current| current.is_none()) fallback=|_| ()>
+                <PairingCard/>
+            </Show>
         </div>
     }
 }
+
+#[component]
+fn PhotoLayout(
+    cx: Scope,
+    path: String,
+    transition: Transition
+) -> impl IntoView {
+    let url = format!("{}{}", MEDIA_ROOT, path);
+
+    let info = create_local_resource(
+        cx,
+        || (),
+        move |_| get_photo_info(cx, path.clone())
+    );
+
+    let info = move || info.read(cx).and_then(|info| info.ok()).unwrap_or_default();
+
+    view! { cx,
+        <div class=format!("photo-layout {}", transition.class())>
+            <img class=move || format!("photo orientation-{}", info().orientation) src=url/>
+            {move || {
+                info().caption().map(|caption| {
+                    view! { cx, <div class="photo-caption">{caption}</div> }
+                })
+            }}
+        </div>
+    }
+}
+
+#[component]
+fn AudioLayout(
+    cx: Scope,
+    path: String,
+    info: TrackInfo
+) -> impl IntoView {
+    let file_name = path.rsplit('/').next().unwrap_or(&path).to_string();
+    let art = format!("{}{}", ART_ROOT, path);
+
+    view! { cx,
+        <div class="audio-layout d-flex align-items-center justify-content-center h-100">
+            <div class="row align-items-center w-75">
+                <div class="col-5">
+                    {if info.has_art {
+                        view! { cx, <img class="audio-art shadow" src=art/> }.into_view(cx)
+                    } else {
+                        view! { cx,
+                            <div class="audio-art audio-art-placeholder">
+                                <Icon icon="music"/>
+                            </div>
+                        }.into_view(cx)
+                    }}
+                </div>
+                <div class="col-7">
+                    <h1 class="display-4">{info.title.unwrap_or(file_name)}</h1>
+                    <h2 class="text-body-secondary">{info.artist}</h2>
+                    <h3 class="text-body-tertiary">{info.album}</h3>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Slide {
+    path: String,
+    settings: SlideSettings,
+    elapsed: f64,
+    paused: bool
+}
+
+impl Slide {
+    fn new(path: String, settings: SlideSettings, elapsed: f64, paused: bool) -> Self {
+        Self {
+            path,
+            settings,
+            // Not `clamp`, which panics on an interval saved before intervals were checked
+            elapsed: elapsed.min(settings.interval).max(0.0),
+            paused
+        }
+    }
+
+    /// Advances the slide timer, returning true only on the tick the interval runs out.
+    fn tick(&mut self, seconds: f64) -> bool {
+        if self.paused || self.elapsed >= self.settings.interval {
+            return false;
+        }
+
+        self.elapsed = (self.elapsed + seconds).min(self.settings.interval);
+        self.elapsed >= self.settings.interval
+    }
+
+    fn state(&self) -> PlayerState {
+        let phase = if self.paused {
+            Phase::Paused
+        } else if self.elapsed >= self.settings.interval {
+            Phase::Ended
+        } else {
+            Phase::Playing
+        };
+
+        PlayerState::Media(MediaState {
+            path: self.path.clone(),
+            phase,
+            duration: self.settings.interval,
+            position: self.elapsed,
+            buffered: vec![]
+        })
+    }
+}
+
+/// Shows subtitles and picks the audio track in the profile's languages, where the video has them.
+fn set_languages(video: &web_sys::HtmlVideoElement, settings: &ProfileSettings) {
+    use web_sys::TextTrackMode;
+
+    if let Some(tracks) = video.text_tracks() {
+        for ix in 0..tracks.length() {
+            if let Some(track) = tracks.get(ix) {
+                let show = settings.subtitle_language.as_deref() == Some(track.language().as_str());
+                track.set_mode(if show { TextTrackMode::Showing } else { TextTrackMode::Disabled });
+            }
+        }
+    }
+
+    if let Some(language) = &settings.audio_language {
+        let tracks = video.audio_tracks();
+        let tracks = (0..tracks.length()).filter_map(|ix| tracks.get(ix)).collect::<Vec<_>>();
+
+        if tracks.iter().any(|track| &track.language() == language) {
+            for track in tracks {
+                track.set_enabled(&track.language() == language);
+            }
+        }
+    }
+}
+
+/// The parts of the current media the video has loaded.
+fn buffered(video: &web_sys::HtmlVideoElement) -> Vec<(f64, f64)> {
+    let ranges = video.buffered();
+
+    (0..ranges.length())
+        .filter_map(|ix| ranges.start(ix).ok().zip(ranges.end(ix).ok()))
+        .collect()
+}
+
+/// Why the video failed, as a remote should show it.
+fn media_error(video: &web_sys::HtmlVideoElement) -> String {
+    use web_sys::MediaError;
+
+    match video.error().map(|error| error.code()) {
+        Some(MediaError::MEDIA_ERR_NETWORK) => "The network failed while loading",
+        Some(MediaError::MEDIA_ERR_DECODE) => "The file couldn't be decoded",
+        Some(MediaError::MEDIA_ERR_SRC_NOT_SUPPORTED) => "This display can't play this format",
+        _ => "Playback failed"
+    }.to_string()
+}
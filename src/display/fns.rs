@@ -1,25 +1,151 @@
+use cfg_if::cfg_if;
+#[allow(unused_imports)]
+use crate::error::PlayerError;
 use crate::player::*;
 use leptos::*;
+use crate::profiles::ProfileSettings;
+#[allow(unused_imports)]
+use crate::displays::Displays;
 #[allow(unused_imports)]
-use std::sync::{Arc, RwLock};
+use crate::profiles::Profiles;
 
+/// The display's next instruction. Displays built for another protocol version are told to
+/// reload instead, before they're sent anything they'd misread.
 #[server(GetInstruction, "/api")]
-pub async fn get_instruction(cx: Scope) -> Result<Option<Instruction>, ServerFnError> {
-    let instruction = expect_context::<Arc<RwLock<Option<Instruction>>>>(cx);
+pub async fn get_instruction(cx: Scope, display: String, protocol: u32) -> Result<Option<Instruction>, ServerFnError> {
+    if protocol != PROTOCOL_VERSION {
+        return Err(PlayerError::Outdated { client: protocol, server: PROTOCOL_VERSION }.into());
+    }
 
-    let mut writable = instruction.write().unwrap();
-    let pending = writable.clone();
-    *writable = None;
+    let pending = as_display(cx, &display, |session| {
+        session.seen();
+        session.instructions.pop_front()
+    })?;
 
     Ok(pending)
 }
 
 #[server(SetPlayerState, "/api")]
-pub async fn set_player_state(cx: Scope, state: PlayerState) -> Result<(), ServerFnError> {
-    let player_state = expect_context::<Arc<RwLock<PlayerState>>>(cx);
+pub async fn set_player_state(cx: Scope, display: String, state: PlayerState) -> Result<(), ServerFnError> {
+    let profiles = expect_context::<Profiles>(cx);
+
+    let progress = as_display(cx, &display, |session| {
+        session.seen();
+        session.reconnect(&state);
+        session.report(state.clone());
+        session.profile
+    })?;
+
+    // Resume points and watched state are only kept for videos
+    if let (Some(profile), PlayerState::Media(media)) = (progress, state) {
+        if MediaKind::of(&media.path) == MediaKind::Video && media.phase.has_position() {
+            profiles.record(profile, &media.path, media.position, media.duration);
+        }
+    }
+
+    Ok(())
+}
+
+/// Language preferences of the profile playing on this display.
+#[server(GetPlaybackSettings, "/api")]
+pub async fn get_playback_settings(cx: Scope, display: String) -> Result<ProfileSettings, ServerFnError> {
+    let displays = expect_context::<Displays>(cx);
+    let profiles = expect_context::<Profiles>(cx);
+
+    let settings = displays
+        .with(&display, |session| session.profile)
+        .ok()
+        .flatten()
+        .and_then(|id| profiles.list().into_iter().find(|profile| profile.id == id))
+        .map(|profile| profile.settings)
+        .unwrap_or_default();
+
+    Ok(settings)
+}
+
+#[server(Advance, "/api")]
+pub async fn advance(cx: Scope, display: String) -> Result<(), ServerFnError> {
+    let displays = expect_context::<Displays>(cx);
+
+    // Grouped displays move on with their leader
+    if displays.is_following(&display) {
+        return Ok(());
+    }
+
+    let device = display_device(cx);
 
-    let mut writable = player_state.write().unwrap();
-    *writable = state;
+    displays.with(&display, |session| {
+        if let Some((device, paired)) = &device {
+            session.check_device(&display, *device, |id| paired.contains(&id))?;
+        }
+
+        let queue = &mut session.queue;
+
+        let sleeping = match session.sleep.as_ref().map(|timer| timer.mode) {
+            Some(SleepMode::AfterItem) => true,
+            Some(SleepMode::AfterQueue) => queue.position + 1 >= queue.order.len(),
+            _ => false
+        };
+
+        let next = match queue.next() {
+            Some(_) if !sleeping => Some(queue.instruction()),
+            // Left on the last item, so remotes can show the queue finished and offer to play it again
+            None if !sleeping => None,
+            _ => {
+                queue.clear();
+                Some(Instruction::Stop)
+            }
+        };
+
+        if sleeping {
+            session.sleep = None;
+        }
+
+        session.instructions.extend(next);
+        Ok::<_, PlayerError>(())
+    })??;
 
     Ok(())
 }
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use crate::config::Config;
+    use crate::devices::{current_device, Devices};
+    use crate::displays::Session;
+
+    /// With auth on, the device asking to act as a display, and every paired device's id.
+    fn display_device(cx: Scope) -> Option<(u64, Vec<u64>)> {
+        let config = expect_context::<Config>(cx);
+        let devices = expect_context::<Devices>(cx);
+
+        if !config.auth.enabled {
+            return None;
+        }
+
+        let device = current_device(cx)?;
+        Some((device.id, devices.list().iter().map(|device| device.id).collect()))
+    }
+
+    /// Runs `f` on the named display, adding it if it's new, as long as it's this device's
+    /// display, so no other device can take its instructions or report for it.
+    fn as_display<R>(cx: Scope, name: &str, f: impl FnOnce(&mut Session) -> R) -> Result<R, PlayerError> {
+        let displays = expect_context::<Displays>(cx);
+        let device = display_device(cx);
+
+        displays.with_or_add(name, |session| {
+            if let Some((device, paired)) = &device {
+                session.check_device(name, *device, |id| paired.contains(&id))?;
+            }
+
+            Ok(f(session))
+        })?
+    }
+
+    //
+    //
+    //
+}}
@@ -0,0 +1,816 @@
+use cfg_if::cfg_if;
+use crate::player::*;
+use leptos::*;
+
+pub static DEFAULT_DISPLAY : &'static str = "tv";
+
+#[server(ListDisplays, "/api")]
+pub async fn list_displays(cx: Scope) -> Result<Vec<String>, ServerFnError> {
+    let displays = expect_context::<Displays>(cx);
+    Ok(displays.names())
+}
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use crate::error::PlayerError;
+    use crate::library::Libraries;
+    use crate::parties::PARTY_PREFIX;
+    use crate::profiles::Profiles;
+    use crate::sleep::SleepTimer;
+    use crate::store::{self, Migration};
+    use crate::sync::{Follower, Lead};
+    use chrono::{DateTime, Local, NaiveDate};
+    use rand::seq::SliceRandom;
+    use serde::{Deserialize, Serialize};
+    use serde_json::{json, Value};
+    use std::collections::{BTreeMap, BTreeSet, VecDeque};
+    use std::path::PathBuf;
+    use std::sync::{Arc, PoisonError, RwLock};
+    use std::time::{Duration, Instant};
+
+    static MIGRATIONS : &'static [Migration] = &[store::unversioned, media_state_fields];
+    static SAVE_INTERVAL : Duration = Duration::from_secs(5);
+    /// Displays poll several times a second, so one silent this long has gone away
+    static OFFLINE_AFTER : Duration = Duration::from_secs(10);
+    /// Control lapses once its holder has been quiet this long, so a phone left in a drawer
+    /// can't keep it
+    static CONTROL_TIMEOUT : Duration = Duration::from_secs(30 * 60);
+    static ACTIVITY_LENGTH : usize = 20;
+    /// Displays that aren't in the config are forgotten after this many days without checking in
+    static EXPIRE_AFTER_DAYS : i64 = 30;
+    static MAX_NAME_LENGTH : usize = 64;
+    static DAY_FORMAT : &'static str = "%Y-%m-%d";
+
+    /// Everything the server tracks for a single named display.
+    #[derive(Debug, Default)]
+    pub struct Session {
+        pub player: PlayerState,
+        pub instructions: VecDeque<Instruction>,
+        pub queue: Queue,
+        pub sleep: Option<SleepTimer>,
+        /// The profile of whoever last sent an instruction, which playback progress is recorded against
+        pub profile: Option<u64>,
+        /// The group the display has joined, by being sent an instruction as part of it
+        pub group: Option<String>,
+        pub following: Follower,
+        /// The volume it was last given, if not full
+        pub volume: Option<f64>,
+        /// The paired device the display runs on, which only it can check in as
+        pub device: Option<u64>,
+        /// The remote holding control, and when it last used it
+        control: Option<(Remote, Instant)>,
+        /// Newest first, and only kept in memory
+        activity: VecDeque<Activity>,
+        /// Restored after a restart, and not yet heard from its display
+        restored: bool,
+        last_seen: Option<DateTime<Local>>,
+        /// The day it last checked in, kept across restarts so displays gone for good expire
+        seen_on: Option<NaiveDate>,
+        /// When the display last reported its state
+        reported: Option<Instant>
+    }
+
+    /// The part of a session kept across restarts.
+    #[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+    struct SavedSession {
+        player: PlayerState,
+        queue: Queue,
+        profile: Option<u64>,
+        #[serde(default)]
+        seen_on: Option<String>,
+        #[serde(default)]
+        device: Option<u64>
+    }
+
+    impl Session {
+        fn restore(saved: SavedSession) -> Self {
+            Self {
+                player: saved.player,
+                queue: saved.queue,
+                profile: saved.profile,
+                device: saved.device,
+                // Displays saved before this was kept get a fresh start
+                seen_on: saved.seen_on
+                    .and_then(|day| NaiveDate::parse_from_str(&day, DAY_FORMAT).ok())
+                    .or_else(|| Some(Local::now().date_naive())),
+                restored: true,
+                ..Self::default()
+            }
+        }
+
+        fn saved(&self) -> SavedSession {
+            SavedSession {
+                player: self.player.clone(),
+                queue: self.queue.clone(),
+                profile: self.profile,
+                seen_on: self.seen_on.map(|day| day.format(DAY_FORMAT).to_string()),
+                device: self.device
+            }
+        }
+
+        /// Takes the first state a display reports after a restart. If the display has
+        /// lost what it was playing too, it's loaded again, paused where it was.
+        pub fn reconnect(&mut self, state: &PlayerState) {
+            if !std::mem::take(&mut self.restored) || *state != PlayerState::Idle {
+                return;
+            }
+
+            let PlayerState::Media(media) = &self.player else {
+                return;
+            };
+
+            let i = match MediaKind::of(&media.path) {
+                MediaKind::Image => Instruction::Show { path: media.path.clone(), settings: self.queue.slides },
+                _ => Instruction::Pause { path: media.path.clone(), at: media.position }
+            };
+
+            self.instructions.push_back(i);
+        }
+
+        /// Takes the state the display reports.
+        pub fn report(&mut self, state: PlayerState) {
+            self.player = state;
+            self.reported = Some(Instant::now());
+        }
+
+        /// Where playback should have got to by now, at `rate`, allowing for the time since
+        /// the display last reported. `None` unless it's playing.
+        pub fn position_now(&self, rate: f64) -> Option<f64> {
+            let PlayerState::Media(media) = &self.player else {
+                return None;
+            };
+
+            let since = self.reported.map(|time| time.elapsed().as_secs_f64()).unwrap_or(0.0);
+
+            match media.phase {
+                Phase::Playing => Some(media.position + since * rate),
+                _ => None
+            }
+        }
+
+        pub fn status(&self) -> Status {
+            Status {
+                player: self.player.clone(),
+                queue: self.queue.clone(),
+                sleep: self.sleep.as_ref().map(|timer| timer.status()),
+                online: self.is_online(),
+                last_seen: self.last_seen.map(|time| time.format("%Y-%m-%d %H:%M").to_string()),
+                control: self.controller().cloned(),
+                activity: self.activity.iter().cloned().collect(),
+                members: vec![],
+                group: self.group.clone()
+            }
+        }
+
+        /// The remote holding control, unless it has lapsed.
+        pub fn controller(&self) -> Option<&Remote> {
+            self.control
+                .as_ref()
+                .filter(|(_, used)| used.elapsed() < CONTROL_TIMEOUT)
+                .map(|(remote, _)| remote)
+        }
+
+        /// Refuses devices other than the one the display, `name`, runs on. The first to check
+        /// in as it claims it, and another can once that one is no longer `paired`.
+        pub fn check_device(&mut self, name: &str, device: u64, paired: impl Fn(u64) -> bool) -> Result<(), PlayerError> {
+            match self.device {
+                Some(owner) if owner != device && paired(owner) => {
+                    Err(PlayerError::Forbidden(format!("{} is another device's display", name)))
+                },
+                _ => {
+                    self.device = Some(device);
+                    Ok(())
+                }
+            }
+        }
+
+        /// Refuses remotes other than the one holding control of the display, `name`.
+        pub fn check_control(&mut self, name: &str, by: &Remote) -> Result<(), PlayerError> {
+            match self.controller() {
+                Some(holder) if holder.id != by.id => Err(PlayerError::Locked {
+                    display: name.to_string(),
+                    holder: holder.name.clone()
+                }),
+                Some(_) => {
+                    self.control = Some((by.clone(), Instant::now()));
+                    Ok(())
+                },
+                None => Ok(())
+            }
+        }
+
+        /// Gives `by` control, so only it can control the display until it releases it.
+        pub fn lock(&mut self, by: &Remote) {
+            self.control = Some((by.clone(), Instant::now()));
+        }
+
+        pub fn release(&mut self) {
+            self.control = None;
+        }
+
+        /// Adds to the display's activity feed, which every remote is sent.
+        pub fn record(&mut self, by: &Remote, action: impl Into<String>) {
+            let id = self.activity.front().map(|newest| newest.id + 1).unwrap_or(0);
+
+            self.activity.push_front(Activity {
+                id,
+                by: by.clone(),
+                action: action.into(),
+                time: Local::now().format("%H:%M").to_string()
+            });
+
+            self.activity.truncate(ACTIVITY_LENGTH);
+        }
+
+        /// Notes that the display has just checked in.
+        pub fn seen(&mut self) {
+            let now = Local::now();
+
+            self.last_seen = Some(now);
+            self.seen_on = Some(now.date_naive());
+        }
+
+        /// Whether the display hasn't checked in for `EXPIRE_AFTER_DAYS`.
+        fn has_expired(&self) -> bool {
+            let days = self.seen_on.map(|day| (Local::now().date_naive() - day).num_days());
+            !self.is_online() && days.map(|days| days > EXPIRE_AFTER_DAYS).unwrap_or(false)
+        }
+
+        pub fn is_online(&self) -> bool {
+            self.last_seen
+                .and_then(|time| (Local::now() - time).to_std().ok())
+                .map(|since| since < OFFLINE_AFTER)
+                .unwrap_or(false)
+        }
+
+        /// Checks the instruction's values and that every path in it is in the `libraries`,
+        /// expands queue, folder and slideshow instructions, updates the queue, and passes the
+        /// result on to the display.
+        pub fn instruct(&mut self, libraries: &Libraries, i: Instruction) -> Result<(), PlayerError> {
+            i.check().map_err(PlayerError::InvalidInstruction)?;
+
+            let queue = &mut self.queue;
+
+            let i = match i {
+                Instruction::Queue { items, position } => {
+                    for item in &items {
+                        libraries.file(item)?;
+                    }
+
+                    if position >= items.len() {
+                        return Err(PlayerError::InvalidInstruction(format!("Can't start at item {} of {}", position + 1, items.len())));
+                    }
+
+                    queue.replace(items, position);
+
+                    if queue.shuffle {
+                        queue.shuffle_with(|order| order.shuffle(&mut rand::thread_rng()));
+                    }
+
+                    queue.instruction()
+                },
+                Instruction::PlayFolder { folder } => {
+                    libraries.dir(&folder)?;
+
+                    let mut items = libraries.list_files(&folder)
+                        .into_iter()
+                        .filter(|path| MediaKind::of(path) != MediaKind::Image)
+                        .collect::<Vec<_>>();
+                    items.sort();
+
+                    if items.is_empty() {
+                        return Err(PlayerError::InvalidInstruction(format!("{} has nothing to play", folder)));
+                    }
+
+                    return self.instruct(libraries, Instruction::Queue { items, position: 0 });
+                },
+                Instruction::Slideshow(slideshow) => {
+                    libraries.dir(&slideshow.folder)?;
+
+                    let mut images = libraries.list_files(&slideshow.folder)
+                        .into_iter()
+                        .filter(|path| MediaKind::of(path) == MediaKind::Image)
+                        .collect::<Vec<_>>();
+
+                    if images.is_empty() {
+                        return Err(PlayerError::InvalidInstruction(format!("{} has no photos", slideshow.folder)));
+                    }
+
+                    if slideshow.shuffle {
+                        images.shuffle(&mut rand::thread_rng());
+                    } else {
+                        images.sort();
+                    }
+
+                    queue.replace(images, 0);
+                    queue.slides = slideshow.settings;
+                    queue.instruction()
+                },
+                Instruction::Stop => {
+                    queue.clear();
+                    // Displays go back to full volume when stopped
+                    self.volume = None;
+                    Instruction::Stop
+                },
+                Instruction::Play { path, from } => {
+                    libraries.file(&path)?;
+
+                    if queue.current() != Some(&path) {
+                        queue.replace(vec![path.clone()], 0);
+                    }
+
+                    Instruction::Play { path, from }
+                },
+                Instruction::Pause { path, at } => {
+                    libraries.file(&path)?;
+
+                    if queue.current() != Some(&path) {
+                        queue.replace(vec![path.clone()], 0);
+                    }
+
+                    Instruction::Pause { path, at }
+                },
+                Instruction::Show { path, settings } => {
+                    libraries.file(&path)?;
+
+                    if queue.current() != Some(&path) {
+                        queue.replace(vec![path.clone()], 0);
+                    }
+
+                    queue.slides = settings;
+                    Instruction::Show { path, settings }
+                },
+                Instruction::Volume { volume } => {
+                    self.volume = Some(volume);
+                    Instruction::Volume { volume }
+                },
+                Instruction::Seek { at } => Instruction::Seek { at },
+                Instruction::Rate { rate } => Instruction::Rate { rate }
+            };
+
+            self.instructions.push_back(i);
+            Ok(())
+        }
+    }
+
+    /// All known displays, keyed by name: those in the config, and any other display that has
+    /// checked in, until it expires. Remotes can only refer to displays known this way.
+    /// What each is playing and its queue are saved by `run`, so they survive a restart.
+    ///
+    /// Groups can be used wherever a display's name is, and stand for whichever of their
+    /// displays leads them.
+    #[derive(Clone, Debug)]
+    pub struct Displays {
+        path: PathBuf,
+        sessions: Arc<RwLock<BTreeMap<String, Session>>>,
+        groups: Arc<BTreeMap<String, Vec<String>>>,
+        /// Named in the config, so never expired
+        configured: Arc<BTreeSet<String>>,
+        /// For the viewing time left to whoever playback is counted against
+        profiles: Profiles
+    }
+
+    impl Displays {
+        /// Restores the displays saved in `path`, and adds the given displays and the members
+        /// of `groups` so remotes can select them before they connect.
+        pub fn load(
+            path: PathBuf,
+            names: &[String],
+            groups: BTreeMap<String, Vec<String>>,
+            profiles: Profiles
+        ) -> Result<Self, String> {
+            let saved : BTreeMap<String, SavedSession> = store::load(&path, MIGRATIONS)?;
+
+            let mut sessions = saved
+                .into_iter()
+                .map(|(name, saved)| (name, Session::restore(saved)))
+                .collect::<BTreeMap<_, _>>();
+
+            let configured = names
+                .iter()
+                .chain(groups.values().flatten())
+                .cloned()
+                .collect::<BTreeSet<_>>();
+
+            for name in &configured {
+                sessions.entry(name.clone()).or_default();
+            }
+
+            Ok(Self {
+                path,
+                sessions: Arc::new(RwLock::new(sessions)),
+                groups: Arc::new(groups),
+                configured: Arc::new(configured),
+                profiles
+            })
+        }
+
+        /// Runs `f` on the named display, or on the display leading the named group.
+        pub fn with<R>(&self, name: &str, f: impl FnOnce(&mut Session) -> R) -> Result<R, PlayerError> {
+            let mut displays = self.sessions.write().unwrap_or_else(PoisonError::into_inner);
+
+            let name = match self.groups.get(name) {
+                Some(members) => leader(&displays, name, members).to_string(),
+                None => name.to_string()
+            };
+
+            match displays.get_mut(&name) {
+                Some(session) => Ok(f(session)),
+                None => Err(PlayerError::NotFound(format!("The display {}", name)))
+            }
+        }
+
+        /// Runs `f` on the named display, adding it if it's new. Only displays themselves and
+        /// parties use this, so remotes can't add displays just by naming them.
+        pub fn with_or_add<R>(&self, name: &str, f: impl FnOnce(&mut Session) -> R) -> Result<R, PlayerError> {
+            if name.trim().is_empty() || name.chars().count() > MAX_NAME_LENGTH {
+                return Err(PlayerError::InvalidInput(format!("Display names need 1 to {} characters", MAX_NAME_LENGTH)));
+            }
+
+            if self.groups.contains_key(name) {
+                return Err(PlayerError::InvalidInput(format!("{} is the name of a group", name)));
+            }
+
+            let mut displays = self.sessions.write().unwrap_or_else(PoisonError::into_inner);
+            let session = displays.entry(name.to_string()).or_insert_with(|| Session {
+                seen_on: Some(Local::now().date_naive()),
+                ..Session::default()
+            });
+
+            Ok(f(session))
+        }
+
+        /// Whether the display is playing along with a group it doesn't lead, or with a
+        /// party, so its own queue shouldn't move it on.
+        pub fn is_following(&self, name: &str) -> bool {
+            if is_party(name) {
+                return true;
+            }
+
+            let displays = self.sessions.read().unwrap_or_else(PoisonError::into_inner);
+
+            let group = displays
+                .get(name)
+                .and_then(|session| session.group.as_ref())
+                .and_then(|group| Some((group, self.groups.get(group)?)));
+
+            match group {
+                Some((group, members)) => leader(&displays, group, members) != name,
+                None => false
+            }
+        }
+
+        /// Sending to a group brings all its displays into it, and sending straight to a
+        /// display takes it out of any group.
+        fn join(&self, displays: &mut BTreeMap<String, Session>, name: &str) {
+            match self.groups.get(name) {
+                Some(members) => {
+                    for session in members.iter().filter_map(|member| displays.get_mut(member)) {
+                        session.group = Some(name.to_string());
+                        // Progress and viewing time are only counted once, against the leader
+                        session.profile = None;
+                    }
+                },
+                None => {
+                    if let Some(session) = displays.get_mut(name) {
+                        if session.group.take().is_some() {
+                            let reset = session.following.leave();
+                            session.instructions.extend(reset);
+                        }
+                    }
+                }
+            }
+        }
+
+        /// Has each display in an active group follow its leader, with `f`.
+        pub fn sync(&self, mut f: impl FnMut(&Lead, &mut Session)) {
+            let mut displays = self.sessions.write().unwrap_or_else(PoisonError::into_inner);
+
+            for (group, members) in self.groups.iter() {
+                let leader = leader(&displays, group, members).to_string();
+
+                let lead = match displays.get(&leader) {
+                    Some(session) if session.group.as_ref() == Some(group) => Lead {
+                        player: session.player.clone(),
+                        queue: session.queue.clone(),
+                        position: session.position_now(1.0).unwrap_or_else(|| match &session.player {
+                            PlayerState::Media(media) => media.position,
+                            PlayerState::Idle => 0.0
+                        })
+                    },
+                    _ => continue
+                };
+
+                for member in members.iter().filter(|member| **member != leader) {
+                    if let Some(session) = displays.get_mut(member).filter(|session| session.group.as_ref() == Some(group)) {
+                        f(&lead, session);
+                    }
+                }
+            }
+        }
+
+        /// Sends an instruction from `by` to a display, as `Session::instruct`, if the display is
+        /// online. Control isn't checked, as schedules send instructions this way, and what
+        /// they start isn't counted against anyone's viewing time.
+        pub fn instruct(&self, name: &str, libraries: &Libraries, by: &Remote, i: Instruction) -> Result<(), PlayerError> {
+            let profiles = &self.profiles;
+
+            self.send_then(name, |session| send(session, name, libraries, profiles, by, i), |session| session.profile = None)
+        }
+
+        /// As `instruct`, for a remote, which is refused while another remote has control.
+        /// `f` sets whose viewing time it counts against. All under one lock, so control
+        /// can't change hands partway.
+        pub fn instruct_from(
+            &self,
+            name: &str,
+            libraries: &Libraries,
+            by: &Remote,
+            i: Instruction,
+            f: impl FnOnce(&mut Session)
+        ) -> Result<(), PlayerError> {
+            let profiles = &self.profiles;
+
+            let checked = |session: &mut Session| {
+                session.check_control(name, by)?;
+                send(session, name, libraries, profiles, by, i)
+            };
+
+            self.send_then(name, checked, f)
+        }
+
+        /// Runs `f` then `send` on the display that will lead once the named group is joined,
+        /// and only if `send` succeeds joins it, so a refused instruction leaves groups as they
+        /// were. `f` goes first as it sets the profile `send` checks for viewing time, and the
+        /// old profile is put back if `send` fails.
+        fn send_then(
+            &self,
+            name: &str,
+            send: impl FnOnce(&mut Session) -> Result<(), PlayerError>,
+            f: impl FnOnce(&mut Session)
+        ) -> Result<(), PlayerError> {
+            let mut displays = self.sessions.write().unwrap_or_else(PoisonError::into_inner);
+
+            // Once it's joined, every member has, so the first online one leads
+            let target = match self.groups.get(name) {
+                Some(members) => leader_of(&displays, members).unwrap_or(name).to_string(),
+                None => name.to_string()
+            };
+
+            let session = displays
+                .get_mut(&target)
+                .ok_or_else(|| PlayerError::NotFound(format!("The display {}", target)))?;
+
+            let previous = session.profile;
+            f(session);
+            let profile = session.profile;
+
+            if let Err(e) = send(session) {
+                session.profile = previous;
+                return Err(e);
+            }
+
+            self.join(&mut displays, name);
+
+            // Joining clears every member's profile, the leader's included
+            if let Some(session) = displays.get_mut(&target) {
+                session.profile = profile;
+            }
+
+            Ok(())
+        }
+
+        /// Moves a display through its queue for a remote, unless another remote has control,
+        /// starting the item it lands on if the display's profile has viewing time left.
+        pub fn skip(&self, name: &str, by: &Remote, offset: i32) -> Result<(), PlayerError> {
+            let action = if offset < 0 { "skipped back" } else { "skipped ahead" };
+
+            self.with(name, |session| {
+                session.check_control(name, by)?;
+
+                if let Some(profile) = session.profile {
+                    self.profiles.check_time(profile)?;
+                }
+
+                if session.queue.skip(offset).is_some() {
+                    let next = session.queue.instruction();
+                    session.instructions.push_back(next);
+                }
+
+                session.record(by, action);
+                Ok(())
+            })?
+        }
+
+        /// Runs `f` on a display for a remote, unless another remote has control of it, and
+        /// records `action` if it succeeds.
+        pub fn control<R>(
+            &self,
+            name: &str,
+            by: &Remote,
+            action: impl Into<String>,
+            f: impl FnOnce(&mut Session) -> R
+        ) -> Result<R, PlayerError> {
+            self.with(name, |session| {
+                session.check_control(name, by)?;
+
+                let result = f(session);
+                session.record(by, action);
+
+                Ok(result)
+            })?
+        }
+
+        pub fn for_each(&self, mut f: impl FnMut(&str, &mut Session)) {
+            let mut displays = self.sessions.write().unwrap_or_else(PoisonError::into_inner);
+
+            for (name, session) in displays.iter_mut() {
+                f(name, session);
+            }
+        }
+
+        /// Forgets a display, as when the party it watched for has ended.
+        pub fn remove(&self, name: &str) {
+            self.sessions.write().unwrap_or_else(PoisonError::into_inner).remove(name);
+        }
+
+        /// Every display, then every group. Party participants' displays are left out, as
+        /// remotes have no business with them.
+        pub fn names(&self) -> Vec<String> {
+            let displays = self.sessions.read().unwrap_or_else(PoisonError::into_inner);
+
+            displays
+                .keys()
+                .filter(|name| !is_party(name))
+                .chain(self.groups.keys())
+                .cloned()
+                .collect()
+        }
+
+        /// Every display's status, and each group's, which is its leader's.
+        pub fn statuses(&self) -> BTreeMap<String, Status> {
+            let displays = self.sessions.read().unwrap_or_else(PoisonError::into_inner);
+
+            let mut statuses = displays
+                .iter()
+                .filter(|(name, _)| !is_party(name))
+                .map(|(name, session)| (name.clone(), session.status()))
+                .collect::<BTreeMap<_, _>>();
+
+            for (group, members) in self.groups.iter() {
+                let mut status = statuses.get(leader(&displays, group, members)).cloned().unwrap_or_default();
+                status.members = members.clone();
+                status.group = None;
+
+                statuses.insert(group.clone(), status);
+            }
+
+            statuses
+        }
+
+        fn saved(&self) -> BTreeMap<String, SavedSession> {
+            self.sessions
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                // Parties only last as long as the server does
+                .filter(|(name, _)| !is_party(name))
+                .map(|(name, session)| (name.clone(), session.saved()))
+                .collect()
+        }
+
+        pub fn save(&self) -> std::io::Result<()> {
+            store::save(&self.path, MIGRATIONS.len(), &self.saved())
+        }
+
+        /// Forgets displays that aren't in the config and haven't checked in for a long while.
+        fn expire(&self) {
+            let mut displays = self.sessions.write().unwrap_or_else(PoisonError::into_inner);
+
+            displays.retain(|name, session| {
+                let keep = self.configured.contains(name) || !session.has_expired();
+
+                if !keep {
+                    log::warn!("Forgetting the display {}, which hasn't been seen in {} days", name, EXPIRE_AFTER_DAYS);
+                }
+
+                keep
+            });
+        }
+    }
+
+    fn is_party(name: &str) -> bool {
+        name.starts_with(PARTY_PREFIX)
+    }
+
+    /// Passes an instruction on to a display and records it in its activity feed, refusing
+    /// offline displays, as anything queued for one would all play at once when it came back,
+    /// and playback for a profile with no viewing time left.
+    fn send(
+        session: &mut Session,
+        name: &str,
+        libraries: &Libraries,
+        profiles: &Profiles,
+        by: &Remote,
+        i: Instruction
+    ) -> Result<(), PlayerError> {
+        if !session.is_online() {
+            return Err(PlayerError::DisplayOffline {
+                display: name.to_string(),
+                last_seen: session.status().last_seen
+            });
+        }
+
+        if let (true, Some(profile)) = (i.starts_playback(), session.profile) {
+            profiles.check_time(profile)?;
+        }
+
+        let action = i.describe();
+        session.instruct(libraries, i)?;
+        session.record(by, action);
+
+        Ok(())
+    }
+
+    /// The display leading a group: the first online member that has joined it, or failing
+    /// that the first online member, so there's someone to send the group's instructions to.
+    fn leader<'a>(displays: &BTreeMap<String, Session>, group: &'a str, members: &'a [String]) -> &'a str {
+        let is_online = |member: &&String| displays.get(*member).map(|session| session.is_online()).unwrap_or(false);
+        let has_joined = |member: &&String| displays.get(*member).map(|session| session.group.as_deref() == Some(group)).unwrap_or(false);
+
+        members.iter().find(|member| is_online(member) && has_joined(member))
+            .map(String::as_str)
+            .or_else(|| leader_of(displays, members))
+            .unwrap_or(group)
+    }
+
+    /// The first online member of a group, or failing that the first member.
+    fn leader_of<'a>(displays: &BTreeMap<String, Session>, members: &'a [String]) -> Option<&'a str> {
+        let is_online = |member: &&String| displays.get(*member).map(|session| session.is_online()).unwrap_or(false);
+
+        members.iter().find(is_online)
+            .or_else(|| members.first())
+            .map(String::as_str)
+    }
+
+    /// Version 2 gave media state named fields and a phase, in place of `Playing` and `Paused`
+    /// tuples of path, duration and position. The old phase is dropped, as displays were
+    /// saved with it backwards and restored ones come back paused anyway.
+    fn media_state_fields(mut data: Value) -> Result<Value, String> {
+        let sessions = data
+            .as_object_mut()
+            .ok_or_else(|| "Expected displays by name".to_string())?;
+
+        for session in sessions.values_mut() {
+            let Some(media) = session.pointer_mut("/player/Media") else {
+                continue;
+            };
+
+            let old = media
+                .get("Playing")
+                .or_else(|| media.get("Paused"))
+                .and_then(Value::as_array)
+                .cloned()
+                .ok_or_else(|| format!("Unexpected media state {}", media))?;
+
+            *media = json!({
+                "path": old.get(0).cloned().unwrap_or_default(),
+                "phase": "Paused",
+                "duration": old.get(1).cloned().unwrap_or(json!(0.0)),
+                "position": old.get(2).cloned().unwrap_or(json!(0.0)),
+                "buffered": []
+            });
+        }
+
+        Ok(data)
+    }
+
+    /// Saves the displays every few seconds when anything has changed, after forgetting any
+    /// that have expired.
+    pub async fn run(displays: Displays) {
+        let mut last = displays.saved();
+
+        loop {
+            tokio::time::sleep(SAVE_INTERVAL).await;
+
+            displays.expire();
+            let saved = displays.saved();
+
+            if saved == last {
+                continue;
+            }
+
+            match store::save(&displays.path, MIGRATIONS.len(), &saved) {
+                Ok(()) => last = saved,
+                Err(e) => log::warn!("Couldn't save displays: {}", e)
+            }
+        }
+    }
+
+    //
+    //
+    //
+}}
@@ -1,14 +1,126 @@
 use cfg_if::cfg_if;
 use http::status::StatusCode;
 use leptos::*;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
 
 #[cfg(feature = "ssr")]
 use leptos_axum::ResponseOptions;
 
+/// Everything that can go wrong serving a request. Server functions carry it to the client as
+/// JSON inside `ServerFnError::ServerError`, so remotes can show what actually happened.
+/// Their responses get `status()` where it's a server error, and a 400 for client errors, as
+/// the leptos client reads no other 4xx as a failure; media routes answer with `status()`.
+#[derive(Clone, Debug, Deserialize, Error, PartialEq, Serialize)]
+pub enum PlayerError {
+    #[error("The media library is unavailable: {0}")]
+    LibraryUnavailable(String),
+    #[error("{0} was not found")]
+    NotFound(String),
+    #[error("{}", offline_message(.display, .last_seen))]
+    DisplayOffline {
+        display: String,
+        last_seen: Option<String>
+    },
+    /// Another remote has locked control of the display
+    #[error("{holder} has control of {display}")]
+    Locked {
+        display: String,
+        holder: String
+    },
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    InvalidInstruction(String),
+    #[error("{0}")]
+    InvalidInput(String),
+    #[error("Couldn't reach the server")]
+    Unreachable,
+    /// The page was built for a different `PROTOCOL_VERSION`, and should reload
+    #[error("This page is out of date, reloading")]
+    Outdated {
+        client: u32,
+        server: u32
+    },
+    #[error("Something went wrong on the server: {0}")]
+    Internal(String)
+}
+
+impl PlayerError {
+    pub fn status(&self) -> StatusCode {
+        match self {
+            Self::LibraryUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::NotFound(_) => StatusCode::NOT_FOUND,
+            Self::DisplayOffline { .. } | Self::Outdated { .. } => StatusCode::CONFLICT,
+            Self::Locked { .. } => StatusCode::LOCKED,
+            Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
+            Self::Forbidden(_) => StatusCode::FORBIDDEN,
+            Self::InvalidInstruction(_) | Self::InvalidInput(_) => StatusCode::BAD_REQUEST,
+            Self::Unreachable => StatusCode::SERVICE_UNAVAILABLE,
+            Self::Internal(_) => StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
+
+fn offline_message(display: &str, last_seen: &Option<String>) -> String {
+    match last_seen {
+        Some(time) => format!("{} is offline, last seen {}", display, time),
+        None => format!("{} hasn't connected yet", display)
+    }
+}
+
+impl From<PlayerError> for ServerFnError {
+    fn from(error: PlayerError) -> Self {
+        let json = serde_json::to_string(&error).unwrap_or_else(|_| error.to_string());
+        ServerFnError::ServerError(json)
+    }
+}
+
+/// Recovers the error a server function failed with. Failures before it was reached, like a
+/// dropped connection, become `Unreachable`.
+impl From<ServerFnError> for PlayerError {
+    fn from(error: ServerFnError) -> Self {
+        match error {
+            ServerFnError::ServerError(message) => {
+                serde_json::from_str(&message).unwrap_or(Self::Internal(message))
+            },
+            ServerFnError::Request(_) => Self::Unreachable,
+            error => Self::Internal(error.to_string())
+        }
+    }
+}
+
+impl From<std::io::Error> for PlayerError {
+    fn from(error: std::io::Error) -> Self {
+        Self::Internal(error.to_string())
+    }
+}
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use axum::response::{IntoResponse, Response};
+
+    impl IntoResponse for PlayerError {
+        fn into_response(self) -> Response {
+            (self.status(), self.to_string()).into_response()
+        }
+    }
+
+    //
+    //
+    //
+}}
+
 #[component]
 pub fn DisplayError(
     cx: Scope,
-    error: &'static str,
+    #[prop(into)]
+    error: String,
     #[prop(optional, default = StatusCode::INTERNAL_SERVER_ERROR)]
     #[allow(unused_variables)]
     status_code: StatusCode
@@ -25,3 +137,16 @@ pub fn DisplayError(
         <p>{error}</p>
     }
 }
+
+/// Why a server function failed, as an alert.
+#[component]
+pub fn ErrorAlert(
+    cx: Scope,
+    error: ServerFnError,
+    #[prop(optional)]
+    class: &'static str
+) -> impl IntoView {
+    view! { cx,
+        <div class=format!("alert alert-danger {}", class)>{PlayerError::from(error).to_string()}</div>
+    }
+}
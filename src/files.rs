@@ -19,7 +19,10 @@ cfg_if! { if #[cfg(feature = "ssr")] {
 
     pub async fn file_handler(uri: Uri, State(options): State<LeptosOptions>, req: Request<Body>) -> AxumResponse {
         let root = options.site_root.clone();
-        let res = get_static_file(uri.clone(), &root).await.unwrap();
+        let res = match get_static_file(uri.clone(), &root).await {
+            Ok(res) => res,
+            Err(e) => return e.into_response()
+        };
 
         if res.status() == StatusCode::OK {
             res.into_response()
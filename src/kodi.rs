@@ -0,0 +1,775 @@
+use cfg_if::cfg_if;
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use crate::config::{Config, KodiConfig, SkipConfig};
+    use crate::devices::{secrets_match, Devices, Secret};
+    use crate::displays::{Displays, Session};
+    use crate::error::PlayerError;
+    use crate::library::Libraries;
+    use crate::player::*;
+    use crate::tags::read_track_info;
+    use base64::{engine::general_purpose::STANDARD, Engine};
+    use http::header::{HeaderMap, AUTHORIZATION};
+    use serde_json::{json, Value};
+    use std::collections::BTreeSet;
+    use std::net::IpAddr;
+    use thiserror::Error;
+
+    /// Kodi's player ids, one for each kind of media, which are also its playlist ids
+    static AUDIO_PLAYER : u64 = 0;
+    static VIDEO_PLAYER : u64 = 1;
+    static PICTURE_PLAYER : u64 = 2;
+    /// Seconds the big seek steps jump, as in Kodi
+    static BIG_STEP : f64 = 600.0;
+    /// Percent the volume goes up or down by
+    static VOLUME_STEP : f64 = 10.0;
+    static PARSE_ERROR : i64 = -32700;
+    static INVALID_REQUEST : i64 = -32600;
+
+    /// Why a call failed, with the JSON-RPC codes Kodi gives.
+    #[derive(Debug, Error)]
+    enum CallError {
+        #[error("Method not found: {0}")]
+        UnknownMethod(String),
+        #[error("Invalid params: {0}")]
+        InvalidParams(String),
+        #[error("Nothing is playing")]
+        NothingPlaying,
+        #[error("{0}")]
+        Failed(#[from] PlayerError)
+    }
+
+    impl CallError {
+        fn code(&self) -> i64 {
+            match self {
+                Self::UnknownMethod(_) => -32601,
+                Self::InvalidParams(_) => -32602,
+                Self::NothingPlaying | Self::Failed(_) => -32100
+            }
+        }
+    }
+
+    /// What the display is doing, as Kodi's API describes it.
+    struct Now {
+        /// Only while the display is online, with the position it should have reached
+        media: Option<MediaState>,
+        queue: Queue,
+        volume: f64
+    }
+
+    /// The subset of Kodi's JSON-RPC API that remote apps like Kore and Yatse, and Home
+    /// Assistant, need to control a display: players, the playlist, volume and browsing files.
+    /// The display's one queue is every playlist, reported as the playlist of whatever kind of
+    /// media it holds.
+    pub struct Kodi {
+        displays: Displays,
+        libraries: Libraries,
+        display: String,
+        skip: SkipConfig,
+        /// Who Kodi remotes are in the activity feed
+        remote: Remote
+    }
+
+    impl Kodi {
+        pub fn new(config: &Config, displays: Displays, libraries: Libraries) -> Self {
+            Self {
+                displays,
+                libraries,
+                display: config.kodi.display.clone(),
+                skip: config.skip.clone(),
+                remote: Remote {
+                    id: "kodi".to_string(),
+                    name: "A Kodi remote".to_string()
+                }
+            }
+        }
+
+        /// Answers a request or a batch of them, or gives nothing when there were only
+        /// notifications to carry out.
+        pub fn respond(&self, body: &str) -> Option<Value> {
+            let request = match serde_json::from_str::<Value>(body) {
+                Ok(request) => request,
+                Err(e) => return Some(error(Value::Null, PARSE_ERROR, format!("Parse error: {}", e)))
+            };
+
+            match request {
+                Value::Array(batch) => {
+                    let responses = batch.iter().filter_map(|request| self.answer(request)).collect::<Vec<_>>();
+                    (!responses.is_empty()).then_some(Value::Array(responses))
+                },
+                request => self.answer(&request)
+            }
+        }
+
+        fn answer(&self, request: &Value) -> Option<Value> {
+            let id = request.get("id").cloned();
+
+            let Some(method) = request.get("method").and_then(Value::as_str) else {
+                return Some(error(id.unwrap_or(Value::Null), INVALID_REQUEST, "Invalid request".to_string()));
+            };
+
+            let params = request.get("params").cloned().unwrap_or(Value::Null);
+            let result = self.call(method, &params);
+
+            if let Err(e) = &result {
+                log::info!("Kodi call {} failed: {}", method, e);
+            }
+
+            // Notifications are carried out, but never answered
+            let id = id?;
+
+            Some(match result {
+                Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+                Err(e) => error(id, e.code(), e.to_string())
+            })
+        }
+
+        fn call(&self, method: &str, params: &Value) -> Result<Value, CallError> {
+            match method {
+                "JSONRPC.Ping" => Ok(json!("pong")),
+                // Kodi 19's API, which current remotes expect
+                "JSONRPC.Version" => Ok(json!({ "version": { "major": 12, "minor": 0, "patch": 0 } })),
+                "Application.GetProperties" => Ok(self.application_properties(params)),
+                "Application.SetVolume" => self.set_volume(params),
+                "Player.GetActivePlayers" => Ok(self.active_players()),
+                "Player.GetProperties" => self.player_properties(params),
+                "Player.GetItem" => self.player_item(params),
+                "Player.PlayPause" => self.play_pause(params),
+                "Player.Stop" => self.instruct(Instruction::Stop),
+                "Player.Seek" => self.seek(params),
+                "Player.GoTo" => self.go_to(params),
+                "Player.SetShuffle" => self.set_shuffle(params),
+                "Player.SetRepeat" => self.set_repeat(params),
+                "Player.Open" => self.open(params),
+                "Playlist.GetPlaylists" => Ok(json!([
+                    { "playlistid": AUDIO_PLAYER, "type": "audio" },
+                    { "playlistid": VIDEO_PLAYER, "type": "video" },
+                    { "playlistid": PICTURE_PLAYER, "type": "picture" }
+                ])),
+                "Playlist.GetProperties" => self.playlist_properties(params),
+                "Playlist.GetItems" => self.playlist_items(params),
+                "Playlist.Add" => self.playlist_add(params),
+                "Playlist.Remove" => self.playlist_remove(params),
+                "Playlist.Clear" => self.control("cleared the queue", |session| session.queue.clear()),
+                "Files.GetSources" => Ok(self.sources(params)),
+                "Files.GetDirectory" => self.directory(params),
+                method => Err(CallError::UnknownMethod(method.to_string()))
+            }
+        }
+
+        /// Nothing is playing on a display that has never checked in.
+        fn now(&self) -> Now {
+            let now = self.displays.with(&self.display, |session| {
+                let media = match &session.player {
+                    PlayerState::Media(media) if session.is_online() => {
+                        let mut media = media.clone();
+
+                        if let Some(position) = session.position_now(1.0) {
+                            media.position = if media.duration > 0.0 { position.min(media.duration) } else { position };
+                        }
+
+                        Some(media)
+                    },
+                    _ => None
+                };
+
+                Now {
+                    media,
+                    queue: session.queue.clone(),
+                    volume: session.volume.unwrap_or(1.0)
+                }
+            });
+
+            now.unwrap_or_else(|_| Now { media: None, queue: Queue::default(), volume: 1.0 })
+        }
+
+        fn playing(&self) -> Result<MediaState, CallError> {
+            self.now().media.ok_or(CallError::NothingPlaying)
+        }
+
+        /// Sends an instruction as the remote's `Instruct` does, unless another remote has control.
+        /// Kodi remotes have no profile, so the display keeps the one it has, and playback
+        /// still counts against that profile's viewing time.
+        fn instruct(&self, i: Instruction) -> Result<Value, CallError> {
+            self.displays.instruct_from(&self.display, &self.libraries, &self.remote, i, |_| ())?;
+
+            Ok(json!("OK"))
+        }
+
+        fn control(&self, action: impl Into<String>, f: impl FnOnce(&mut Session)) -> Result<Value, CallError> {
+            self.displays.control(&self.display, &self.remote, action, f)?;
+            Ok(json!("OK"))
+        }
+
+        fn application_properties(&self, params: &Value) -> Value {
+            let volume = self.now().volume;
+
+            let properties = json!({
+                "volume": (volume * 100.0).round() as u64,
+                "muted": volume == 0.0,
+                "name": "Player",
+                "version": { "major": 19, "minor": 0, "revision": "", "tag": "stable" }
+            });
+
+            pick(&properties, param(params, "properties", 0))
+        }
+
+        fn set_volume(&self, params: &Value) -> Result<Value, CallError> {
+            let current = (self.now().volume * 100.0).round();
+
+            let volume = match param(params, "volume", 0) {
+                Some(Value::Number(volume)) => volume.as_f64(),
+                Some(Value::String(step)) if step == "increment" => Some(current + VOLUME_STEP),
+                Some(Value::String(step)) if step == "decrement" => Some(current - VOLUME_STEP),
+                _ => None
+            };
+
+            let volume = volume
+                .ok_or_else(|| CallError::InvalidParams("volume must be 0 to 100, increment or decrement".to_string()))?
+                .clamp(0.0, 100.0);
+
+            self.instruct(Instruction::Volume { volume: volume / 100.0 })?;
+
+            Ok(json!(volume as u64))
+        }
+
+        fn active_players(&self) -> Value {
+            match self.now().media {
+                Some(media) => {
+                    let kind = MediaKind::of(&media.path);
+                    json!([{ "playerid": player_id(kind), "playertype": "internal", "type": player_type(kind) }])
+                },
+                None => json!([])
+            }
+        }
+
+        fn player_properties(&self, params: &Value) -> Result<Value, CallError> {
+            let Now { media, queue, .. } = self.now();
+            let media = media.ok_or(CallError::NothingPlaying)?;
+            let kind = MediaKind::of(&media.path);
+
+            let percentage = if media.duration > 0.0 { media.position / media.duration * 100.0 } else { 0.0 };
+
+            let repeat = match queue.repeat {
+                Repeat::Off => "off",
+                Repeat::One => "one",
+                Repeat::All => "all"
+            };
+
+            let properties = json!({
+                "type": player_type(kind),
+                "speed": if matches!(media.phase, Phase::Playing | Phase::Buffering) { 1 } else { 0 },
+                "time": time(media.position),
+                "totaltime": time(media.duration),
+                "percentage": percentage,
+                "position": queue.position,
+                "playlistid": playlist_id(&queue),
+                "shuffled": queue.shuffle,
+                "repeat": repeat,
+                "canseek": kind != MediaKind::Image,
+                "canshuffle": true,
+                "canrepeat": true,
+                "canchangespeed": false,
+                "canmove": false,
+                "canzoom": false,
+                "canrotate": false,
+                "live": false,
+                "partymode": false,
+                "subtitleenabled": false,
+                "currentsubtitle": null,
+                "subtitles": [],
+                "currentaudiostream": null,
+                "audiostreams": [],
+                "currentvideostream": null,
+                "videostreams": []
+            });
+
+            Ok(pick(&properties, param(params, "properties", 1)))
+        }
+
+        fn player_item(&self, params: &Value) -> Result<Value, CallError> {
+            let media = self.playing()?;
+            let mut item = item(&media.path);
+
+            if let (Some(item), Some(properties)) = (item.as_object_mut(), param(params, "properties", 1).and_then(Value::as_array)) {
+                let wants = |name: &str| properties.iter().any(|property| property.as_str() == Some(name));
+
+                if wants("duration") {
+                    item.insert("duration".to_string(), json!(media.duration.round() as u64));
+                }
+
+                if MediaKind::of(&media.path) == MediaKind::Audio {
+                    if let Ok(file) = self.libraries.file(&media.path) {
+                        let info = read_track_info(&file);
+
+                        if let Some(title) = info.title {
+                            item.insert("title".to_string(), json!(title));
+                        }
+
+                        if wants("artist") {
+                            item.insert("artist".to_string(), json!(info.artist.into_iter().collect::<Vec<_>>()));
+                        }
+
+                        if wants("album") {
+                            item.insert("album".to_string(), json!(info.album.unwrap_or_default()));
+                        }
+                    }
+                }
+            }
+
+            Ok(json!({ "item": item }))
+        }
+
+        fn play_pause(&self, params: &Value) -> Result<Value, CallError> {
+            let media = self.playing()?;
+            let is_playing = matches!(media.phase, Phase::Playing | Phase::Buffering);
+            let play = toggle(param(params, "play", 1), is_playing)?;
+
+            if play != is_playing {
+                let i = match (play, media.phase) {
+                    (true, Phase::Ended) => Instruction::Play { path: media.path, from: 0.0 },
+                    (true, _) => Instruction::Play { path: media.path, from: media.position },
+                    (false, _) => Instruction::Pause { path: media.path, at: media.position }
+                };
+
+                self.instruct(i)?;
+            }
+
+            Ok(json!({ "speed": if play { 1 } else { 0 } }))
+        }
+
+        fn seek(&self, params: &Value) -> Result<Value, CallError> {
+            let media = self.playing()?;
+
+            if MediaKind::of(&media.path) == MediaKind::Image {
+                return Err(PlayerError::InvalidInput("Photos can't be seeked".to_string()).into());
+            }
+
+            let value = param(params, "value", 1).cloned().unwrap_or(Value::Null);
+
+            let step = |step: &str| match step {
+                "smallforward" => Some(media.position + self.skip.forward),
+                "smallbackward" => Some(media.position - self.skip.back),
+                "bigforward" => Some(media.position + BIG_STEP),
+                "bigbackward" => Some(media.position - BIG_STEP),
+                _ => None
+            };
+
+            let percentage = |percentage: f64| percentage / 100.0 * media.duration;
+
+            // Kodi has accepted each of these over the years
+            let to = match &value {
+                Value::Number(share) => share.as_f64().map(percentage),
+                Value::String(name) => step(name),
+                Value::Object(seek) => {
+                    if let Some(share) = seek.get("percentage").and_then(Value::as_f64) {
+                        Some(percentage(share))
+                    } else if let Some(time) = seek.get("time") {
+                        seconds(time)
+                    } else if let Some(offset) = seek.get("seconds").and_then(Value::as_f64) {
+                        Some(media.position + offset)
+                    } else if let Some(name) = seek.get("step").and_then(Value::as_str) {
+                        step(name)
+                    } else {
+                        seconds(&value)
+                    }
+                },
+                _ => None
+            };
+
+            let to = to.ok_or_else(|| CallError::InvalidParams(format!("Can't seek to {}", value)))?;
+            let to = if media.duration > 0.0 { to.clamp(0.0, media.duration) } else { to.max(0.0) };
+
+            self.instruct(Instruction::Seek { at: to })?;
+
+            Ok(json!({
+                "percentage": if media.duration > 0.0 { to / media.duration * 100.0 } else { 0.0 },
+                "time": time(to),
+                "totaltime": time(media.duration)
+            }))
+        }
+
+        fn go_to(&self, params: &Value) -> Result<Value, CallError> {
+            let to = param(params, "to", 1);
+
+            let offset = |queue: &Queue| match to {
+                Some(Value::String(to)) if to == "previous" => Some(-1),
+                Some(Value::String(to)) if to == "next" => Some(1),
+                Some(Value::Number(position)) => position.as_i64().map(|position| (position - queue.position as i64) as i32),
+                _ => None
+            };
+
+            let offset = offset(&self.now().queue)
+                .ok_or_else(|| CallError::InvalidParams("to must be previous, next or a playlist position".to_string()))?;
+
+            self.displays.skip(&self.display, &self.remote, offset)?;
+            Ok(json!("OK"))
+        }
+
+        fn set_shuffle(&self, params: &Value) -> Result<Value, CallError> {
+            use rand::seq::SliceRandom;
+
+            let shuffle = toggle(param(params, "shuffle", 1), self.now().queue.shuffle)?;
+            let action = if shuffle { "turned shuffle on" } else { "turned shuffle off" };
+
+            self.control(action, |session| {
+                if shuffle {
+                    session.queue.shuffle_with(|order| order.shuffle(&mut rand::thread_rng()));
+                } else {
+                    session.queue.unshuffle();
+                }
+            })
+        }
+
+        fn set_repeat(&self, params: &Value) -> Result<Value, CallError> {
+            let repeat = match param(params, "repeat", 1).and_then(Value::as_str) {
+                Some("off") => Repeat::Off,
+                Some("one") => Repeat::One,
+                Some("all") => Repeat::All,
+                Some("cycle") => self.now().queue.repeat.cycle(),
+                _ => return Err(CallError::InvalidParams("repeat must be off, one, all or cycle".to_string()))
+            };
+
+            let action = match repeat {
+                Repeat::Off => "turned repeat off",
+                Repeat::One => "set repeat to one item",
+                Repeat::All => "set repeat to the whole queue"
+            };
+
+            self.control(action, |session| session.queue.repeat = repeat)
+        }
+
+        fn open(&self, params: &Value) -> Result<Value, CallError> {
+            let item = param(params, "item", 0).cloned().unwrap_or(Value::Null);
+
+            if let Some(file) = item.get("file").and_then(Value::as_str) {
+                return self.instruct(Instruction::Queue { items: vec![file.to_string()], position: 0 });
+            }
+
+            if let Some(folder) = item.get("directory").and_then(Value::as_str) {
+                return self.instruct(Instruction::PlayFolder { folder: folder.trim_end_matches('/').to_string() });
+            }
+
+            if item.get("playlistid").is_some() {
+                let position = item.get("position").and_then(Value::as_u64).unwrap_or(0) as usize;
+
+                // Starts the queue where asked, so the instruction to play it leaves it be
+                let i = self.displays.with(&self.display, |session| -> Result<Instruction, PlayerError> {
+                    session.check_control(&self.display, &self.remote)?;
+
+                    if position >= session.queue.order.len() {
+                        return Err(PlayerError::InvalidInput(format!("The queue has no item {}", position + 1)));
+                    }
+
+                    session.queue.position = position;
+                    Ok(session.queue.instruction())
+                })??;
+
+                return self.instruct(i);
+            }
+
+            Err(CallError::InvalidParams("item must have a file, directory or playlistid".to_string()))
+        }
+
+        fn playlist_properties(&self, params: &Value) -> Result<Value, CallError> {
+            let queue = self.now().queue;
+            let id = playlist_param(params)?;
+
+            let kind = match id {
+                0 => "audio",
+                2 => "picture",
+                _ => "video"
+            };
+
+            let size = if id == playlist_id(&queue) { queue.order.len() } else { 0 };
+
+            Ok(pick(&json!({ "type": kind, "size": size }), param(params, "properties", 1)))
+        }
+
+        fn playlist_items(&self, params: &Value) -> Result<Value, CallError> {
+            let queue = self.now().queue;
+
+            let items = if playlist_param(params)? == playlist_id(&queue) {
+                queue.order.iter().filter_map(|ix| queue.items.get(*ix)).map(|path| item(path)).collect()
+            } else {
+                vec![]
+            };
+
+            Ok(limited("items", items, param(params, "limits", 2)))
+        }
+
+        fn playlist_add(&self, params: &Value) -> Result<Value, CallError> {
+            let pictures = playlist_param(params)? == PICTURE_PLAYER;
+
+            let items = match param(params, "item", 1) {
+                Some(Value::Array(items)) => items.iter().collect(),
+                Some(item) => vec![item],
+                None => vec![]
+            };
+
+            let mut paths = vec![];
+
+            for item in items {
+                if let Some(file) = item.get("file").and_then(Value::as_str) {
+                    self.libraries.file(file)?;
+                    paths.push(file.to_string());
+                } else if let Some(folder) = item.get("directory").and_then(Value::as_str) {
+                    let folder = folder.trim_end_matches('/');
+                    self.libraries.dir(folder)?;
+
+                    let mut files = self.libraries
+                        .list_files(folder)
+                        .into_iter()
+                        .filter(|path| (MediaKind::of(path) == MediaKind::Image) == pictures)
+                        .collect::<Vec<_>>();
+                    files.sort();
+
+                    paths.extend(files);
+                } else {
+                    return Err(CallError::InvalidParams("items must have a file or directory".to_string()));
+                }
+            }
+
+            let action = match paths.as_slice() {
+                [] => return Ok(json!("OK")),
+                [path] => format!("queued {}", file_name(path)),
+                paths => format!("queued {} items", paths.len())
+            };
+
+            self.control(action, |session| {
+                for path in paths {
+                    session.queue.push(path);
+                }
+            })
+        }
+
+        fn playlist_remove(&self, params: &Value) -> Result<Value, CallError> {
+            let position = param(params, "position", 1)
+                .and_then(Value::as_u64)
+                .ok_or_else(|| CallError::InvalidParams("position is required".to_string()))? as usize;
+
+            self.displays.with(&self.display, |session| -> Result<(), PlayerError> {
+                session.check_control(&self.display, &self.remote)?;
+
+                let removed = session.queue
+                    .remove(position)
+                    .ok_or_else(|| PlayerError::InvalidInput("That item can't be removed".to_string()))?;
+
+                session.record(&self.remote, format!("removed {} from the queue", file_name(&removed)));
+                Ok(())
+            })??;
+
+            Ok(json!("OK"))
+        }
+
+        fn sources(&self, params: &Value) -> Value {
+            let sources = self.libraries
+                .names()
+                .into_iter()
+                .map(|(id, name)| json!({ "file": format!("{}/", id), "label": name }))
+                .collect();
+
+            limited("sources", sources, param(params, "limits", 1))
+        }
+
+        fn directory(&self, params: &Value) -> Result<Value, CallError> {
+            let directory = param(params, "directory", 0)
+                .and_then(Value::as_str)
+                .ok_or_else(|| CallError::InvalidParams("directory is required".to_string()))?
+                .trim_end_matches('/');
+
+            let kind = match param(params, "media", 1).and_then(Value::as_str) {
+                Some("video") => Some(MediaKind::Video),
+                Some("music") => Some(MediaKind::Audio),
+                Some("pictures") => Some(MediaKind::Image),
+                _ => None
+            };
+
+            let limits = param(params, "limits", 4);
+
+            if directory.is_empty() {
+                let files = self.libraries
+                    .names()
+                    .into_iter()
+                    .map(|(id, name)| json!({ "file": format!("{}/", id), "filetype": "directory", "label": name, "type": "unknown" }))
+                    .collect();
+
+                return Ok(limited("files", files, limits));
+            }
+
+            self.libraries.dir(directory)?;
+
+            let prefix = format!("{}/", directory);
+            let mut folders = BTreeSet::new();
+            let mut files = vec![];
+
+            let listed = self.libraries
+                .list_files(directory)
+                .into_iter()
+                .filter(|path| kind.map(|kind| MediaKind::of(path) == kind).unwrap_or(true));
+
+            // Files are listed recursively, so anything deeper stands for the folder it's in
+            for path in listed {
+                let Some(rest) = path.strip_prefix(&prefix) else {
+                    continue;
+                };
+
+                match rest.split_once('/') {
+                    Some((folder, _)) => {
+                        folders.insert(folder.to_string());
+                    },
+                    None => files.push(path.clone())
+                }
+            }
+
+            files.sort();
+
+            let folders = folders
+                .into_iter()
+                .map(|folder| json!({ "file": format!("{}{}/", prefix, folder), "filetype": "directory", "label": folder, "type": "unknown" }));
+
+            let files = files
+                .iter()
+                .map(|path| {
+                    let mut file = item(path);
+                    file["filetype"] = json!("file");
+                    file
+                });
+
+            Ok(limited("files", folders.chain(files).collect(), limits))
+        }
+    }
+
+    /// Checks a request carries Kodi's web server password, when one is set. Kodi remotes
+    /// send it as basic auth, and the username isn't checked. Wrong passwords count towards
+    /// locking the client out, but asking without one doesn't, as remotes do that first.
+    pub fn authorize(config: &KodiConfig, devices: &Devices, client: IpAddr, headers: &HeaderMap) -> Result<(), PlayerError> {
+        let Some(password) = &config.password else {
+            return Ok(());
+        };
+
+        let given = headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Basic "))
+            .and_then(|credentials| STANDARD.decode(credentials.trim()).ok())
+            .and_then(|credentials| String::from_utf8(credentials).ok())
+            .and_then(|credentials| credentials.split_once(':').map(|(_, given)| given.to_string()))
+            .ok_or_else(|| PlayerError::Unauthorized("The Kodi password is needed".to_string()))?;
+
+        devices.attempt(client, Secret::Kodi, || {
+            match secrets_match(&given, password) {
+                true => Ok(()),
+                false => Err(PlayerError::Unauthorized("Wrong Kodi password".to_string()))
+            }
+        })
+    }
+
+    fn error(id: Value, code: i64, message: String) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "error": { "code": code, "message": message } })
+    }
+
+    /// A named parameter, or the same one given by position.
+    fn param<'a>(params: &'a Value, name: &str, index: usize) -> Option<&'a Value> {
+        match params {
+            Value::Object(params) => params.get(name),
+            Value::Array(params) => params.get(index),
+            _ => None
+        }
+    }
+
+    fn playlist_param(params: &Value) -> Result<u64, CallError> {
+        param(params, "playlistid", 0)
+            .and_then(Value::as_u64)
+            .ok_or_else(|| CallError::InvalidParams("playlistid is required".to_string()))
+    }
+
+    /// A boolean parameter, or `"toggle"` (the default) to flip `current`.
+    fn toggle(value: Option<&Value>, current: bool) -> Result<bool, CallError> {
+        match value {
+            None => Ok(!current),
+            Some(Value::String(toggle)) if toggle == "toggle" => Ok(!current),
+            Some(Value::Bool(value)) => Ok(*value),
+            Some(value) => Err(CallError::InvalidParams(format!("Expected true, false or toggle, found {}", value)))
+        }
+    }
+
+    /// Only the requested properties, as Kodi answers.
+    fn pick(all: &Value, properties: Option<&Value>) -> Value {
+        let picked = properties
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .filter_map(|name| Some((name.to_string(), all.get(name)?.clone())))
+            .collect::<serde_json::Map<_, _>>();
+
+        Value::Object(picked)
+    }
+
+    /// A list under `name`, cut down to the requested `limits` and described as Kodi does.
+    fn limited(name: &str, items: Vec<Value>, limits: Option<&Value>) -> Value {
+        let total = items.len();
+        let limit = |name: &str| limits.and_then(|limits| limits.get(name)).and_then(Value::as_u64).map(|n| n as usize);
+
+        let start = limit("start").unwrap_or(0).min(total);
+        let end = limit("end").unwrap_or(total).clamp(start, total);
+
+        json!({
+            name: items[start..end],
+            "limits": { "start": start, "end": end, "total": total }
+        })
+    }
+
+    fn item(path: &str) -> Value {
+        json!({ "file": path, "label": file_name(path), "type": "unknown" })
+    }
+
+    fn player_id(kind: MediaKind) -> u64 {
+        match kind {
+            MediaKind::Audio => AUDIO_PLAYER,
+            MediaKind::Video => VIDEO_PLAYER,
+            MediaKind::Image => PICTURE_PLAYER
+        }
+    }
+
+    fn player_type(kind: MediaKind) -> &'static str {
+        match kind {
+            MediaKind::Audio => "audio",
+            MediaKind::Video => "video",
+            MediaKind::Image => "picture"
+        }
+    }
+
+    /// The playlist the queue is shown as, by what's playing or, failing that, what's first.
+    fn playlist_id(queue: &Queue) -> u64 {
+        queue.current()
+            .or_else(|| queue.items.first())
+            .map(|path| player_id(MediaKind::of(path)))
+            .unwrap_or(VIDEO_PLAYER)
+    }
+
+    fn time(seconds: f64) -> Value {
+        let millis = (seconds.max(0.0) * 1000.0).round() as u64;
+
+        json!({
+            "hours": millis / 3_600_000,
+            "minutes": millis / 60_000 % 60,
+            "seconds": millis / 1000 % 60,
+            "milliseconds": millis % 1000
+        })
+    }
+
+    fn seconds(time: &Value) -> Option<f64> {
+        let part = |name: &str| time.get(name).and_then(Value::as_f64).unwrap_or(0.0);
+        time.is_object().then(|| part("hours") * 3600.0 + part("minutes") * 60.0 + part("seconds") + part("milliseconds") / 1000.0)
+    }
+
+    //
+    //
+    //
+}}
@@ -1,11 +1,31 @@
 use cfg_if::cfg_if;
 pub mod app;
+pub mod config;
+pub mod control;
+pub mod devices;
 pub mod display;
+pub mod displays;
 pub mod error;
 pub mod files;
+pub mod icons;
+pub mod kodi;
+pub mod library;
 pub mod loading;
+pub mod pair;
+pub mod parties;
+pub mod party;
+pub mod photos;
 pub mod player;
+pub mod profiles;
 pub mod remote;
+pub mod schedule;
+pub mod sleep;
+pub mod socket;
+pub mod store;
+pub mod sync;
+pub mod tags;
+pub mod tls;
+pub mod toast;
 
 cfg_if! { if #[cfg(feature = "hydrate")] {
     //
@@ -15,6 +35,7 @@ cfg_if! { if #[cfg(feature = "hydrate")] {
     use leptos::*;
     use wasm_bindgen::prelude::wasm_bindgen;
     use crate::app::*;
+    use crate::socket::BASE_PATH;
 
     #[wasm_bindgen]
     pub fn hydrate() {
@@ -25,6 +46,14 @@ cfg_if! { if #[cfg(feature = "hydrate")] {
         leptos::mount_to_body(move |cx| {
             view! { cx, <App/> }
         });
+
+        // Lets the remote be installed and open without waiting on the network. Browsers
+        // leave `serviceWorker` undefined outside secure contexts.
+        let workers = window().navigator().service_worker();
+
+        if !workers.is_undefined() {
+            let _ = workers.register(&format!("{}/sw.js", BASE_PATH));
+        }
     }
 
     //
@@ -0,0 +1,403 @@
+use cfg_if::cfg_if;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+/// A library and the files in it. Media paths are namespaced by library, as
+/// `<library id>/<path within the library>`, so the same file name can exist in two libraries.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Listing {
+    pub id: String,
+    pub name: String,
+    pub files: Vec<String>
+}
+
+/// A WebVTT subtitle file alongside a video.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Subtitle {
+    pub path: String,
+    /// From the file name, e.g. "en" for `Film.en.vtt`
+    pub language: Option<String>
+}
+
+/// Splits a media path into its library id and the path within that library.
+pub fn split_path(path: &str) -> (&str, &str) {
+    path.split_once('/').unwrap_or((path, ""))
+}
+
+#[server(ListSubtitles, "/api")]
+pub async fn list_subtitles(cx: Scope, path: String) -> Result<Vec<Subtitle>, ServerFnError> {
+    let libraries = crate::profiles::profile_libraries(cx)?;
+    Ok(libraries.subtitles(&path)?)
+}
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use crate::config::Config;
+    use crate::error::PlayerError;
+    use crate::player::MediaKind;
+    use crate::profiles::Restrictions;
+    use std::path::{Component, Path, PathBuf};
+    use std::sync::Arc;
+
+    #[derive(Clone, Debug)]
+    pub struct LibraryRoot {
+        pub id: String,
+        pub name: String,
+        pub path: PathBuf,
+        /// `path` with symlinks resolved, which every file must be inside
+        canonical: PathBuf,
+        follow_symlinks: bool
+    }
+
+    /// The configured libraries, and which files in them are listed.
+    #[derive(Clone, Debug)]
+    pub struct Libraries {
+        roots: Arc<Vec<LibraryRoot>>,
+        /// Lowercase extensions without the dot. Everything is allowed if empty.
+        extensions: Arc<Vec<String>>,
+        /// Limits for a kid profile, which hide everything else
+        restrictions: Option<Arc<Restrictions>>
+    }
+
+    impl Libraries {
+        pub fn new(config: &Config) -> Self {
+            let roots = config.libraries
+                .iter()
+                .map(|library| LibraryRoot {
+                    id: library.id(),
+                    name: library.name.clone(),
+                    path: library.path.clone(),
+                    canonical: library.path.canonicalize().unwrap_or_else(|_| library.path.clone()),
+                    follow_symlinks: library.follow_symlinks
+                })
+                .collect();
+
+            Self {
+                roots: Arc::new(roots),
+                extensions: Arc::new(config.extensions.clone()),
+                restrictions: None
+            }
+        }
+
+        /// The same libraries, limited to what a kid profile may see.
+        pub fn restricted(&self, restrictions: Restrictions) -> Self {
+            Self {
+                restrictions: Some(Arc::new(restrictions)),
+                ..self.clone()
+            }
+        }
+
+        /// The file a client supplied media path refers to, if it's one the library lists, or a
+        /// subtitle beside one.
+        pub fn file(&self, path: &str) -> Result<PathBuf, PlayerError> {
+            let file = self.resolve(path)?;
+
+            if !file.is_file() || is_nfo(path) || !(self.allows(path) || is_subtitle(path)) {
+                return Err(PlayerError::InvalidInput(format!("{} is not a playable file", path)));
+            }
+
+            if !self.permits(path) {
+                return Err(PlayerError::Forbidden(format!("{} isn't available to this profile", path)));
+            }
+
+            Ok(file)
+        }
+
+        /// As `file`, for routes that only make sense for one kind of media, like album art.
+        pub fn media(&self, path: &str, kind: MediaKind) -> Result<PathBuf, PlayerError> {
+            if MediaKind::of(path) != kind || is_subtitle(path) {
+                return Err(PlayerError::InvalidInput(format!("{} is not {}", path, kind.describe())));
+            }
+
+            self.file(path)
+        }
+
+        /// The folder a client supplied media path refers to.
+        pub fn dir(&self, path: &str) -> Result<PathBuf, PlayerError> {
+            let dir = self.resolve(path)?;
+
+            if !dir.is_dir() {
+                return Err(PlayerError::InvalidInput(format!("{} is not a folder", path)));
+            }
+
+            if !self.restrictions.as_ref().map(|r| r.reaches(path)).unwrap_or(true) {
+                return Err(PlayerError::Forbidden(format!("{} isn't available to this profile", path)));
+            }
+
+            Ok(dir)
+        }
+
+        /// Canonicalises a media path, rejecting any that escape the library root, whether by
+        /// `..` or through a symlink. Unless the library follows symlinks, paths through one are
+        /// refused even if it stays inside, as listing doesn't follow them either.
+        fn resolve(&self, path: &str) -> Result<PathBuf, PlayerError> {
+            let (id, relative) = split_path(path);
+
+            let root = self.roots
+                .iter()
+                .find(|root| root.id == id)
+                .ok_or_else(|| PlayerError::NotFound(format!("Library '{}'", id)))?;
+
+            let components = Path::new(relative)
+                .components()
+                .filter(|c| *c != Component::CurDir)
+                .collect::<Vec<_>>();
+
+            if !components.iter().all(|c| matches!(c, Component::Normal(_))) {
+                return Err(PlayerError::InvalidInput(format!("Invalid path {}", path)));
+            }
+
+            // A missing root is usually an unmounted drive or share, rather than a bad path
+            if !root.path.is_dir() {
+                return Err(PlayerError::LibraryUnavailable(root.name.clone()));
+            }
+
+            let resolved = root.path
+                .join(relative)
+                .canonicalize()
+                .map_err(|_| PlayerError::NotFound(path.to_string()))?;
+
+            if !root.follow_symlinks && !resolved.starts_with(&root.canonical) {
+                return Err(PlayerError::Forbidden(format!("{} is outside its library", path)));
+            }
+
+            // Anything else resolving elsewhere went through a symlink inside the library
+            if !root.follow_symlinks && resolved != root.canonical.join(components.iter().collect::<PathBuf>()) {
+                return Err(PlayerError::NotFound(path.to_string()));
+            }
+
+            Ok(resolved)
+        }
+
+        pub fn allows(&self, path: &str) -> bool {
+            let extension = path
+                .rsplit_once('.')
+                .map(|(_, ext)| ext.to_lowercase())
+                .unwrap_or_default();
+
+            self.extensions.is_empty() || self.extensions.contains(&extension)
+        }
+
+        /// Whether the profile these libraries are restricted to, if any, may see a file.
+        fn permits(&self, path: &str) -> bool {
+            let Some(restrictions) = &self.restrictions else {
+                return true;
+            };
+
+            if !restrictions.allows_path(path) {
+                return false;
+            }
+
+            if restrictions.max_rating.is_none() || is_subtitle(path) || MediaKind::of(path) != MediaKind::Video {
+                return true;
+            }
+
+            restrictions.allows_rating(self.rating(path).as_deref())
+        }
+
+        /// A video's rating, from the `<mpaa>` tag of `Film.nfo` or `movie.nfo` beside `Film.mkv`.
+        pub fn rating(&self, path: &str) -> Option<String> {
+            let file = self.resolve(path).ok()?;
+            let stem = file.file_stem()?;
+            let dir = file.parent()?;
+
+            [dir.join(format!("{}.nfo", stem.to_string_lossy())), dir.join("movie.nfo")]
+                .iter()
+                .filter_map(|nfo| std::fs::read_to_string(nfo).ok())
+                .find_map(|nfo| {
+                    let (_, rest) = nfo.split_once("<mpaa>")?;
+                    let (rating, _) = rest.split_once("</mpaa>")?;
+
+                    Some(rating.trim().to_string()).filter(|rating| !rating.is_empty())
+                })
+        }
+
+        /// Lists the allowed files below the media path `dir`, or in every library if it's empty.
+        pub fn list_files(&self, dir: &str) -> Vec<String> {
+            let (id, dir) = split_path(dir);
+
+            self.roots
+                .iter()
+                .filter(|root| id.is_empty() || root.id == id)
+                .flat_map(|root| self.list_root(root, dir))
+                .collect()
+        }
+
+        /// Each library's id and name, without listing any files.
+        pub fn names(&self) -> Vec<(String, String)> {
+            self.roots
+                .iter()
+                .map(|root| (root.id.clone(), root.name.clone()))
+                .collect()
+        }
+
+        /// Every library that can be read. Fails only if none can, so one unmounted drive
+        /// doesn't hide the rest.
+        pub fn listings(&self) -> Result<Vec<Listing>, PlayerError> {
+            let (available, unavailable) : (Vec<_>, Vec<_>) = self.roots
+                .iter()
+                .partition(|root| root.path.is_dir());
+
+            for root in &unavailable {
+                log::warn!("Library '{}' at {} is unavailable", root.name, root.path.display());
+            }
+
+            if available.is_empty() {
+                let names = unavailable.iter().map(|root| root.name.as_str()).collect::<Vec<_>>();
+                return Err(PlayerError::LibraryUnavailable(names.join(", ")));
+            }
+
+            Ok(available
+                .into_iter()
+                .map(|root| Listing {
+                    id: root.id.clone(),
+                    name: root.name.clone(),
+                    files: self.list_root(root, "")
+                })
+                .collect())
+        }
+
+        fn list_root(&self, root: &LibraryRoot, dir: &str) -> Vec<String> {
+            walkdir::WalkDir::new(root.path.join(dir))
+                .follow_links(root.follow_symlinks)
+                .into_iter()
+                .filter_map(|file| file.ok())
+                .filter(|e| e.metadata().map(|m| m.is_file()).unwrap_or(false))
+                .filter_map(|e| e.path().strip_prefix(&root.path).ok().map(|p| format!("{}/{}", root.id, p.display())))
+                .filter(|path| !is_subtitle(path) && !is_nfo(path) && self.allows(path) && self.permits(path))
+                .collect()
+        }
+
+        /// Subtitles for a video, named like it, e.g. `Film.vtt` or `Film.en.vtt` for `Film.mkv`.
+        pub fn subtitles(&self, path: &str) -> Result<Vec<Subtitle>, PlayerError> {
+            self.file(path)?;
+
+            let (dir, name) = path.rsplit_once('/').unwrap_or((path, ""));
+            let stem = name.rsplit_once('.').map(|(stem, _)| stem).unwrap_or(name);
+
+            let mut subtitles = std::fs::read_dir(self.dir(dir)?)?
+                .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+                .filter_map(|file_name| {
+                    let language = match file_name.strip_prefix(stem)?.strip_suffix(".vtt")? {
+                        "" => None,
+                        language => Some(language.strip_prefix('.')?.to_string())
+                    };
+
+                    Some(Subtitle {
+                        path: format!("{}/{}", dir, file_name),
+                        language
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            subtitles.sort_by(|a, b| a.path.cmp(&b.path));
+            Ok(subtitles)
+        }
+    }
+
+    fn is_subtitle(path: &str) -> bool {
+        path.to_lowercase().ends_with(".vtt")
+    }
+
+    fn is_nfo(path: &str) -> bool {
+        path.to_lowercase().ends_with(".nfo")
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use crate::config::LibraryConfig;
+
+        /// A library holding `Film.mkv` and its `Film.nfo`, beside a file outside it, in a
+        /// fresh directory for one test.
+        fn library(test: &str, follow_symlinks: bool) -> (Libraries, PathBuf) {
+            let dir = std::env::temp_dir().join(format!("player-library-{}-{}", test, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+
+            let root = dir.join("films");
+            std::fs::create_dir_all(root.join("Old")).unwrap();
+            std::fs::write(root.join("Film.mkv"), "").unwrap();
+            std::fs::write(root.join("Film.nfo"), "<mpaa>PG</mpaa>").unwrap();
+            std::fs::write(dir.join("secret.mkv"), "").unwrap();
+
+            let config = Config {
+                libraries: vec![LibraryConfig {
+                    id: None,
+                    name: "Films".to_string(),
+                    path: root.clone(),
+                    follow_symlinks
+                }],
+                ..Config::default()
+            };
+
+            (Libraries::new(&config), dir)
+        }
+
+        #[test]
+        fn finds_files_in_their_library() {
+            let (libraries, dir) = library("finds", false);
+
+            assert_eq!(libraries.file("films/Film.mkv"), Ok(dir.join("films/Film.mkv").canonicalize().unwrap()));
+            assert_eq!(libraries.file("films/./Film.mkv"), Ok(dir.join("films/Film.mkv").canonicalize().unwrap()));
+            assert!(libraries.dir("films/Old").is_ok());
+            assert!(libraries.dir("films").is_ok());
+        }
+
+        #[test]
+        fn refuses_paths_that_climb_out() {
+            let (libraries, _) = library("climb", false);
+
+            assert!(matches!(libraries.file("films/../secret.mkv"), Err(PlayerError::InvalidInput(_))));
+            assert!(matches!(libraries.file("films/Old/../../secret.mkv"), Err(PlayerError::InvalidInput(_))));
+            assert!(matches!(libraries.file("films//etc/passwd"), Err(PlayerError::InvalidInput(_))));
+            assert!(matches!(libraries.dir("films/.."), Err(PlayerError::InvalidInput(_))));
+        }
+
+        #[test]
+        fn refuses_unknown_libraries_and_files() {
+            let (libraries, _) = library("unknown", false);
+
+            assert!(matches!(libraries.file("music/Film.mkv"), Err(PlayerError::NotFound(_))));
+            assert!(matches!(libraries.file("films/Missing.mkv"), Err(PlayerError::NotFound(_))));
+            assert!(matches!(libraries.file("films/Old"), Err(PlayerError::InvalidInput(_))));
+        }
+
+        #[test]
+        fn refuses_files_the_library_doesnt_list() {
+            let (libraries, _) = library("unlisted", false);
+
+            assert!(matches!(libraries.file("films/Film.nfo"), Err(PlayerError::InvalidInput(_))));
+            assert!(matches!(libraries.media("films/Film.mkv", MediaKind::Audio), Err(PlayerError::InvalidInput(_))));
+        }
+
+        #[cfg(unix)]
+        #[test]
+        fn refuses_symlinks_unless_followed() {
+            use std::os::unix::fs::symlink;
+
+            for follow_symlinks in [false, true] {
+                let (libraries, dir) = library(&format!("symlinks-{}", follow_symlinks), follow_symlinks);
+                symlink(dir.join("secret.mkv"), dir.join("films/Escape.mkv")).unwrap();
+                symlink(dir.join("films/Film.mkv"), dir.join("films/Alias.mkv")).unwrap();
+
+                let escape = libraries.file("films/Escape.mkv");
+                let alias = libraries.file("films/Alias.mkv");
+
+                if follow_symlinks {
+                    assert!(escape.is_ok());
+                    assert!(alias.is_ok());
+                } else {
+                    assert!(matches!(escape, Err(PlayerError::Forbidden(_))));
+                    assert!(matches!(alias, Err(PlayerError::NotFound(_))));
+                }
+            }
+        }
+    }
+
+    //
+    //
+    //
+}}
@@ -7,91 +7,338 @@ cfg_if! {
         //
 
         use axum::{
-            response::{Response, IntoResponse},
-            routing::get,
-            extract::{FromRef, Path, State, RawQuery},
-            http::{Request, header::HeaderMap},
-            body::Body as AxumBody,
+            middleware::{self, Next},
+            response::{Response, IntoResponse, Redirect},
+            routing::{get, post},
+            extract::{ConnectInfo, FromRef, Path, Query, State, RawQuery},
+            http::{Request, StatusCode, header::{self, HeaderMap}},
+            body::{boxed, Body as AxumBody, Full, HttpBody},
             Router
         };
         use clap::Parser;
         use leptos::*;
         use leptos_axum::{generate_route_list, handle_server_fns_with_context, LeptosRoutes};
         use player::app::*;
+        use player::config::{Config, LibraryConfig};
+        use player::devices::{token_from_headers, Client, Devices, PairDevice};
         use player::files::file_handler;
-        use player::player::{Instruction, MediaRoot, PlayerState};
-        use std::sync::{Arc, RwLock};
+        use player::kodi::Kodi;
+        use player::displays::Displays;
+        use player::error::PlayerError;
+        use player::library::Libraries;
+        use player::parties::Parties;
+        use player::player::{MediaKind, Status, PROTOCOL_VERSION};
+        use player::profiles::{libraries_for, Profiles};
+        use player::schedule::Schedules;
+        use player::socket::{Hello, BASE_PATH};
+        use player::tags::read_album_art;
+        use std::collections::BTreeMap;
+        use std::net::{IpAddr, SocketAddr};
+        use std::path::PathBuf;
         use tower::ServiceExt;
-        use tower_http::services::ServeDir;
+        use tower_http::services::ServeFile;
 
+        /// Options here override the config file.
         #[derive(Clone, Parser)]
         struct Args {
-            #[arg(long = "root", default_value = "dev-media")]
-            media_root: String
+            /// TOML config file
+            #[arg(long, env = "PLAYER_CONFIG")]
+            config: Option<PathBuf>,
+            /// Serve a single library from this directory, in place of those in the config
+            #[arg(long = "root", env = "PLAYER_ROOT")]
+            media_root: Option<PathBuf>,
+            #[arg(long = "data", env = "PLAYER_DATA")]
+            data_dir: Option<PathBuf>,
+            #[arg(long, env = "PLAYER_ADDRESS")]
+            address: Option<IpAddr>,
+            #[arg(long, env = "PLAYER_PORT")]
+            port: Option<u16>
         }
-        
+
         #[derive(Clone, FromRef)]
         struct AppState {
-            args: Args,
+            config: Config,
+            libraries: Libraries,
             leptos: LeptosOptions,
-            player: Arc<RwLock<PlayerState>>,
-            instruction: Arc<RwLock<Option<Instruction>>>
+            displays: Displays,
+            parties: Parties,
+            schedules: Schedules,
+            devices: Devices,
+            profiles: Profiles
         }
         
         #[tokio::main]
         async fn main() {
             simple_logger::init_with_level(log::Level::Warn).expect("couldn't initialize logging");
 
-            let args = Args::parse();
-        
             let conf = get_configuration(None).await.unwrap();
-            let leptos_options = conf.leptos_options;
+            let mut leptos_options = conf.leptos_options;
+
+            let config = match load_config(Args::parse(), leptos_options.site_addr) {
+                Ok(config) => config,
+                Err(e) => {
+                    eprintln!("Invalid configuration:\n{}", e);
+                    std::process::exit(1);
+                }
+            };
+
+            leptos_options.site_addr = SocketAddr::new(
+                config.server.address.unwrap_or(leptos_options.site_addr.ip()),
+                config.server.port.unwrap_or(leptos_options.site_addr.port())
+            );
+
             let addr = leptos_options.site_addr;
             let routes = generate_route_list(|cx| view! { cx, <App/> }).await;
-            let media_server =  ServeDir::new(String::from(&args.media_root));
-        
+
+            let libraries = Libraries::new(&config);
+
+            let tls = match config.tls.is_enabled() {
+                true => Some(player::tls::load(&config).await.unwrap_or_else(|e| {
+                    eprintln!("{}", e);
+                    std::process::exit(1);
+                })),
+                false => None
+            };
+
+            if let (Some(_), Some(port)) = (&tls, config.tls.redirect_port) {
+                tokio::spawn(player::tls::redirect_to_https(addr.ip(), port, addr.port()));
+            }
+
+            let schedules = config.data_dir.join("schedules.json");
+            let schedules = Schedules::load(schedules, &config.schedules).expect("couldn't load schedules");
+
+            let devices = config.data_dir.join("devices.json");
+            let devices = Devices::load(devices).expect("couldn't load devices");
+
+            let profiles = config.data_dir.join("profiles.json");
+            let profiles = Profiles::load(profiles).expect("couldn't load profiles");
+
+            let displays = config.data_dir.join("displays.json");
+            let groups = config.groups
+                .iter()
+                .map(|group| (group.name.clone(), group.displays.clone()))
+                .collect();
+            let mut known = config.displays.clone();
+
+            // Kodi remotes can't add their display by playing on it, so it's always known
+            if config.kodi.enabled && !config.groups.iter().any(|group| group.name == config.kodi.display) {
+                known.push(config.kodi.display.clone());
+            }
+
+            let displays = Displays::load(displays, &known, groups, profiles.clone()).expect("couldn't load displays");
+
             let app_state = AppState {
-                args,
+                displays,
+                parties: Parties::default(),
+                config,
+                libraries,
                 leptos: leptos_options,
-                player: Arc::new(RwLock::new(PlayerState::Idle)),
-                instruction: Arc::new(RwLock::new(None))
+                schedules,
+                devices,
+                profiles
             };
+
+            tokio::spawn(player::displays::run(app_state.displays.clone()));
+            tokio::spawn(player::sleep::run(app_state.displays.clone()));
+            tokio::spawn(player::sync::run(app_state.displays.clone(), app_state.config.sync));
+            tokio::spawn(player::parties::run(
+                app_state.parties.clone(),
+                app_state.displays.clone(),
+                app_state.config.sync
+            ));
+            tokio::spawn(player::profiles::run(app_state.profiles.clone(), app_state.displays.clone()));
+            tokio::spawn(player::schedule::run(
+                app_state.schedules.clone(),
+                app_state.displays.clone(),
+                app_state.libraries.clone()
+            ));
         
+            let displays = app_state.displays.clone();
+            let profiles = app_state.profiles.clone();
+
             let app = Router::new()
                 .route("/api/*fn_name", get(server_fn_handler).post(server_fn_handler))
                 .route("/ws", get(websocket))
+                .route("/jsonrpc", get(jsonrpc).post(jsonrpc))
+                .route("/art/*path", get(album_art))
+                .route("/play/*path", get(play))
                 .leptos_routes_with_handler(routes, get(leptos_routes_handler))
-                .nest_service(
-                    "/play",
-                    get(move |request| media_server.oneshot(request))
-                )
+                .route_layer(middleware::from_fn_with_state(app_state.clone(), require_device))
                 .fallback(file_handler)
                 .with_state(app_state);
         
-            axum::Server::bind(&addr)
-                .serve(app.into_make_service())
-                .await
-                .unwrap();
+            // The remote's socket URL follows the page, so HTTPS pages get WSS
+            let server = async move {
+                match tls {
+                    Some(tls) => {
+                        axum_server::bind_rustls(addr, tls)
+                            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                            .await
+                            .unwrap();
+                    },
+                    None => {
+                        axum::Server::bind(&addr)
+                            .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+                            .await
+                            .unwrap();
+                    }
+                }
+            };
+
+            tokio::select! {
+                _ = server => (),
+                _ = shutdown_signal() => ()
+            }
+
+            // Save anything not yet written, so nothing is lost between periodic saves
+            if let Err(e) = displays.save() {
+                log::error!("Couldn't save displays: {}", e);
+            }
+
+            if let Err(e) = profiles.flush() {
+                log::error!("Couldn't save profiles: {}", e);
+            }
+        }
+
+        /// Resolves on Ctrl+C, or when a service manager asks the server to stop.
+        async fn shutdown_signal() {
+            #[cfg(unix)]
+            {
+                use tokio::signal::unix::{signal, SignalKind};
+
+                let mut terminate = signal(SignalKind::terminate()).expect("couldn't listen for SIGTERM");
+
+                tokio::select! {
+                    _ = tokio::signal::ctrl_c() => (),
+                    _ = terminate.recv() => ()
+                }
+            }
+
+            #[cfg(not(unix))]
+            let _ = tokio::signal::ctrl_c().await;
         }
         
+        /// Reads the config file, applies command line and environment overrides, and validates the result.
+        /// Leptos's `site_addr` fills in the port if it's unset, so it's checked as it will be bound.
+        fn load_config(args: Args, site_addr: SocketAddr) -> Result<Config, String> {
+            let mut config = Config::load(args.config.as_deref())?;
+
+            if let Some(path) = args.media_root {
+                config.libraries = vec![LibraryConfig {
+                    id: None,
+                    name: "Media".to_string(),
+                    path,
+                    follow_symlinks: false
+                }];
+            }
+
+            if let Some(data_dir) = args.data_dir {
+                config.data_dir = data_dir;
+            }
+
+            if let Some(address) = args.address {
+                config.server.address = Some(address);
+            }
+
+            if let Some(port) = args.port {
+                config.server.port = Some(port);
+            }
+
+            config.server.port.get_or_insert(site_addr.port());
+
+            config.validate()
+        }
+
+        /// With auth on, only paired devices get past this. Everything else is sent to pair,
+        /// or refused outright for API, socket and media requests.
+        async fn require_device<B>(State(app_state): State<AppState>, request: Request<B>, next: Next<B>) -> Response {
+            let path = request.uri().path();
+
+            // Kodi remotes can't pair, and give a password instead
+            let is_open = !app_state.config.auth.enabled
+                || path == "/pair"
+                || path == "/jsonrpc"
+                || path == format!("/api/{}", PairDevice::url());
+
+            let is_paired = token_from_headers(request.headers())
+                .and_then(|token| app_state.devices.authenticate(token))
+                .is_some();
+
+            if is_open || is_paired {
+                return next.run(request).await;
+            }
+
+            let is_page = !["/api/", "/art/", "/play/"].iter().any(|prefix| path.starts_with(prefix)) && path != "/ws";
+
+            if is_page {
+                let back_to = request.uri().path_and_query().map(|p| p.as_str()).unwrap_or("/");
+                let back_to = url::form_urlencoded::byte_serialize(back_to.as_bytes()).collect::<String>();
+
+                Redirect::to(&format!("{}/pair?next={}", BASE_PATH, back_to)).into_response()
+            } else {
+                PlayerError::Unauthorized("Pair this device first".to_string()).into_response()
+            }
+        }
+
         async fn server_fn_handler(
             State(app_state): State<AppState>,
+            ConnectInfo(addr): ConnectInfo<SocketAddr>,
             path: Path<String>,
             headers: HeaderMap,
             raw_query: RawQuery,
             request: Request<AxumBody>
-        ) -> impl IntoResponse {
-            handle_server_fns_with_context(
+        ) -> Response {
+            let response = handle_server_fns_with_context(
                 path,
                 headers,
                 raw_query,
                 move |cx| {
-                    provide_context(cx, MediaRoot(app_state.args.media_root.clone()));
-                    provide_context(cx, app_state.player.clone());
-                    provide_context(cx, app_state.instruction.clone());
+                    provide_context(cx, app_state.config.clone());
+                    provide_context(cx, app_state.libraries.clone());
+                    provide_context(cx, app_state.displays.clone());
+                    provide_context(cx, app_state.parties.clone());
+                    provide_context(cx, app_state.schedules.clone());
+                    provide_context(cx, app_state.devices.clone());
+                    provide_context(cx, app_state.profiles.clone());
+                    provide_context(cx, Client(addr.ip()));
                 },
                 request
-            ).await
+            ).await;
+
+            with_error_status(response.into_response()).await
+        }
+
+        /// Server functions always fail with a 500. Gives failures from a `PlayerError` its
+        /// status instead, as far as the leptos client allows: it reads errors from 5xx
+        /// responses, and a 400's body as the error, but takes any other status as success, so
+        /// other client errors are sent as 400s.
+        async fn with_error_status(response: Response) -> Response {
+            if response.status() != StatusCode::INTERNAL_SERVER_ERROR {
+                return response;
+            }
+
+            let (mut parts, mut body) = response.into_parts();
+            let mut bytes = Vec::new();
+
+            while let Some(chunk) = body.data().await {
+                match chunk {
+                    Ok(chunk) => bytes.extend_from_slice(&chunk),
+                    Err(_) => return StatusCode::INTERNAL_SERVER_ERROR.into_response()
+                }
+            }
+
+            let error = serde_json::from_slice::<ServerFnError>(&bytes).ok().map(PlayerError::from);
+
+            match error {
+                Some(error) if error.status().is_client_error() => {
+                    parts.status = StatusCode::BAD_REQUEST;
+                    bytes = serde_json::to_vec(&error).unwrap_or_default();
+                },
+                Some(error) => parts.status = error.status(),
+                None => ()
+            }
+
+            Response::from_parts(parts, boxed(Full::from(bytes)))
         }
         
         async fn leptos_routes_handler(
@@ -101,9 +348,13 @@ cfg_if! {
             let handler = leptos_axum::render_app_to_stream_with_context(
                 app_state.leptos.clone(),
                 move |cx| {
-                    provide_context(cx, MediaRoot(app_state.args.media_root.clone()));
-                    provide_context(cx, app_state.player.clone());
-                    provide_context(cx, app_state.instruction.clone());
+                    provide_context(cx, app_state.config.clone());
+                    provide_context(cx, app_state.libraries.clone());
+                    provide_context(cx, app_state.displays.clone());
+                    provide_context(cx, app_state.parties.clone());
+                    provide_context(cx, app_state.schedules.clone());
+                    provide_context(cx, app_state.devices.clone());
+                    provide_context(cx, app_state.profiles.clone());
                 },
                 |cx| view! { cx, <App/> }
             );
@@ -111,20 +362,100 @@ cfg_if! {
             handler(req).await.into_response()
         }
 
-        async fn websocket(State(state) : State<AppState>, ws: axum::extract::WebSocketUpgrade) -> axum::response::Response {
-            ws.on_upgrade(|socket| handle_socket(state, socket))
+        async fn album_art(State(app_state): State<AppState>, Path(path): Path<String>, headers: HeaderMap) -> Response {
+            let libraries = libraries_for(&app_state.libraries, &app_state.profiles, &app_state.devices, app_state.config.auth.enabled, &headers);
+
+            let file = match libraries.and_then(|libraries| libraries.media(&path, MediaKind::Audio)) {
+                Ok(file) => file,
+                Err(e) => return e.into_response()
+            };
+
+            match read_album_art(&file) {
+                Some((mime, data)) => ([(header::CONTENT_TYPE, mime)], data).into_response(),
+                None => PlayerError::NotFound(format!("Album art for {}", path)).into_response()
+            }
         }
 
-        async fn handle_socket(state: AppState, mut socket: axum::extract::ws::WebSocket) {
-            let mut signal = leptos_server_signal::ServerSignal::<PlayerState>::new("player_state").unwrap();
+        /// Serves a media file, checking the path the same way as instructions so nothing
+        /// outside the libraries, or hidden from the browser's profile, can be fetched.
+        async fn play(State(app_state): State<AppState>, Path(path): Path<String>, request: Request<AxumBody>) -> Response {
+            let libraries = libraries_for(&app_state.libraries, &app_state.profiles, &app_state.devices, app_state.config.auth.enabled, request.headers());
+
+            let path = match libraries.and_then(|libraries| libraries.file(&path)) {
+                Ok(path) => path,
+                Err(e) => return e.into_response()
+            };
+
+            match ServeFile::new(path).oneshot(request).await {
+                Ok(response) => response.map(boxed).into_response(),
+                Err(e) => match e {}
+            }
+        }
+
+        /// Kodi's JSON-RPC API, when enabled, for remote apps built for Kodi. Calls are POSTed,
+        /// or sent in a `request` query parameter as Kodi also takes them.
+        async fn jsonrpc(
+            State(app_state): State<AppState>,
+            ConnectInfo(addr): ConnectInfo<SocketAddr>,
+            Query(params): Query<BTreeMap<String, String>>,
+            headers: HeaderMap,
+            body: String
+        ) -> Response {
+            let config = &app_state.config.kodi;
+
+            if !config.enabled {
+                return PlayerError::NotFound("/jsonrpc".to_string()).into_response();
+            }
+
+            if let Err(e) = player::kodi::authorize(config, &app_state.devices, addr.ip(), &headers) {
+                let challenge = [(header::WWW_AUTHENTICATE, "Basic realm=\"Kodi\"")];
+                return (StatusCode::UNAUTHORIZED, challenge, e.to_string()).into_response();
+            }
+
+            let body = params.get("request").unwrap_or(&body);
+
+            let kodi = Kodi::new(&app_state.config, app_state.displays.clone(), app_state.libraries.clone());
+
+            match kodi.respond(body) {
+                Some(response) => axum::Json(response).into_response(),
+                None => StatusCode::NO_CONTENT.into_response()
+            }
+        }
+
+        async fn websocket(
+            State(state) : State<AppState>,
+            Query(params): Query<BTreeMap<String, String>>,
+            ws: axum::extract::WebSocketUpgrade
+        ) -> axum::response::Response {
+            let protocol = params.get("protocol").and_then(|protocol| protocol.parse::<u32>().ok());
+            ws.on_upgrade(move |socket| handle_socket(state, socket, protocol))
+        }
+
+        /// Greets the page with the protocol version, then streams display statuses to it,
+        /// unless it was built for another version.
+        async fn handle_socket(state: AppState, mut socket: axum::extract::ws::WebSocket, protocol: Option<u32>) {
+            use axum::extract::ws::Message;
+
+            let hello = serde_json::to_string(&Hello { protocol: PROTOCOL_VERSION }).unwrap_or_default();
+
+            if socket.send(Message::Text(hello)).await.is_err() {
+                return;
+            }
+
+            if protocol != Some(PROTOCOL_VERSION) {
+                log::info!("Closing a socket from a page on protocol {:?}, this is {}", protocol, PROTOCOL_VERSION);
+                return;
+            }
+
+            let mut signal = leptos_server_signal::ServerSignal::<BTreeMap<String, Status>>::new("displays").unwrap();
         
             loop {
                 tokio::time::sleep(tokio::time::Duration::from_millis(100)).await;
 
-                let player_state = state.player.read().unwrap().clone();
+                let statuses = state.displays.statuses();
 
                 let result = signal
-                    .with(&mut socket, |state| *state = player_state)
+                    .with(&mut socket, |state| *state = statuses)
                     .await;
 
                 if result.is_err() {
@@ -0,0 +1,121 @@
+use crate::devices::{get_pairing_code, PairDevice};
+use crate::error::ErrorAlert;
+use crate::icons::Icon;
+use crate::socket::BASE_PATH;
+use leptos::*;
+use leptos_router::use_query_map;
+
+/// Where unpaired browsers are sent. `?pin=` is filled in by the display's QR code, and
+/// `?next=` is the page to return to once paired.
+#[component]
+pub fn Pair(cx: Scope) -> impl IntoView {
+    let query = use_query_map(cx);
+    let pin_from_link = query.with_untracked(|query| query.get("pin").cloned()).unwrap_or_default();
+    let next = query
+        .with_untracked(|query| query.get("next").cloned())
+        .filter(|next| next.starts_with('/') && !next.starts_with("//"))
+        .unwrap_or_else(|| "/".to_string());
+    // Sent here from the display page, which pairs as a display
+    let display = next.starts_with("/tv");
+
+    let pair = create_server_action::<PairDevice>(cx);
+
+    let (name, set_name) = create_signal(cx, String::new());
+    let (pin, set_pin) = create_signal(cx, pin_from_link);
+
+    create_effect(cx, move |_| {
+        if let Some(Ok(())) = pair.value().get() {
+            // A full load, so the status socket reconnects with the new cookie
+            let _ = window().location().set_href(&format!("{}{}", BASE_PATH, next));
+        }
+    });
+
+    let error = move || {
+        pair.value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|e| view! { cx, <ErrorAlert error=e class="mt-3"/> })
+    };
+
+    view! { cx,
+        <div class="container-xl">
+            <div class="card shadow mt-5">
+                <div class="card-body">
+                    <h4 class="mb-3">"Pair this device"</h4>
+                    <p class="text-body-secondary">
+                        {if display { "Enter the admin PIN to use this device as a display." } else { "Enter the PIN shown on the TV, or the admin PIN." }}
+                    </p>
+                    <div class="row g-3">
+                        <div class="col-12 col-md-6">
+                            <label class="form-label">"Device name"</label>
+                            <input
+                                class="form-control"
+                                placeholder="Living room phone"
+                                prop:value=name
+                                on:input=move |ev| set_name.set(event_target_value(&ev))
+                            />
+                        </div>
+                        <div class="col-12 col-md-6">
+                            <label class="form-label">"PIN"</label>
+                            <input
+                                class="form-control font-monospace"
+                                inputmode="numeric"
+                                autocomplete="off"
+                                prop:value=pin
+                                on:input=move |ev| set_pin.set(event_target_value(&ev))
+                            />
+                        </div>
+                    </div>
+                    {error}
+                    <button
+                        class="btn btn-primary mt-3"
+                        disabled=move || pair.pending().get()
+                        on:click=move |_| pair.dispatch(PairDevice { pin: pin.get(), name: name.get(), display })
+                    >
+                        <Icon icon="link" class="me-2"/>
+                        "Pair"
+                    </button>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+/// The pairing PIN and QR code, shown on an idle display when auth is on.
+#[component]
+pub fn PairingCard(cx: Scope) -> impl IntoView {
+    #[allow(unused_variables)]
+    let (refresh, set_refresh) = create_signal(cx, 0);
+
+    let code = create_local_resource(
+        cx,
+        move || refresh.get(),
+        move |_| {
+            let url = format!("{}{}/pair?pin=", window().location().origin().unwrap_or_default(), BASE_PATH);
+            get_pairing_code(cx, url)
+        }
+    );
+
+    // PINs expire and are single use, so keep checking for a new one
+    #[cfg(not(feature = "ssr"))]
+    if let Ok(interval) = set_interval_with_handle(
+        move || set_refresh.update(|n| *n += 1),
+        std::time::Duration::from_secs(15)
+    ) {
+        on_cleanup(cx, move || interval.clear());
+    }
+
+    move || {
+        code.read(cx).and_then(|code| code.ok()).flatten().map(|code| {
+            view! { cx,
+                <div class="pairing-card">
+                    <div class="pairing-qr" inner_html=code.qr></div>
+                    <div>
+                        <div class="text-body-secondary">"Scan to pair a remote, or enter"</div>
+                        <div class="pairing-pin font-monospace">{code.pin}</div>
+                    </div>
+                </div>
+            }
+        })
+    }
+}
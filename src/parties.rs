@@ -0,0 +1,397 @@
+use cfg_if::cfg_if;
+use crate::player::*;
+use serde::{Deserialize, Serialize};
+
+/// Display names given to party participants start with this, which keeps them out of the
+/// remote's list of displays.
+pub static PARTY_PREFIX : &'static str = "party:";
+
+/// A watch party, as everyone in it sees it.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct PartyStatus {
+    pub path: Option<String>,
+    pub playing: bool,
+    /// Where the party's clock was when this status was sent
+    pub position: f64,
+    pub duration: f64,
+    pub participants: Vec<Participant>,
+    /// Oldest first
+    pub chat: Vec<ChatMessage>
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Participant {
+    pub remote: Remote,
+    pub online: bool,
+    /// Still loading or catching up
+    pub waiting: bool
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct ChatMessage {
+    pub id: u64,
+    pub by: Remote,
+    pub text: String,
+    pub kind: MessageKind,
+    pub time: String
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum MessageKind {
+    #[default]
+    Chat,
+    /// A single emoji, shown large
+    Reaction,
+    /// Someone playing, pausing or seeking, as in the activity feed
+    Action
+}
+
+/// Where a participant watches, and who the party knows them as.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct JoinedParty {
+    pub display: String,
+    pub remote: Remote
+}
+
+/// Changes any participant can make to what the party is watching.
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum PartyAction {
+    Play,
+    Pause,
+    Seek(f64)
+}
+
+/// The reactions participants can send.
+pub static REACTIONS : &'static [&'static str] = &["👍", "😂", "😮", "😢", "❤️", "👏"];
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use crate::config::SyncConfig;
+    use crate::displays::Displays;
+    use crate::error::PlayerError;
+    use crate::sync::{follow, Lead};
+    use chrono::Local;
+    use rand::Rng;
+    use std::collections::{BTreeMap, VecDeque};
+    use std::sync::{Arc, PoisonError, RwLock};
+    use std::time::{Duration, Instant};
+
+    static SYNC_INTERVAL : Duration = Duration::from_millis(500);
+    /// Parties nobody has been in for this long are ended
+    static EMPTY_FOR : Duration = Duration::from_secs(30 * 60);
+    static CHAT_LENGTH : usize = 50;
+    static MAX_MESSAGE_LENGTH : usize = 500;
+
+    /// The party's position, which every participant follows. Only the server moves it.
+    #[derive(Clone, Debug)]
+    struct Clock {
+        playing: bool,
+        position: f64,
+        since: Instant
+    }
+
+    impl Clock {
+        fn now(&self) -> f64 {
+            match self.playing {
+                true => self.position + self.since.elapsed().as_secs_f64(),
+                false => self.position
+            }
+        }
+
+        fn set(&mut self, playing: bool, position: f64) {
+            self.playing = playing;
+            self.position = position.max(0.0);
+            self.since = Instant::now();
+        }
+    }
+
+    #[derive(Clone, Debug)]
+    struct Party {
+        path: String,
+        clock: Clock,
+        /// Learnt from the participants, as the server doesn't read media
+        duration: f64,
+        /// By remote id, with the display each watches on
+        participants: BTreeMap<String, (Remote, String)>,
+        chat: VecDeque<ChatMessage>,
+        empty_since: Option<Instant>
+    }
+
+    impl Party {
+        /// The clock's position, which can't run past the end once the duration is known.
+        fn position(&self) -> f64 {
+            match self.duration > 0.0 {
+                true => self.clock.now().min(self.duration),
+                false => self.clock.now()
+            }
+        }
+
+        fn say(&mut self, by: &Remote, text: String, kind: MessageKind) {
+            let id = self.chat.back().map(|newest| newest.id + 1).unwrap_or(0);
+
+            self.chat.push_back(ChatMessage {
+                id,
+                by: by.clone(),
+                text,
+                kind,
+                time: Local::now().format("%H:%M").to_string()
+            });
+
+            while self.chat.len() > CHAT_LENGTH {
+                self.chat.pop_front();
+            }
+        }
+
+        /// What the participants should be doing, as if it were a display they follow.
+        fn lead(&self) -> Lead {
+            let position = self.position();
+
+            let phase = match (self.clock.playing, self.duration > 0.0 && position >= self.duration) {
+                (_, true) => Phase::Ended,
+                (true, false) => Phase::Playing,
+                (false, false) => Phase::Paused
+            };
+
+            let mut queue = Queue::default();
+            queue.replace(vec![self.path.clone()], 0);
+
+            Lead {
+                player: PlayerState::Media(MediaState {
+                    path: self.path.clone(),
+                    phase,
+                    duration: self.duration,
+                    position,
+                    buffered: vec![]
+                }),
+                queue,
+                position
+            }
+        }
+    }
+
+    /// Watch parties, kept in memory only. Each participant watches on a display of its own,
+    /// which follows the party's clock like a grouped display follows its leader.
+    #[derive(Clone, Debug, Default)]
+    pub struct Parties {
+        parties: Arc<RwLock<BTreeMap<String, Party>>>
+    }
+
+    impl Parties {
+        /// Starts a party watching `path` from `position`, paused until someone presses play.
+        pub fn start(&self, by: &Remote, path: String, position: f64) -> Result<String, PlayerError> {
+            check_position(position)?;
+
+            let mut rng = rand::thread_rng();
+            let id = (0..6).map(|_| (b'a' + rng.gen_range(0..26)) as char).collect::<String>();
+
+            let mut party = Party {
+                path,
+                clock: Clock { playing: false, position: 0.0, since: Instant::now() },
+                duration: 0.0,
+                participants: BTreeMap::new(),
+                chat: VecDeque::new(),
+                empty_since: Some(Instant::now())
+            };
+
+            party.clock.set(false, position);
+            party.say(by, "started the party".to_string(), MessageKind::Action);
+
+            self.parties.write().unwrap_or_else(PoisonError::into_inner).insert(id.clone(), party);
+            Ok(id)
+        }
+
+        /// What the party is watching.
+        pub fn path(&self, id: &str) -> Result<String, PlayerError> {
+            self.with(id, |party| Ok(party.path.clone()))
+        }
+
+        /// Adds a participant, giving the name of the display it should watch on.
+        pub fn join(&self, id: &str, by: &Remote) -> Result<String, PlayerError> {
+            self.with(id, |party| {
+                let display = format!("{}{}/{}", PARTY_PREFIX, id, by.id);
+
+                if party.participants.insert(by.id.clone(), (by.clone(), display.clone())).is_none() {
+                    party.say(by, "joined".to_string(), MessageKind::Action);
+                }
+
+                Ok(display)
+            })
+        }
+
+        pub fn act(&self, id: &str, by: &Remote, action: PartyAction) -> Result<(), PlayerError> {
+            self.with(id, |party| {
+                if !party.participants.contains_key(&by.id) {
+                    return Err(PlayerError::Forbidden("Join the party first".to_string()));
+                }
+
+                let position = party.position();
+
+                let message = match action {
+                    PartyAction::Play => {
+                        // Starting over once it's finished
+                        let from = if party.duration > 0.0 && position >= party.duration { 0.0 } else { position };
+                        party.clock.set(true, from);
+                        format!("played from {}", format_position(from))
+                    },
+                    PartyAction::Pause => {
+                        party.clock.set(false, position);
+                        format!("paused at {}", format_position(position))
+                    },
+                    PartyAction::Seek(to) => {
+                        check_position(to)?;
+
+                        let playing = party.clock.playing;
+                        party.clock.set(playing, to);
+                        format!("jumped to {}", format_position(to))
+                    }
+                };
+
+                party.say(by, message, MessageKind::Action);
+                Ok(())
+            })
+        }
+
+        pub fn say(&self, id: &str, by: &Remote, text: &str, kind: MessageKind) -> Result<(), PlayerError> {
+            let text = text.trim();
+
+            if text.is_empty() || text.chars().count() > MAX_MESSAGE_LENGTH {
+                return Err(PlayerError::InvalidInput(format!("Messages need 1 to {} characters", MAX_MESSAGE_LENGTH)));
+            }
+
+            if kind == MessageKind::Reaction && !REACTIONS.contains(&text) {
+                return Err(PlayerError::InvalidInput(format!("{} isn't a reaction", text)));
+            }
+
+            self.with(id, |party| {
+                if !party.participants.contains_key(&by.id) {
+                    return Err(PlayerError::Forbidden("Join the party first".to_string()));
+                }
+
+                party.say(by, text.to_string(), kind);
+                Ok(())
+            })
+        }
+
+        fn with<R>(&self, id: &str, f: impl FnOnce(&mut Party) -> Result<R, PlayerError>) -> Result<R, PlayerError> {
+            let mut parties = self.parties.write().unwrap_or_else(PoisonError::into_inner);
+
+            match parties.get_mut(id) {
+                Some(party) => f(party),
+                None => Err(PlayerError::NotFound(format!("The party {}", id)))
+            }
+        }
+
+        /// The party as its participants see it, which only they can.
+        pub fn status(&self, id: &str, by: &Remote, displays: &Displays) -> Result<PartyStatus, PlayerError> {
+            let party = self.with(id, |party| {
+                if !party.participants.contains_key(&by.id) {
+                    return Err(PlayerError::Forbidden("Join the party first".to_string()));
+                }
+
+                Ok(party.clone())
+            })?;
+
+            let participants = party.participants
+                .values()
+                .map(|(remote, display)| {
+                    let (online, waiting) = displays.with(display, |session| {
+                        let waiting = match &session.player {
+                            PlayerState::Media(media) => media.phase.is_waiting(),
+                            PlayerState::Idle => true
+                        };
+
+                        (session.is_online(), waiting)
+                    })
+                    .unwrap_or((false, false));
+
+                    Participant { remote: remote.clone(), online, waiting }
+                })
+                .collect();
+
+            Ok(PartyStatus {
+                path: Some(party.path.clone()),
+                playing: party.clock.playing,
+                position: party.position(),
+                duration: party.duration,
+                participants,
+                chat: party.chat.iter().cloned().collect()
+            })
+        }
+
+        /// Keeps each participant's display on the party's clock, learns the duration from
+        /// them, and ends parties that have been empty a while, along with their displays.
+        fn sync(&self, displays: &Displays, settings: SyncConfig) {
+            let mut parties = self.parties.write().unwrap_or_else(PoisonError::into_inner);
+            let mut ended = vec![];
+
+            for (id, party) in parties.iter_mut() {
+                let lead = party.lead();
+                let mut anyone_online = false;
+
+                for (_, display) in party.participants.values() {
+                    let _ = displays.with(display, |session| {
+                        if let PlayerState::Media(media) = &session.player {
+                            if media.path == party.path && media.duration > 0.0 {
+                                party.duration = media.duration;
+                            }
+                        }
+
+                        anyone_online |= session.is_online();
+                        follow(&lead, session, settings);
+                    });
+                }
+
+                // Finished parties stop where they ended rather than running on
+                if party.clock.playing && party.duration > 0.0 && party.clock.now() >= party.duration {
+                    let duration = party.duration;
+                    party.clock.set(false, duration);
+                }
+
+                party.empty_since = match (anyone_online, party.empty_since) {
+                    (true, _) => None,
+                    (false, since) => Some(since.unwrap_or_else(Instant::now))
+                };
+
+                if party.empty_since.map(|since| since.elapsed() > EMPTY_FOR).unwrap_or(false) {
+                    ended.push(id.clone());
+                }
+            }
+
+            for id in ended {
+                if let Some(party) = parties.remove(&id) {
+                    log::info!("Ending the empty party {}", id);
+
+                    for (_, display) in party.participants.values() {
+                        displays.remove(display);
+                    }
+                }
+            }
+        }
+    }
+
+    /// Refuses positions a party's clock, and so its participants' displays, can't use.
+    fn check_position(position: f64) -> Result<(), PlayerError> {
+        match position.is_finite() && position >= 0.0 {
+            true => Ok(()),
+            false => Err(PlayerError::InvalidInstruction(format!("Can't jump to {}", position)))
+        }
+    }
+
+    /// Moves every party's participants along with its clock, a couple of times a second.
+    pub async fn run(parties: Parties, displays: Displays, settings: SyncConfig) {
+        loop {
+            tokio::time::sleep(SYNC_INTERVAL).await;
+            parties.sync(&displays, settings);
+        }
+    }
+
+    //
+    //
+    //
+}}
@@ -0,0 +1,234 @@
+pub mod fns;
+
+use crate::config::get_remote_settings;
+use crate::display::Display;
+use crate::error::ErrorAlert;
+use crate::icons::Icon;
+use crate::parties::*;
+use crate::party::fns::*;
+use crate::player::*;
+use crate::toast::{provide_toasts, toast_errors, ToastList};
+use leptos::*;
+use leptos_router::use_params_map;
+use std::time::Duration;
+
+/// How many chat messages are shown over the video.
+static CHAT_SHOWN : usize = 6;
+/// How often the party's status is fetched, as often as the server moves participants along.
+#[cfg_attr(feature = "ssr", allow(dead_code))]
+static STATUS_INTERVAL : Duration = Duration::from_millis(500);
+
+/// A watch party, joined from the link its starter shared. Each participant's own display
+/// plays in the background, kept on the party's clock by the server.
+#[component]
+pub fn Party(cx: Scope) -> impl IntoView {
+    let party = use_params_map(cx).with_untracked(|params| params.get("party").cloned().unwrap_or_default());
+    let party = store_value(cx, party);
+    provide_toasts(cx);
+
+    // Only joined by the browser, so unpaired participants get their cookie from a real response
+    let joined = create_local_resource(cx, || (), move |_| join_party(cx, party.get_value()));
+
+    view! { cx,
+        <div class="party h-100">
+            {move || match joined.read(cx) {
+                None => view! { cx,
+                    <div class="d-flex align-items-center justify-content-center h-100">
+                        <span class="spinner-border"></span>
+                    </div>
+                }.into_view(cx),
+                Some(Err(e)) => view! { cx,
+                    <div class="container-xl mt-5">
+                        <ErrorAlert error=e/>
+                    </div>
+                }.into_view(cx),
+                Some(Ok(joined)) => view! { cx,
+                    <Display name=joined.display hide_pairing=true/>
+                    <PartyOverlay party=party.get_value() you=joined.remote/>
+                }.into_view(cx)
+            }}
+            <ToastList/>
+        </div>
+    }
+}
+
+/// Who's watching, the controls every participant shares, and the chat.
+#[component]
+fn PartyOverlay(cx: Scope, party: String, you: Remote) -> impl IntoView {
+    let party = store_value(cx, party);
+    let you = store_value(cx, you);
+    #[allow(unused_variables)]
+    let (status, set_status) = create_signal(cx, PartyStatus::default());
+
+    // Asked for rather than streamed, as only participants may see it
+    #[cfg(not(feature = "ssr"))]
+    set_interval(
+        move || {
+            spawn_local(async move {
+                if let Ok(latest) = get_party_status(cx, party.get_value()).await {
+                    set_status.set(latest);
+                }
+            })
+        },
+        STATUS_INTERVAL
+    );
+
+    let control = create_server_action::<PartyControl>(cx);
+    let say = create_server_action::<PartySay>(cx);
+    toast_errors(cx, control);
+    toast_errors(cx, say);
+
+    let settings = create_resource(cx, || (), move |_| get_remote_settings(cx));
+    let settings = move || settings.read(cx).and_then(|settings| settings.ok()).unwrap_or_default();
+
+    let (message, set_message) = create_signal(cx, String::new());
+
+    // Clears the message once it's been sent
+    create_effect(cx, move |_| {
+        if let Some(Ok(())) = say.value().get() {
+            set_message.set(String::new());
+        }
+    });
+
+    let is_you = move |remote: &Remote| you.with_value(|you| you.id == remote.id);
+    let name_of = move |remote: &Remote| if is_you(remote) { "You".to_string() } else { remote.name.clone() };
+
+    let playing = move || status.with(|status| status.playing);
+    let position = move || status.with(|status| status.position);
+    let duration = move || status.with(|status| status.duration);
+
+    let act = move |action| control.dispatch(PartyControl { party: party.get_value(), action });
+    let seek = move |to: f64| {
+        let to = if duration() > 0.0 { to.clamp(0.0, duration()) } else { to.max(0.0) };
+        act(PartyAction::Seek(to));
+    };
+
+    let send = move |ev: ev::SubmitEvent| {
+        ev.prevent_default();
+        say.dispatch(PartySay { party: party.get_value(), text: message.get(), reaction: false });
+    };
+
+    let react = move |reaction: &'static str| {
+        say.dispatch(PartySay { party: party.get_value(), text: reaction.to_string(), reaction: true });
+    };
+
+    view! { cx,
+        <div class="party-overlay">
+            <div class="party-participants d-flex flex-wrap gap-2">
+                <Icon icon="group"/>
+                {move || {
+                    status.with(|status| {
+                        status.participants
+                            .iter()
+                            .map(|participant| {
+                                let class = if participant.online { "badge text-bg-primary" } else { "badge text-bg-secondary" };
+                                let name = name_of(&participant.remote);
+                                let waiting = participant.online && participant.waiting;
+
+                                view! { cx,
+                                    <span class=class>
+                                        {waiting.then(|| view! { cx, <span class="spinner-border spinner-border-sm me-1"></span> })}
+                                        {name}
+                                    </span>
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                }}
+            </div>
+            <div class="party-chat">
+                {move || {
+                    status.with(|status| {
+                        let skip = status.chat.len().saturating_sub(CHAT_SHOWN);
+
+                        status.chat
+                            .iter()
+                            .skip(skip)
+                            .map(|message| {
+                                let name = name_of(&message.by);
+
+                                match message.kind {
+                                    MessageKind::Chat => view! { cx,
+                                        <div class="party-message">
+                                            <strong class="me-1">{name}</strong>
+                                            {message.text.clone()}
+                                        </div>
+                                    },
+                                    MessageKind::Reaction => view! { cx,
+                                        <div class="party-message party-reaction">
+                                            <span class="small me-1">{name}</span>
+                                            <span class="fs-4">{message.text.clone()}</span>
+                                        </div>
+                                    },
+                                    MessageKind::Action => view! { cx,
+                                        <div class="party-message text-body-secondary small">
+                                            {format!("{} {}", name, message.text)}
+                                        </div>
+                                    }
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                }}
+            </div>
+            <div class="party-controls">
+                <div class="d-flex align-items-center gap-2 mb-2">
+                    <button class="btn btn-primary" title="Back" on:click=move |_| seek(position() - settings().skip_back)>
+                        <Icon icon="rewind"/>
+                    </button>
+                    <button
+                        class="btn btn-primary"
+                        on:click=move |_| act(if playing() { PartyAction::Pause } else { PartyAction::Play })
+                    >
+                        {move || {
+                            if playing() {
+                                view! { cx, <Icon icon="pause"/> }
+                            } else {
+                                view! { cx, <Icon icon="play"/> }
+                            }
+                        }}
+                    </button>
+                    <button class="btn btn-primary" title="Forward" on:click=move |_| seek(position() + settings().skip_forward)>
+                        <Icon icon="fast-forward"/>
+                    </button>
+                    <input
+                        class="party-seek flex-grow-1"
+                        type="range"
+                        min="0"
+                        step="1"
+                        prop:max=move || duration().floor()
+                        prop:value=move || position().floor()
+                        on:change=move |ev| {
+                            if let Ok(to) = event_target_value(&ev).parse::<f64>() {
+                                seek(to);
+                            }
+                        }
+                    />
+                    <span class="font-monospace small">
+                        {move || format!("{} / {}", format_position(position()), format_position(duration()))}
+                    </span>
+                </div>
+                <div class="d-flex align-items-center gap-2">
+                    <form class="d-flex gap-2 flex-grow-1" on:submit=send>
+                        <input
+                            class="form-control form-control-sm"
+                            placeholder="Say something"
+                            prop:value=message
+                            on:input=move |ev| set_message.set(event_target_value(&ev))
+                        />
+                        <button class="btn btn-sm btn-primary" type="submit" title="Send">
+                            <Icon icon="send"/>
+                        </button>
+                    </form>
+                    {REACTIONS
+                        .iter()
+                        .copied()
+                        .map(|reaction| view! { cx,
+                            <button class="btn btn-sm btn-link" on:click=move |_| react(reaction)>{reaction}</button>
+                        })
+                        .collect::<Vec<_>>()}
+                </div>
+            </div>
+        </div>
+    }
+}
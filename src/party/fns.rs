@@ -0,0 +1,81 @@
+use cfg_if::cfg_if;
+#[allow(unused_imports)]
+use crate::error::PlayerError;
+use crate::parties::*;
+use leptos::*;
+
+/// Starts a watch party of `path`, giving the party's id for others to join with.
+#[server(StartParty, "/api")]
+pub async fn start_party(cx: Scope, path: String, position: f64) -> Result<String, ServerFnError> {
+    profile_libraries(cx)?.file(&path)?;
+
+    let parties = expect_context::<Parties>(cx);
+    Ok(parties.start(&current_remote(cx), path, position)?)
+}
+
+/// Joins a party, giving the display this participant watches on. Joining again picks up the
+/// same display.
+#[server(JoinParty, "/api")]
+pub async fn join_party(cx: Scope, party: String) -> Result<JoinedParty, ServerFnError> {
+    let parties = expect_context::<Parties>(cx);
+    let displays = expect_context::<Displays>(cx);
+
+    // Kid profiles can only join parties watching what they could play themselves
+    profile_libraries(cx)?.file(&parties.path(&party)?)?;
+
+    let remote = current_remote(cx);
+    let display = parties.join(&party, &remote)?;
+    let profile = current_profile(cx).map(|profile| profile.id);
+    // Claimed for the joining device now, as other participants can see the display's name
+    let device = current_device(cx).map(|device| device.id);
+
+    displays.with_or_add(&display, |session| {
+        session.profile = profile;
+        session.device = session.device.or(device);
+    })?;
+
+    Ok(JoinedParty { display, remote })
+}
+
+/// Plays, pauses or seeks for everyone in the party.
+#[server(PartyControl, "/api")]
+pub async fn party_control(cx: Scope, party: String, action: PartyAction) -> Result<(), ServerFnError> {
+    let parties = expect_context::<Parties>(cx);
+    parties.act(&party, &current_remote(cx), action)?;
+
+    Ok(())
+}
+
+/// Says something to the party, or reacts with one of `REACTIONS`.
+#[server(PartySay, "/api")]
+pub async fn party_say(cx: Scope, party: String, text: String, reaction: bool) -> Result<(), ServerFnError> {
+    let parties = expect_context::<Parties>(cx);
+    let kind = if reaction { MessageKind::Reaction } else { MessageKind::Chat };
+    parties.say(&party, &current_remote(cx), &text, kind)?;
+
+    Ok(())
+}
+
+/// What the party is watching, who's in it and what they've said. Only participants can see it.
+#[server(GetPartyStatus, "/api")]
+pub async fn get_party_status(cx: Scope, party: String) -> Result<PartyStatus, ServerFnError> {
+    let parties = expect_context::<Parties>(cx);
+    let displays = expect_context::<Displays>(cx);
+
+    Ok(parties.status(&party, &current_remote(cx), &displays)?)
+}
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use crate::control::current_remote;
+    use crate::devices::current_device;
+    use crate::displays::Displays;
+    use crate::profiles::{current_profile, profile_libraries};
+
+    //
+    //
+    //
+}}
@@ -0,0 +1,79 @@
+use cfg_if::cfg_if;
+use crate::player::*;
+use leptos::*;
+#[allow(unused_imports)]
+use crate::library::*;
+
+#[server(GetPhotoInfo, "/api")]
+pub async fn get_photo_info(cx: Scope, path: String) -> Result<PhotoInfo, ServerFnError> {
+    let libraries = crate::profiles::profile_libraries(cx)?;
+    let path = libraries.media(&path, MediaKind::Image)?;
+
+    Ok(read_photo_info(&path))
+}
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use exif::{DateTime, Exif, In, Tag, Value};
+    use std::fs::File;
+    use std::io::BufReader;
+    use std::path::Path;
+
+    /// Reads EXIF orientation, capture date and GPS position, defaulting to an upright photo.
+    pub fn read_photo_info(path: &Path) -> PhotoInfo {
+        let Some(exif) = read_exif(path) else {
+            return PhotoInfo { orientation: 1, ..PhotoInfo::default() };
+        };
+
+        let orientation = exif
+            .get_field(Tag::Orientation, In::PRIMARY)
+            .and_then(|field| field.value.get_uint(0))
+            .unwrap_or(1);
+
+        let taken = exif
+            .get_field(Tag::DateTimeOriginal, In::PRIMARY)
+            .and_then(|field| match field.value {
+                Value::Ascii(ref values) => values.first().and_then(|v| DateTime::from_ascii(v).ok()),
+                _ => None
+            })
+            .map(|dt| format!("{:04}-{:02}-{:02} {:02}:{:02}", dt.year, dt.month, dt.day, dt.hour, dt.minute));
+
+        let latitude = read_coordinate(&exif, Tag::GPSLatitude, Tag::GPSLatitudeRef, b"S");
+        let longitude = read_coordinate(&exif, Tag::GPSLongitude, Tag::GPSLongitudeRef, b"W");
+
+        PhotoInfo {
+            orientation,
+            taken,
+            location: latitude.zip(longitude)
+        }
+    }
+
+    fn read_exif(path: &Path) -> Option<Exif> {
+        let file = File::open(path).ok()?;
+        exif::Reader::new().read_from_container(&mut BufReader::new(file)).ok()
+    }
+
+    /// Converts a degrees/minutes/seconds GPS field into signed decimal degrees.
+    fn read_coordinate(exif: &Exif, tag: Tag, ref_tag: Tag, negative: &[u8]) -> Option<f64> {
+        let degrees = match exif.get_field(tag, In::PRIMARY)?.value {
+            Value::Rational(ref v) if v.len() >= 3 => {
+                v[0].to_f64() + v[1].to_f64() / 60.0 + v[2].to_f64() / 3600.0
+            },
+            _ => return None
+        };
+
+        let is_negative = match exif.get_field(ref_tag, In::PRIMARY).map(|field| &field.value) {
+            Some(Value::Ascii(values)) => values.first().map(|v| v.as_slice() == negative).unwrap_or(false),
+            _ => false
+        };
+
+        Some(if is_negative { -degrees } else { degrees })
+    }
+
+    //
+    //
+    //
+}}
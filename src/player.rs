@@ -1,7 +1,67 @@
+mod playback;
+
 use serde::{Deserialize, Serialize};
 
-#[derive(Clone)]
-pub struct MediaRoot(pub String);
+pub use crate::player::playback::{MediaEvent, Playback};
+
+/// Version of the types displays and remotes exchange with the server. Fields can be added
+/// without changing it, as long as they're `#[serde(default)]`, since unknown fields are
+/// ignored and missing ones defaulted either way. Anything else needs it bumped, so pages
+/// built against the old types reload rather than misread the new.
+pub static PROTOCOL_VERSION : u32 = 3;
+
+/// Slowest and fastest displays can be told to play
+pub static MIN_RATE : f64 = 0.5;
+pub static MAX_RATE : f64 = 2.0;
+
+static AUDIO_EXTENSIONS : &'static [&'static str] = &["mp3", "flac", "ogg", "oga", "opus", "m4a", "aac", "wav"];
+static IMAGE_EXTENSIONS : &'static [&'static str] = &["jpg", "jpeg", "png", "gif", "webp", "avif", "bmp"];
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Status {
+    pub player: PlayerState,
+    pub queue: Queue,
+    pub sleep: Option<SleepStatus>,
+    /// Whether the display has checked in recently. `player` is its last known state when offline.
+    pub online: bool,
+    /// When the display last checked in, if it has since the server started
+    pub last_seen: Option<String>,
+    /// The remote holding control, while only it may control the display
+    pub control: Option<Remote>,
+    /// What's been done to the display recently, newest first
+    pub activity: Vec<Activity>,
+    /// For a group, its displays. Its status is that of whichever leads it.
+    pub members: Vec<String>,
+    /// The group the display is playing along with, if it's joined one
+    pub group: Option<String>
+}
+
+/// Whoever sent something to a display: a paired device, an unpaired remote, or a schedule.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Remote {
+    /// Opaque, so it can be shown to other remotes without letting them pass as this one
+    pub id: String,
+    pub name: String
+}
+
+/// One entry in a display's activity feed.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Activity {
+    /// Counts up for each display, so repeats of the same action can be told apart
+    pub id: u64,
+    pub by: Remote,
+    /// What was done, to follow the remote's name, like "paused at 41:02"
+    pub action: String,
+    pub time: String
+}
+
+impl Activity {
+    pub fn message(&self) -> String {
+        format!("{} {}", self.by.name, self.action)
+    }
+}
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub enum PlayerState {
@@ -16,14 +76,545 @@ impl Default for PlayerState {
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
-pub enum MediaState {
-    Paused(String, f64, f64),
-    Playing(String, f64, f64)
+pub struct MediaState {
+    pub path: String,
+    pub phase: Phase,
+    /// Seconds, or 0 until known
+    pub duration: f64,
+    pub position: f64,
+    /// Start and end seconds of each part the display has loaded
+    #[serde(default)]
+    pub buffered: Vec<(f64, f64)>
+}
+
+/// Where a display is with its current media, as followed by `Playback`.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Phase {
+    /// A new item is being fetched
+    Loading,
+    /// Playback stalled waiting for data
+    Buffering,
+    Playing,
+    Paused,
+    /// Moving to a new position
+    Seeking,
+    /// Played to the end
+    Ended,
+    /// The item couldn't be played, and why
+    Error(String)
+}
+
+impl Phase {
+    /// Whether the display is busy, rather than waiting on the viewer.
+    pub fn is_waiting(&self) -> bool {
+        matches!(self, Self::Loading | Self::Buffering | Self::Seeking)
+    }
+
+    /// Whether the position reported alongside means anything, so can be resumed from.
+    pub fn has_position(&self) -> bool {
+        !matches!(self, Self::Loading | Self::Error(_))
+    }
 }
 
 #[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub enum Instruction {
     Stop,
-    Play(String, f64),
-    Pause(String, f64)
+    Play {
+        path: String,
+        #[serde(default)]
+        from: f64
+    },
+    Pause {
+        path: String,
+        #[serde(default)]
+        at: f64
+    },
+    Queue {
+        items: Vec<String>,
+        #[serde(default)]
+        position: usize
+    },
+    PlayFolder {
+        folder: String
+    },
+    Slideshow(Slideshow),
+    Show {
+        path: String,
+        #[serde(default)]
+        settings: SlideSettings
+    },
+    Volume {
+        volume: f64
+    },
+    /// Moves within what's already playing, without loading it again
+    Seek {
+        at: f64
+    },
+    /// Plays faster or slower, which grouped displays use to catch up with their leader
+    Rate {
+        rate: f64
+    }
+}
+
+impl Instruction {
+    /// What the instruction does, as it reads in the activity feed.
+    pub fn describe(&self) -> String {
+        match self {
+            Self::Stop => "stopped playback".to_string(),
+            Self::Play { path, from } if *from > 0.0 => format!("played {} from {}", file_name(path), format_position(*from)),
+            Self::Play { path, .. } => format!("played {}", file_name(path)),
+            Self::Pause { at, .. } => format!("paused at {}", format_position(*at)),
+            Self::Queue { items, position } => match items.get(*position) {
+                Some(path) if items.len() > 1 => format!("queued {} items, starting with {}", items.len(), file_name(path)),
+                Some(path) => format!("played {}", file_name(path)),
+                None => "cleared the queue".to_string()
+            },
+            Self::PlayFolder { folder } => format!("played everything in {}", file_name(folder)),
+            Self::Slideshow(slideshow) => format!("started a slideshow of {}", file_name(&slideshow.folder)),
+            Self::Show { path, .. } => format!("showed {}", file_name(path)),
+            Self::Volume { volume } => format!("set the volume to {}%", (volume * 100.0).round()),
+            Self::Seek { at } => format!("jumped to {}", format_position(*at)),
+            Self::Rate { rate } => format!("set the speed to {}x", rate)
+        }
+    }
+
+    /// Whether the instruction starts or resumes playback. Stopping, pausing and changing the
+    /// volume or speed are allowed even with no viewing time left.
+    pub fn starts_playback(&self) -> bool {
+        !matches!(self, Self::Stop | Self::Pause { .. } | Self::Volume { .. } | Self::Rate { .. })
+    }
+
+    /// Checks the instruction's values are ones a display can use, so nothing out of range is
+    /// passed on to it or the displays following it.
+    pub fn check(&self) -> Result<(), String> {
+        match self {
+            Self::Slideshow(slideshow) => slideshow.settings.check(),
+            Self::Show { settings, .. } => settings.check(),
+            Self::Play { from: position, .. } | Self::Pause { at: position, .. } | Self::Seek { at: position } => {
+                match position.is_finite() && *position >= 0.0 {
+                    true => Ok(()),
+                    false => Err(format!("Can't jump to {}", position))
+                }
+            },
+            Self::Volume { volume } => match (0.0..=1.0).contains(volume) {
+                true => Ok(()),
+                false => Err(format!("Volume must be 0 to 1, not {}", volume))
+            },
+            Self::Rate { rate } => match (MIN_RATE..=MAX_RATE).contains(rate) {
+                true => Ok(()),
+                false => Err(format!("Speed must be {}x to {}x, not {}", MIN_RATE, MAX_RATE, rate))
+            },
+            Self::Stop | Self::Queue { .. } | Self::PlayFolder { .. } => Ok(())
+        }
+    }
+}
+
+/// The last part of a library path.
+pub fn file_name(path: &str) -> &str {
+    path.trim_end_matches('/').rsplit('/').next().unwrap_or(path)
+}
+
+/// A position in seconds as `m:ss`, or `h:mm:ss` from an hour.
+pub fn format_position(seconds: f64) -> String {
+    let seconds = seconds.max(0.0) as u64;
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum SleepMode {
+    Minutes(u32),
+    AfterItem,
+    AfterQueue
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct SleepStatus {
+    pub mode: SleepMode,
+    pub fade: bool,
+    /// Seconds until playback stops, for timed modes
+    pub remaining: Option<u64>
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Slideshow {
+    pub folder: String,
+    pub shuffle: bool,
+    pub settings: SlideSettings
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct SlideSettings {
+    pub interval: f64,
+    pub transition: Transition
+}
+
+impl SlideSettings {
+    /// Displays count each slide down from its interval, so it has to be a positive number.
+    pub fn check(&self) -> Result<(), String> {
+        match self.interval.is_finite() && self.interval > 0.0 {
+            true => Ok(()),
+            false => Err(format!("Slides need a positive number of seconds, not {}", self.interval))
+        }
+    }
+}
+
+impl Default for SlideSettings {
+    fn default() -> Self {
+        Self {
+            interval: 10.0,
+            transition: Transition::Fade
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum Transition {
+    None,
+    Fade,
+    Slide
+}
+
+impl Transition {
+    pub fn class(&self) -> &'static str {
+        match self {
+            Self::None => "transition-none",
+            Self::Fade => "transition-fade",
+            Self::Slide => "transition-slide"
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum MediaKind {
+    Video,
+    Audio,
+    Image
+}
+
+impl MediaKind {
+    pub fn of(path: &str) -> Self {
+        let extension = path
+            .rsplit_once('.')
+            .map(|(_, ext)| ext.to_lowercase())
+            .unwrap_or_default();
+
+        if AUDIO_EXTENSIONS.contains(&extension.as_str()) {
+            Self::Audio
+        } else if IMAGE_EXTENSIONS.contains(&extension.as_str()) {
+            Self::Image
+        } else {
+            Self::Video
+        }
+    }
+
+    /// The kind of media, following "a" or "an".
+    pub fn describe(&self) -> &'static str {
+        match self {
+            Self::Video => "a video",
+            Self::Audio => "a track",
+            Self::Image => "a photo"
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub enum Repeat {
+    #[default]
+    Off,
+    One,
+    All
+}
+
+impl Repeat {
+    pub fn cycle(&self) -> Self {
+        match self {
+            Self::Off => Self::All,
+            Self::All => Self::One,
+            Self::One => Self::Off
+        }
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct Queue {
+    pub items: Vec<String>,
+    /// Play order as indices into `items`, shuffled when `shuffle` is set
+    pub order: Vec<usize>,
+    /// Position within `order`
+    pub position: usize,
+    pub shuffle: bool,
+    pub repeat: Repeat,
+    pub slides: SlideSettings
+}
+
+impl Queue {
+    /// Replaces the queued items, keeping the shuffle, repeat and slide settings.
+    /// A shuffled queue must be reshuffled by the caller.
+    pub fn replace(&mut self, items: Vec<String>, position: usize) {
+        self.position = position.min(items.len().saturating_sub(1));
+        self.order = (0..items.len()).collect();
+        self.items = items;
+    }
+
+    pub fn current(&self) -> Option<&String> {
+        self.order.get(self.position).and_then(|ix| self.items.get(*ix))
+    }
+
+    /// The instruction that starts the current item from the beginning.
+    pub fn instruction(&self) -> Instruction {
+        match self.current() {
+            Some(path) if MediaKind::of(path) == MediaKind::Image => Instruction::Show { path: path.clone(), settings: self.slides },
+            Some(path) => Instruction::Play { path: path.clone(), from: 0.0 },
+            None => Instruction::Stop
+        }
+    }
+
+    /// Moves the queue position by `offset`, returning the new current item,
+    /// or `None` (leaving the position untouched) if that would leave the queue.
+    /// Wraps around when repeating the whole queue.
+    pub fn skip(&mut self, offset: i32) -> Option<String> {
+        let len = self.order.len() as i64;
+        let position = self.position as i64 + offset as i64;
+
+        if len == 0 {
+            return None;
+        }
+
+        let position = match self.repeat {
+            Repeat::All => position.rem_euclid(len),
+            _ if position < 0 || position >= len => return None,
+            _ => position
+        };
+
+        self.position = position as usize;
+        self.current().cloned()
+    }
+
+    /// Moves on once the current item has finished, honouring the repeat mode.
+    pub fn next(&mut self) -> Option<String> {
+        match self.repeat {
+            Repeat::One => self.current().cloned(),
+            _ => self.skip(1)
+        }
+    }
+
+    /// Shuffles the play order using `shuffle`, keeping the current item first.
+    pub fn shuffle_with(&mut self, shuffle: impl FnOnce(&mut [usize])) {
+        let current = self.order.get(self.position).copied();
+
+        let mut rest = (0..self.items.len())
+            .filter(|ix| Some(*ix) != current)
+            .collect::<Vec<_>>();
+        shuffle(&mut rest);
+
+        self.order = current.into_iter().chain(rest).collect();
+        self.position = 0;
+        self.shuffle = true;
+    }
+
+    /// Restores the original order, keeping the current item.
+    pub fn unshuffle(&mut self) {
+        let current = self.order.get(self.position).copied();
+
+        self.order = (0..self.items.len()).collect();
+        self.position = current.unwrap_or(0);
+        self.shuffle = false;
+    }
+
+    /// Adds an item to the end of the play order.
+    pub fn push(&mut self, item: String) {
+        self.order.push(self.items.len());
+        self.items.push(item);
+    }
+
+    /// Removes the item at `position` in the play order, returning it, unless it's the current
+    /// item or past the end.
+    pub fn remove(&mut self, position: usize) -> Option<String> {
+        if position == self.position || position >= self.order.len() {
+            return None;
+        }
+
+        let ix = self.order.remove(position);
+
+        for other in self.order.iter_mut().filter(|other| **other > ix) {
+            *other -= 1;
+        }
+
+        if position < self.position {
+            self.position -= 1;
+        }
+
+        Some(self.items.remove(ix))
+    }
+
+    pub fn clear(&mut self) {
+        self.items.clear();
+        self.order.clear();
+        self.position = 0;
+    }
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct TrackInfo {
+    pub title: Option<String>,
+    pub artist: Option<String>,
+    pub album: Option<String>,
+    pub has_art: bool
+}
+
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(default)]
+pub struct PhotoInfo {
+    pub orientation: u32,
+    pub taken: Option<String>,
+    pub location: Option<(f64, f64)>
+}
+
+impl PhotoInfo {
+    pub fn caption(&self) -> Option<String> {
+        let location = self.location.map(|(lat, lon)| {
+            format!(
+                "{:.4}° {}, {:.4}° {}",
+                lat.abs(),
+                if lat < 0.0 { "S" } else { "N" },
+                lon.abs(),
+                if lon < 0.0 { "W" } else { "E" }
+            )
+        });
+
+        let parts = [self.taken.clone(), location]
+            .into_iter()
+            .flatten()
+            .collect::<Vec<_>>();
+
+        (!parts.is_empty()).then(|| parts.join(" · "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn queue(items: &[&str], position: usize) -> Queue {
+        let mut queue = Queue::default();
+        queue.replace(items.iter().map(|item| item.to_string()).collect(), position);
+        queue
+    }
+
+    #[test]
+    fn skips_within_the_queue() {
+        let mut queue = queue(&["a.mp3", "b.mp3", "c.mp3"], 0);
+
+        assert_eq!(queue.skip(2), Some("c.mp3".to_string()));
+        assert_eq!(queue.skip(1), None);
+        assert_eq!(queue.position, 2);
+        assert_eq!(queue.skip(-1), Some("b.mp3".to_string()));
+        assert_eq!(queue.skip(-2), None);
+        assert_eq!(queue.position, 1);
+    }
+
+    #[test]
+    fn skipping_an_empty_queue_does_nothing() {
+        let mut queue = Queue::default();
+        queue.repeat = Repeat::All;
+
+        assert_eq!(queue.skip(1), None);
+        assert_eq!(queue.next(), None);
+    }
+
+    #[test]
+    fn repeats_one_or_all() {
+        let mut queue = queue(&["a.mp3", "b.mp3"], 1);
+
+        assert_eq!(queue.next(), None);
+
+        queue.repeat = Repeat::One;
+        assert_eq!(queue.next(), Some("b.mp3".to_string()));
+
+        queue.repeat = Repeat::All;
+        assert_eq!(queue.next(), Some("a.mp3".to_string()));
+        assert_eq!(queue.skip(-1), Some("b.mp3".to_string()));
+    }
+
+    #[test]
+    fn shuffles_around_the_current_item() {
+        let mut queue = queue(&["a.mp3", "b.mp3", "c.mp3", "d.mp3"], 2);
+
+        queue.shuffle_with(|rest| rest.reverse());
+        assert!(queue.shuffle);
+        assert_eq!(queue.order, vec![2, 3, 1, 0]);
+        assert_eq!(queue.position, 0);
+        assert_eq!(queue.current(), Some(&"c.mp3".to_string()));
+
+        queue.skip(1);
+        queue.unshuffle();
+        assert!(!queue.shuffle);
+        assert_eq!(queue.order, vec![0, 1, 2, 3]);
+        assert_eq!(queue.current(), Some(&"d.mp3".to_string()));
+    }
+
+    #[test]
+    fn removes_around_the_current_item() {
+        let mut queue = queue(&["a.mp3", "b.mp3", "c.mp3"], 1);
+
+        assert_eq!(queue.remove(1), None);
+        assert_eq!(queue.remove(3), None);
+        assert_eq!(queue.remove(0), Some("a.mp3".to_string()));
+        assert_eq!(queue.position, 0);
+        assert_eq!(queue.order, vec![0, 1]);
+        assert_eq!(queue.current(), Some(&"b.mp3".to_string()));
+    }
+
+    #[test]
+    fn starts_images_as_slides() {
+        let queue = queue(&["a.jpg", "b.mp4"], 0);
+
+        assert!(matches!(queue.instruction(), Instruction::Show { path, .. } if path == "a.jpg"));
+        assert_eq!(Queue::default().instruction(), Instruction::Stop);
+    }
+
+    #[test]
+    fn refuses_slides_without_a_positive_interval() {
+        let show = |interval| Instruction::Show {
+            path: "a.jpg".to_string(),
+            settings: SlideSettings { interval, ..SlideSettings::default() }
+        };
+
+        assert_eq!(show(5.0).check(), Ok(()));
+
+        for interval in [0.0, -1.0, f64::NAN, f64::INFINITY] {
+            assert!(show(interval).check().is_err(), "{}", interval);
+        }
+    }
+
+    #[test]
+    fn refuses_values_out_of_range() {
+        assert_eq!(Instruction::Seek { at: 0.0 }.check(), Ok(()));
+        assert_eq!(Instruction::Volume { volume: 1.0 }.check(), Ok(()));
+        assert_eq!(Instruction::Rate { rate: MAX_RATE }.check(), Ok(()));
+
+        let refused = [
+            Instruction::Seek { at: -1.0 },
+            Instruction::Seek { at: f64::NAN },
+            Instruction::Play { path: "a.mp4".to_string(), from: f64::INFINITY },
+            Instruction::Pause { path: "a.mp4".to_string(), at: -0.5 },
+            Instruction::Volume { volume: 1.5 },
+            Instruction::Volume { volume: f64::NAN },
+            Instruction::Rate { rate: 0.0 },
+            Instruction::Rate { rate: f64::INFINITY }
+        ];
+
+        for i in refused {
+            assert!(i.check().is_err(), "{:?}", i);
+        }
+    }
 }
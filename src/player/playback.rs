@@ -0,0 +1,176 @@
+use crate::player::Phase;
+
+/// The media element events a display passes on to its `Playback`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum MediaEvent {
+    /// A new source was set, either to play or to wait paused
+    Load { play: bool },
+    /// `play`: playback was asked for, but may not have started
+    Play,
+    /// `playing`: playback has started, or resumed after stalling
+    Playing,
+    Pause,
+    /// `waiting`: playback stalled for lack of data
+    Waiting,
+    /// `canplay`: enough has loaded to start
+    CanPlay,
+    Seeking,
+    Seeked,
+    Ended,
+    Error(String)
+}
+
+/// Follows a display's media element through its events. Elements report what has
+/// happened rather than what they're doing, so whether playback is wanted is tracked
+/// too, to tell stalling from pausing.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Playback {
+    phase: Phase,
+    wants_play: bool
+}
+
+impl Default for Playback {
+    fn default() -> Self {
+        Self {
+            phase: Phase::Loading,
+            wants_play: false
+        }
+    }
+}
+
+impl Playback {
+    pub fn phase(&self) -> &Phase {
+        &self.phase
+    }
+
+    pub fn apply(&mut self, event: MediaEvent) {
+        // A failed source stays failed until another is loaded
+        if matches!(self.phase, Phase::Error(_)) && !matches!(event, MediaEvent::Load { .. }) {
+            return;
+        }
+
+        let phase = std::mem::replace(&mut self.phase, Phase::Loading);
+
+        self.phase = match (event, phase) {
+            (MediaEvent::Load { play }, _) => {
+                self.wants_play = play;
+                Phase::Loading
+            },
+            (MediaEvent::Play, Phase::Paused | Phase::Ended) => {
+                self.wants_play = true;
+                Phase::Buffering
+            },
+            (MediaEvent::Play, phase) => {
+                self.wants_play = true;
+                phase
+            },
+            (MediaEvent::Playing, _) => {
+                self.wants_play = true;
+                Phase::Playing
+            },
+            // Pausing while loading or seeking only decides what happens once that's done
+            (MediaEvent::Pause, phase @ (Phase::Loading | Phase::Seeking | Phase::Ended)) => {
+                self.wants_play = false;
+                phase
+            },
+            (MediaEvent::Pause, _) => {
+                self.wants_play = false;
+                Phase::Paused
+            },
+            (MediaEvent::Waiting, Phase::Playing) if self.wants_play => Phase::Buffering,
+            (MediaEvent::Waiting, phase) => phase,
+            (MediaEvent::CanPlay, Phase::Loading | Phase::Buffering) if !self.wants_play => Phase::Paused,
+            (MediaEvent::CanPlay, phase) => phase,
+            (MediaEvent::Seeking, _) => Phase::Seeking,
+            (MediaEvent::Seeked, _) if self.wants_play => Phase::Playing,
+            (MediaEvent::Seeked, _) => Phase::Paused,
+            (MediaEvent::Ended, _) => {
+                self.wants_play = false;
+                Phase::Ended
+            },
+            (MediaEvent::Error(message), _) => {
+                self.wants_play = false;
+                Phase::Error(message)
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn after(events: Vec<MediaEvent>) -> Phase {
+        let mut playback = Playback::default();
+
+        for event in events {
+            playback.apply(event);
+        }
+
+        playback.phase().clone()
+    }
+
+    #[test]
+    fn loads_then_plays_or_waits_paused() {
+        assert_eq!(after(vec![MediaEvent::Load { play: true }]), Phase::Loading);
+        assert_eq!(after(vec![MediaEvent::Load { play: true }, MediaEvent::CanPlay]), Phase::Loading);
+        assert_eq!(after(vec![MediaEvent::Load { play: true }, MediaEvent::CanPlay, MediaEvent::Playing]), Phase::Playing);
+        assert_eq!(after(vec![MediaEvent::Load { play: false }, MediaEvent::CanPlay]), Phase::Paused);
+    }
+
+    #[test]
+    fn stalls_and_recovers() {
+        let playing = vec![MediaEvent::Load { play: true }, MediaEvent::Playing];
+
+        assert_eq!(after([playing.clone(), vec![MediaEvent::Waiting]].concat()), Phase::Buffering);
+        assert_eq!(after([playing, vec![MediaEvent::Waiting, MediaEvent::Playing]].concat()), Phase::Playing);
+    }
+
+    #[test]
+    fn waiting_while_paused_isnt_a_stall() {
+        let events = vec![MediaEvent::Load { play: true }, MediaEvent::Playing, MediaEvent::Pause, MediaEvent::Waiting];
+        assert_eq!(after(events), Phase::Paused);
+    }
+
+    #[test]
+    fn seeks_back_to_what_was_wanted() {
+        let playing = vec![MediaEvent::Load { play: true }, MediaEvent::Playing];
+        let paused = vec![MediaEvent::Load { play: false }, MediaEvent::CanPlay];
+
+        assert_eq!(after([playing.clone(), vec![MediaEvent::Seeking]].concat()), Phase::Seeking);
+        assert_eq!(after([playing, vec![MediaEvent::Seeking, MediaEvent::Seeked]].concat()), Phase::Playing);
+        assert_eq!(after([paused, vec![MediaEvent::Seeking, MediaEvent::Seeked]].concat()), Phase::Paused);
+    }
+
+    #[test]
+    fn pausing_while_loading_pauses_once_loaded() {
+        let events = vec![MediaEvent::Load { play: true }, MediaEvent::Pause];
+        assert_eq!(after(events.clone()), Phase::Loading);
+        assert_eq!(after([events, vec![MediaEvent::CanPlay]].concat()), Phase::Paused);
+    }
+
+    #[test]
+    fn pausing_while_seeking_pauses_once_seeked() {
+        let events = vec![MediaEvent::Load { play: true }, MediaEvent::Playing, MediaEvent::Seeking, MediaEvent::Pause];
+        assert_eq!(after(events.clone()), Phase::Seeking);
+        assert_eq!(after([events, vec![MediaEvent::Seeked]].concat()), Phase::Paused);
+    }
+
+    #[test]
+    fn ends_and_plays_again() {
+        let ended = vec![MediaEvent::Load { play: true }, MediaEvent::Playing, MediaEvent::Ended];
+
+        assert_eq!(after(ended.clone()), Phase::Ended);
+        assert_eq!(after([ended.clone(), vec![MediaEvent::Pause]].concat()), Phase::Ended);
+        assert_eq!(after([ended, vec![MediaEvent::Play]].concat()), Phase::Buffering);
+    }
+
+    #[test]
+    fn errors_stick_until_the_next_load() {
+        let failed = vec![MediaEvent::Load { play: true }, MediaEvent::Error("Unsupported".to_string())];
+
+        assert_eq!(after(failed.clone()), Phase::Error("Unsupported".to_string()));
+        assert_eq!(after([failed.clone(), vec![MediaEvent::Playing]].concat()), Phase::Error("Unsupported".to_string()));
+        assert_eq!(after([failed, vec![MediaEvent::Load { play: true }]].concat()), Phase::Loading);
+    }
+}
@@ -0,0 +1,778 @@
+use cfg_if::cfg_if;
+#[allow(unused_imports)]
+use crate::error::PlayerError;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+
+pub static PROFILE_COOKIE : &'static str = "player_profile";
+
+/// Share of a video that must be played for it to count as watched.
+static WATCHED_FRACTION : f64 = 0.95;
+/// Most extra time that can be given at once
+static MAX_EXTRA_MINUTES : u32 = 24 * 60;
+/// Requests without a profile are refused once there are kid profiles, so adults need one
+static NEEDS_NORMAL_PROFILE : &'static str = "Keep a normal profile for adults, as browsers without a profile can't see anything while there are kid profiles";
+
+/// Ratings read from `.nfo` files, from least to most mature. Ratings in the same group
+/// are treated as equal, and the first of each is offered as a limit.
+pub static RATINGS : &'static [&'static [&'static str]] = &[
+    &["G", "TV-Y", "TV-G"],
+    &["TV-Y7"],
+    &["PG", "TV-PG"],
+    &["PG-13", "TV-14"],
+    &["R", "TV-MA"],
+    &["NC-17"]
+];
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Profile {
+    pub id: u64,
+    pub name: String,
+    pub has_pin: bool,
+    pub settings: ProfileSettings,
+    /// Set for kid profiles
+    pub restrictions: Option<Restrictions>,
+    /// Viewing time left today, if the profile has a daily limit
+    pub minutes_left: Option<u32>
+}
+
+/// What a kid profile can see and do. Kid profiles can't delete anything or use admin pages.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct Restrictions {
+    /// Libraries or folders the profile can see, as media paths like `kids` or `films/Animation`.
+    /// Everything if empty.
+    pub folders: Vec<String>,
+    /// The most mature rating allowed, e.g. "PG". Videos without a rating are hidden when set.
+    pub max_rating: Option<String>,
+    /// Minutes of playback allowed each day
+    pub daily_minutes: Option<u32>
+}
+
+impl Restrictions {
+    /// Whether a media path is inside one of the allowed folders.
+    pub fn allows_path(&self, path: &str) -> bool {
+        self.folders.is_empty() || self.folders.iter().any(|folder| is_within(path, folder))
+    }
+
+    /// Whether a folder is allowed or leads to one that is, so it can be browsed.
+    pub fn reaches(&self, dir: &str) -> bool {
+        self.allows_path(dir) || self.folders.iter().any(|folder| is_within(folder, dir))
+    }
+
+    /// Whether a video with `rating` can be played. Unrated videos only pass without a limit.
+    pub fn allows_rating(&self, rating: Option<&str>) -> bool {
+        match &self.max_rating {
+            None => true,
+            Some(max) => match (rating.and_then(rating_level), rating_level(max)) {
+                (Some(level), Some(max)) => level <= max,
+                _ => false
+            }
+        }
+    }
+}
+
+/// Where a rating falls in `RATINGS`, ignoring case and prefixes like "Rated " or "US:".
+pub fn rating_level(rating: &str) -> Option<usize> {
+    let rating = rating.rsplit(':').next().unwrap_or(rating).trim().to_uppercase();
+    let rating = rating.strip_prefix("RATED ").unwrap_or(&rating).trim();
+
+    RATINGS.iter().position(|group| group.contains(&rating))
+}
+
+fn is_within(path: &str, folder: &str) -> bool {
+    path == folder || path.strip_prefix(folder).map(|rest| rest.starts_with('/')).unwrap_or(false)
+}
+
+/// Preferences which override the server config for one profile.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ProfileSettings {
+    pub skip_back: Option<f64>,
+    pub skip_forward: Option<f64>,
+    /// Language code, e.g. "en", matched against audio tracks
+    pub audio_language: Option<String>,
+    /// Language code matched against subtitle files, e.g. "en" for `Film.en.vtt`
+    pub subtitle_language: Option<String>
+}
+
+/// What a profile has watched and liked, keyed by media path.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct ProfileMedia {
+    /// Seconds into each partly watched video
+    pub resume: BTreeMap<String, f64>,
+    pub watched: BTreeSet<String>,
+    pub favorites: BTreeSet<String>
+}
+
+impl ProfileMedia {
+    /// Notes how far into `path` playback has got, marking it watched near the end.
+    pub fn record(&mut self, path: &str, position: f64, duration: f64) {
+        if duration > 0.0 && position >= duration * WATCHED_FRACTION {
+            self.resume.remove(path);
+            self.watched.insert(path.to_string());
+        } else if position > 0.0 {
+            self.resume.insert(path.to_string(), position);
+        }
+    }
+}
+
+#[server(ListProfiles, "/api")]
+pub async fn list_profiles(cx: Scope) -> Result<Vec<Profile>, ServerFnError> {
+    let profiles = expect_context::<Profiles>(cx);
+    Ok(profiles.list())
+}
+
+/// The profile this browser has selected, if any.
+#[server(GetCurrentProfile, "/api")]
+pub async fn get_current_profile(cx: Scope) -> Result<Option<Profile>, ServerFnError> {
+    Ok(current_profile(cx))
+}
+
+#[server(SelectProfile, "/api")]
+pub async fn select_profile(cx: Scope, id: u64, pin: String) -> Result<(), ServerFnError> {
+    use crate::devices::{current_device, set_cookie};
+
+    let key = unlock(cx, id, &pin)?;
+
+    if let Some(device) = current_device(cx) {
+        expect_context::<Devices>(cx)
+            .set_profile(device.id, id)
+            .map_err(PlayerError::from)?;
+    }
+
+    set_cookie(cx, PROFILE_COOKIE, &key);
+    Ok(())
+}
+
+#[server(AddProfile, "/api")]
+pub async fn add_profile(cx: Scope, name: String, pin: String) -> Result<(), ServerFnError> {
+    require_unrestricted(cx)?;
+
+    let profiles = expect_context::<Profiles>(cx);
+
+    let name = name.trim().to_string();
+    let pin = Some(pin.trim().to_string()).filter(|pin| !pin.is_empty());
+
+    if name.is_empty() {
+        return Err(PlayerError::InvalidInput("Profiles need a name".to_string()).into());
+    }
+
+    if pin.as_ref().map(|pin| !pin.chars().all(|c| c.is_ascii_digit())).unwrap_or(false) {
+        return Err(PlayerError::InvalidInput("PINs can only contain digits".to_string()).into());
+    }
+
+    profiles
+        .add(name, pin)
+        .map_err(|e| PlayerError::from(e).into())
+}
+
+/// Deletes a profile, which needs its PIN if it has one.
+#[server(DeleteProfile, "/api")]
+pub async fn delete_profile(cx: Scope, id: u64, pin: String) -> Result<(), ServerFnError> {
+    require_unrestricted(cx)?;
+
+    let profiles = expect_context::<Profiles>(cx);
+
+    unlock(cx, id, &pin)?;
+
+    if !profiles.keeps_normal_profile(id) && profiles.list().iter().any(|profile| profile.id != id && profile.restrictions.is_some()) {
+        return Err(PlayerError::InvalidInput(NEEDS_NORMAL_PROFILE.to_string()).into());
+    }
+
+    profiles
+        .update(|profiles| profiles.retain(|profile| profile.id != id))
+        .map_err(|e| PlayerError::from(e).into())
+}
+
+#[server(SetProfileSettings, "/api")]
+pub async fn set_profile_settings(cx: Scope, settings: ProfileSettings) -> Result<(), ServerFnError> {
+    let profiles = expect_context::<Profiles>(cx);
+    let profile = current_profile(cx).ok_or_else(|| PlayerError::InvalidInput("No profile selected".to_string()))?;
+
+    profiles
+        .update(|profiles| {
+            if let Some(stored) = profiles.iter_mut().find(|stored| stored.id == profile.id) {
+                stored.settings = settings;
+            }
+        })
+        .map_err(|e| PlayerError::from(e).into())
+}
+
+/// Makes a profile a kid profile, or a normal one with `None`.
+#[server(SetProfileRestrictions, "/api")]
+pub async fn set_profile_restrictions(cx: Scope, id: u64, restrictions: Option<Restrictions>) -> Result<(), ServerFnError> {
+    require_unrestricted(cx)?;
+
+    let profiles = expect_context::<Profiles>(cx);
+
+    let restrictions = match restrictions {
+        Some(mut restrictions) => {
+            restrictions.folders = restrictions.folders
+                .iter()
+                .map(|folder| folder.trim().trim_matches('/').to_string())
+                .filter(|folder| !folder.is_empty())
+                .collect();
+
+            if let Some(max) = &restrictions.max_rating {
+                if rating_level(max).is_none() {
+                    return Err(PlayerError::InvalidInput(format!("Unknown rating {}", max)).into());
+                }
+            }
+
+            if restrictions.daily_minutes == Some(0) {
+                return Err(PlayerError::InvalidInput("A daily limit needs at least a minute".to_string()).into());
+            }
+
+            Some(restrictions)
+        },
+        None => None
+    };
+
+    if restrictions.is_some() && !profiles.keeps_normal_profile(id) {
+        return Err(PlayerError::InvalidInput(NEEDS_NORMAL_PROFILE.to_string()).into());
+    }
+
+    profiles
+        .update(|profiles| {
+            if let Some(stored) = profiles.iter_mut().find(|stored| stored.id == id) {
+                stored.restrictions = restrictions;
+            }
+        })
+        .map_err(|e| PlayerError::from(e).into())
+}
+
+/// Extends a kid profile's viewing time for today, by up to a day. Needs the admin PIN or the
+/// PIN of a normal profile, and wrong ones count against the client like wrong admin PINs.
+#[server(GrantExtraTime, "/api")]
+pub async fn grant_extra_time(cx: Scope, id: u64, pin: String, minutes: u32) -> Result<(), ServerFnError> {
+    use crate::config::Config;
+    use crate::devices::{client, secrets_match, Devices, Secret};
+
+    let config = expect_context::<Config>(cx);
+    let devices = expect_context::<Devices>(cx);
+    let profiles = expect_context::<Profiles>(cx);
+
+    if !(1..=MAX_EXTRA_MINUTES).contains(&minutes) {
+        return Err(PlayerError::InvalidInput(format!("Extra time must be 1 to {} minutes", MAX_EXTRA_MINUTES)).into());
+    }
+
+    devices.attempt(client(cx), Secret::Admin, || {
+        let is_admin = config.auth.admin_pin.as_deref().map(|admin_pin| secrets_match(&pin, admin_pin)).unwrap_or(false);
+
+        match is_admin || profiles.is_adult_pin(&pin) {
+            true => Ok(()),
+            false => Err(PlayerError::Unauthorized("That isn't an adult PIN".to_string()))
+        }
+    })?;
+
+    profiles
+        .grant(id, minutes)
+        .map_err(|e| PlayerError::from(e).into())
+}
+
+/// Resume points, watched state and favorites for the current profile. Empty without one.
+#[server(GetProfileMedia, "/api")]
+pub async fn get_profile_media(cx: Scope) -> Result<ProfileMedia, ServerFnError> {
+    let profiles = expect_context::<Profiles>(cx);
+
+    Ok(current_profile(cx)
+        .and_then(|profile| profiles.media(profile.id))
+        .unwrap_or_default())
+}
+
+#[server(ToggleFavorite, "/api")]
+pub async fn toggle_favorite(cx: Scope, path: String) -> Result<(), ServerFnError> {
+    let profiles = expect_context::<Profiles>(cx);
+    let profile = current_profile(cx).ok_or_else(|| PlayerError::InvalidInput("Pick a profile to keep favorites".to_string()))?;
+
+    profiles
+        .update(|profiles| {
+            if let Some(stored) = profiles.iter_mut().find(|stored| stored.id == profile.id) {
+                let favorites = &mut stored.media.favorites;
+
+                if !favorites.remove(&path) {
+                    favorites.insert(path);
+                }
+            }
+        })
+        .map_err(|e| PlayerError::from(e).into())
+}
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use crate::devices::{client, cookie, current_device, request_cookie, secrets_match, token_from_headers, Device, DeviceKind, Devices, Secret};
+    use crate::displays::Displays;
+    use crate::library::Libraries;
+    use crate::player::{Instruction, Phase, PlayerState};
+    use crate::store::{self, Migration};
+    use chrono::Local;
+    use http::header::HeaderMap;
+    use rand::Rng;
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::{Arc, PoisonError, RwLock};
+    use std::time::Duration;
+
+    static TICK : Duration = Duration::from_secs(5);
+    /// Ticks between saves of playback progress and viewing time
+    static SAVE_EVERY : u64 = 3;
+
+    static MIGRATIONS : &'static [Migration] = &[store::unversioned];
+
+    #[derive(Clone, Debug, Deserialize, Serialize)]
+    pub struct StoredProfile {
+        pub id: u64,
+        pub name: String,
+        pin: Option<String>,
+        /// Secret held in the profile cookie, so choosing a PIN protected profile can't be faked
+        key: String,
+        #[serde(default)]
+        pub settings: ProfileSettings,
+        #[serde(default)]
+        pub media: ProfileMedia,
+        #[serde(default)]
+        pub restrictions: Option<Restrictions>,
+        #[serde(default)]
+        usage: Usage
+    }
+
+    /// Viewing time on one day, for profiles with a daily limit.
+    #[derive(Clone, Debug, Default, Deserialize, Serialize)]
+    struct Usage {
+        date: String,
+        seconds: f64,
+        extra_minutes: u32
+    }
+
+    impl StoredProfile {
+        fn profile(&self) -> Profile {
+            Profile {
+                id: self.id,
+                name: self.name.clone(),
+                has_pin: self.pin.is_some(),
+                settings: self.settings.clone(),
+                restrictions: self.restrictions.clone(),
+                minutes_left: self.seconds_left().map(|seconds| (seconds / 60.0).ceil() as u32)
+            }
+        }
+
+        /// Viewing time left today, if limited.
+        fn seconds_left(&self) -> Option<f64> {
+            let daily = self.restrictions.as_ref()?.daily_minutes?;
+
+            let (seconds, extra) = if self.usage.date == today() {
+                (self.usage.seconds, self.usage.extra_minutes)
+            } else {
+                (0.0, 0)
+            };
+
+            Some((daily.saturating_add(extra) as f64 * 60.0 - seconds).max(0.0))
+        }
+
+        /// Today's usage, starting afresh on a new day.
+        fn usage_today(&mut self) -> &mut Usage {
+            let today = today();
+
+            if self.usage.date != today {
+                self.usage = Usage { date: today, ..Usage::default() };
+            }
+
+            &mut self.usage
+        }
+    }
+
+    /// Profiles, persisted as JSON. Edits are saved straight away, while playback progress
+    /// is saved by `run` every so often.
+    #[derive(Clone, Debug)]
+    pub struct Profiles {
+        path: PathBuf,
+        profiles: Arc<RwLock<Vec<StoredProfile>>>,
+        unsaved: Arc<AtomicBool>
+    }
+
+    impl Profiles {
+        /// Loads profiles from `path`, starting empty if it doesn't exist yet.
+        pub fn load(path: PathBuf) -> Result<Self, String> {
+            let profiles = store::load(&path, MIGRATIONS)?;
+
+            Ok(Self {
+                path,
+                profiles: Arc::new(RwLock::new(profiles)),
+                unsaved: Arc::new(AtomicBool::new(false))
+            })
+        }
+
+        pub fn list(&self) -> Vec<Profile> {
+            self.profiles.read().unwrap_or_else(PoisonError::into_inner).iter().map(StoredProfile::profile).collect()
+        }
+
+        pub fn by_key(&self, key: &str) -> Option<Profile> {
+            self.profiles
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .find(|stored| stored.key == key)
+                .map(StoredProfile::profile)
+        }
+
+        pub fn by_id(&self, id: u64) -> Option<Profile> {
+            self.profiles
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .find(|stored| stored.id == id)
+                .map(StoredProfile::profile)
+        }
+
+        /// Whether any profile is a kid profile.
+        pub fn has_kids(&self) -> bool {
+            self.profiles.read().unwrap_or_else(PoisonError::into_inner).iter().any(|stored| stored.restrictions.is_some())
+        }
+
+        /// Whether a normal profile other than `id` exists, for adults to pick.
+        pub fn keeps_normal_profile(&self, id: u64) -> bool {
+            self.profiles
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .any(|stored| stored.id != id && stored.restrictions.is_none())
+        }
+
+        pub fn media(&self, id: u64) -> Option<ProfileMedia> {
+            self.profiles
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .find(|stored| stored.id == id)
+                .map(|stored| stored.media.clone())
+        }
+
+        /// Checks `pin` against the profile's, returning the key for its cookie.
+        pub fn unlock(&self, id: u64, pin: &str) -> Result<String, PlayerError> {
+            let profiles = self.profiles.read().unwrap_or_else(PoisonError::into_inner);
+            let stored = profiles
+                .iter()
+                .find(|stored| stored.id == id)
+                .ok_or_else(|| PlayerError::NotFound("That profile".to_string()))?;
+
+            match &stored.pin {
+                Some(expected) if !secrets_match(pin, expected) => Err(PlayerError::Unauthorized("Wrong PIN".to_string())),
+                _ => Ok(stored.key.clone())
+            }
+        }
+
+        pub fn add(&self, name: String, pin: Option<String>) -> std::io::Result<()> {
+            let mut rng = rand::thread_rng();
+            let key = (0..16).map(|_| format!("{:02x}", rng.gen::<u8>())).collect::<String>();
+
+            self.update(|profiles| {
+                let id = profiles.iter().map(|stored| stored.id).max().unwrap_or(0) + 1;
+
+                profiles.push(StoredProfile {
+                    id,
+                    name,
+                    pin,
+                    key,
+                    settings: ProfileSettings::default(),
+                    media: ProfileMedia::default(),
+                    restrictions: None,
+                    usage: Usage::default()
+                });
+            })
+        }
+
+        /// Records playback progress in memory, to be saved by `run`.
+        pub fn record(&self, id: u64, path: &str, position: f64, duration: f64) {
+            let mut profiles = self.profiles.write().unwrap_or_else(PoisonError::into_inner);
+
+            if let Some(stored) = profiles.iter_mut().find(|stored| stored.id == id) {
+                stored.media.record(path, position, duration);
+                self.unsaved.store(true, Ordering::Relaxed);
+            }
+        }
+
+        /// Counts playback time against a limited profile, returning whether it has any left.
+        pub fn spend(&self, id: u64, time: Duration) -> bool {
+            let mut profiles = self.profiles.write().unwrap_or_else(PoisonError::into_inner);
+
+            let Some(stored) = profiles.iter_mut().find(|stored| stored.id == id) else {
+                return true;
+            };
+
+            if stored.seconds_left().is_none() {
+                return true;
+            }
+
+            stored.usage_today().seconds += time.as_secs_f64();
+            self.unsaved.store(true, Ordering::Relaxed);
+
+            stored.seconds_left().map(|left| left > 0.0).unwrap_or(true)
+        }
+
+        /// Refuses a profile that has used up today's viewing time.
+        pub fn check_time(&self, id: u64) -> Result<(), PlayerError> {
+            let out_of_time = self.profiles
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .find(|stored| stored.id == id)
+                .and_then(StoredProfile::seconds_left)
+                .map(|left| left <= 0.0)
+                .unwrap_or(false);
+
+            match out_of_time {
+                true => Err(PlayerError::Forbidden("No viewing time left today".to_string())),
+                false => Ok(())
+            }
+        }
+
+        pub fn grant(&self, id: u64, minutes: u32) -> std::io::Result<()> {
+            self.update(|profiles| {
+                if let Some(stored) = profiles.iter_mut().find(|stored| stored.id == id) {
+                    let usage = stored.usage_today();
+                    usage.extra_minutes = usage.extra_minutes.saturating_add(minutes);
+                }
+            })
+        }
+
+        /// Whether `pin` belongs to a profile without restrictions.
+        pub fn is_adult_pin(&self, pin: &str) -> bool {
+            self.profiles
+                .read()
+                .unwrap_or_else(PoisonError::into_inner)
+                .iter()
+                .filter(|stored| stored.restrictions.is_none())
+                .filter_map(|stored| stored.pin.as_deref())
+                .fold(false, |found, expected| secrets_match(pin, expected) || found)
+        }
+
+        pub fn update(&self, f: impl FnOnce(&mut Vec<StoredProfile>)) -> std::io::Result<()> {
+            let mut profiles = self.profiles.write().unwrap_or_else(PoisonError::into_inner);
+            f(&mut profiles);
+            self.save(&profiles)
+        }
+
+        /// Saves playback progress and viewing time recorded since the last save.
+        pub fn flush(&self) -> std::io::Result<()> {
+            if !self.unsaved.load(Ordering::Relaxed) {
+                return Ok(());
+            }
+
+            // Held through the write, so nothing recorded meanwhile is marked saved
+            let stored = self.profiles.read().unwrap_or_else(PoisonError::into_inner);
+            self.save(&stored)
+        }
+
+        /// Writes `profiles` out, which callers hold a lock on. Only marks them saved once
+        /// written, so a failed write is retried by the next flush.
+        fn save(&self, profiles: &[StoredProfile]) -> std::io::Result<()> {
+            store::save(&self.path, MIGRATIONS.len(), &profiles)?;
+            self.unsaved.store(false, Ordering::Relaxed);
+            Ok(())
+        }
+    }
+
+    /// The profile chosen by the browser making the current request.
+    pub fn current_profile(cx: Scope) -> Option<Profile> {
+        profile_for(cx, current_device(cx).as_ref())
+    }
+
+    /// The profile picked on a paired device, or failing that the browser's cookie.
+    fn profile_for(cx: Scope, device: Option<&Device>) -> Option<Profile> {
+        let devices = expect_context::<Devices>(cx);
+        let profiles = expect_context::<Profiles>(cx);
+        let key = request_cookie(cx, PROFILE_COOKIE);
+
+        picked(&profiles, &devices, device, key.as_deref())
+    }
+
+    fn picked(profiles: &Profiles, devices: &Devices, device: Option<&Device>, key: Option<&str>) -> Option<Profile> {
+        device
+            .and_then(|device| devices.profile(device.id))
+            .and_then(|id| profiles.by_id(id))
+            .or_else(|| key.and_then(|key| profiles.by_key(key)))
+    }
+
+    /// The libraries as the current profile may see them.
+    pub fn profile_libraries(cx: Scope) -> Result<Libraries, PlayerError> {
+        let libraries = expect_context::<Libraries>(cx);
+        let profiles = expect_context::<Profiles>(cx);
+        let device = current_device(cx);
+
+        let restrictions = restrictions(&profiles, device.as_ref(), profile_for(cx, device.as_ref()), false)?;
+        Ok(restrict(libraries, restrictions))
+    }
+
+    /// As `profile_libraries`, for routes outside server functions, like media files. With
+    /// auth off, displays can't be told apart from remotes here, so a request without a
+    /// profile gets every library.
+    pub fn libraries_for(
+        libraries: &Libraries,
+        profiles: &Profiles,
+        devices: &Devices,
+        auth_enabled: bool,
+        headers: &HeaderMap
+    ) -> Result<Libraries, PlayerError> {
+        let device = token_from_headers(headers).and_then(|token| devices.authenticate(token));
+        let profile = picked(profiles, devices, device.as_ref(), cookie(headers, PROFILE_COOKIE));
+
+        let restrictions = restrictions(profiles, device.as_ref(), profile, !auth_enabled)?;
+        Ok(restrict(libraries.clone(), restrictions))
+    }
+
+    /// The restrictions on a request. One without a profile is refused while there are kid
+    /// profiles, as clearing cookies would otherwise get past them, unless it comes from an
+    /// admin or display device, or is `trusted` by the caller.
+    fn restrictions(
+        profiles: &Profiles,
+        device: Option<&Device>,
+        profile: Option<Profile>,
+        trusted: bool
+    ) -> Result<Option<Restrictions>, PlayerError> {
+        if let Some(profile) = profile {
+            return Ok(profile.restrictions);
+        }
+
+        let is_trusted = trusted || device
+            .map(|device| matches!(device.kind, DeviceKind::Admin | DeviceKind::Display))
+            .unwrap_or(false);
+
+        match is_trusted || !profiles.has_kids() {
+            true => Ok(None),
+            false => Err(PlayerError::Forbidden("Pick a profile first".to_string()))
+        }
+    }
+
+    fn restrict(libraries: Libraries, restrictions: Option<Restrictions>) -> Libraries {
+        match restrictions {
+            Some(restrictions) => libraries.restricted(restrictions),
+            None => libraries
+        }
+    }
+
+    /// Checks a profile's PIN, returning the key for its cookie. Wrong PINs count against the
+    /// client like wrong admin PINs, as a normal profile's PIN can also grant extra time.
+    fn unlock(cx: Scope, id: u64, pin: &str) -> Result<String, PlayerError> {
+        let devices = expect_context::<Devices>(cx);
+        let profiles = expect_context::<Profiles>(cx);
+
+        devices.attempt(client(cx), Secret::Admin, || profiles.unlock(id, pin))
+    }
+
+    /// Refuses kid profiles, and requests without a profile as `profile_libraries` does, for
+    /// anything that deletes or administers.
+    pub fn require_unrestricted(cx: Scope) -> Result<(), ServerFnError> {
+        let profiles = expect_context::<Profiles>(cx);
+        let device = current_device(cx);
+
+        match restrictions(&profiles, device.as_ref(), profile_for(cx, device.as_ref()), false)? {
+            Some(_) => Err(PlayerError::Forbidden("Kid profiles can't do that".to_string()).into()),
+            None => Ok(())
+        }
+    }
+
+    /// Counts viewing time for profiles with a daily limit, stopping their displays once it
+    /// runs out, and saves progress every so often.
+    pub async fn run(profiles: Profiles, displays: Displays) {
+        let mut ticks = 0u64;
+
+        loop {
+            tokio::time::sleep(TICK).await;
+            ticks += 1;
+
+            displays.for_each(|_, session| {
+                let Some(id) = session.profile else {
+                    return;
+                };
+
+                let is_playing = session.is_online()
+                    && matches!(&session.player, PlayerState::Media(media) if media.phase == Phase::Playing);
+
+                if is_playing && !profiles.spend(id, TICK) {
+                    session.queue.clear();
+                    session.instructions.push_back(Instruction::Stop);
+                }
+            });
+
+            if ticks % SAVE_EVERY == 0 {
+                if let Err(e) = profiles.flush() {
+                    log::warn!("Couldn't save profiles: {}", e);
+                }
+            }
+        }
+    }
+
+    fn today() -> String {
+        Local::now().format("%Y-%m-%d").to_string()
+    }
+
+    //
+    //
+    //
+}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn restrictions(folders: &[&str], max_rating: Option<&str>) -> Restrictions {
+        Restrictions {
+            folders: folders.iter().map(|folder| folder.to_string()).collect(),
+            max_rating: max_rating.map(str::to_string),
+            daily_minutes: None
+        }
+    }
+
+    #[test]
+    fn reads_ratings_loosely() {
+        assert_eq!(rating_level("G"), Some(0));
+        assert_eq!(rating_level("tv-g"), Some(0));
+        assert_eq!(rating_level("Rated PG-13"), Some(3));
+        assert_eq!(rating_level("US:R"), Some(4));
+        assert_eq!(rating_level(" NC-17 "), Some(5));
+        assert_eq!(rating_level("Unrated"), None);
+        assert_eq!(rating_level(""), None);
+    }
+
+    #[test]
+    fn allows_ratings_up_to_the_limit() {
+        let pg = restrictions(&[], Some("PG"));
+
+        assert!(pg.allows_rating(Some("G")));
+        assert!(pg.allows_rating(Some("TV-PG")));
+        assert!(!pg.allows_rating(Some("PG-13")));
+        assert!(!pg.allows_rating(Some("Unrated")));
+        assert!(!pg.allows_rating(None));
+
+        let unlimited = restrictions(&[], None);
+        assert!(unlimited.allows_rating(None));
+        assert!(unlimited.allows_rating(Some("NC-17")));
+    }
+
+    #[test]
+    fn allows_paths_inside_allowed_folders() {
+        let kids = restrictions(&["kids", "films/Animation"], None);
+
+        assert!(kids.allows_path("kids/Film.mkv"));
+        assert!(kids.allows_path("films/Animation"));
+        assert!(kids.allows_path("films/Animation/Up.mkv"));
+        assert!(!kids.allows_path("films/Animation Extra/Film.mkv"));
+        assert!(!kids.allows_path("films/Horror.mkv"));
+        assert!(!kids.allows_path("kidsfilms/Film.mkv"));
+
+        assert!(restrictions(&[], None).allows_path("films/Horror.mkv"));
+    }
+
+    #[test]
+    fn reaches_folders_leading_to_allowed_ones() {
+        let kids = restrictions(&["films/Animation"], None);
+
+        assert!(kids.reaches("films"));
+        assert!(kids.reaches("films/Animation/Pixar"));
+        assert!(!kids.reaches("films/Horror"));
+        assert!(!kids.reaches("music"));
+    }
+}
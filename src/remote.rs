@@ -1,42 +1,277 @@
+mod activity;
 mod browse;
+mod devices;
 mod fns;
 mod media;
+mod mini_player;
+mod profiles;
+mod queue;
+mod schedules;
+mod settings;
+mod sleep;
+mod slideshow;
 
-use crate::error::DisplayError;
+use crate::control::{get_remote, SetRemoteName};
+use crate::displays::DEFAULT_DISPLAY;
+use crate::error::ErrorAlert;
+use crate::icons::Icon;
 use crate::loading::Loading;
-use crate::player::PlayerState;
+use crate::player::{Activity, MediaState, Phase, PlayerState, Queue, Remote, Repeat, SleepStatus, Status};
+use crate::remote::activity::{ActivityFeed, ControlAlert};
 use crate::remote::browse::Browse;
 use crate::remote::fns::*;
-use crate::remote::media::Media;
+use crate::remote::media::{Finished, Media};
+use crate::remote::mini_player::MiniPlayer;
+use crate::remote::queue::QueueList;
+use crate::remote::settings::Settings;
+use crate::socket::{create_server_signal, use_connection, Connection, BASE_PATH};
+use crate::toast::{provide_toasts, ToastList, Toasts};
 use leptos::*;
-use leptos_server_signal::create_server_signal;
+use leptos_router::{use_query_map, Outlet, A};
+use std::collections::BTreeMap;
 
+pub use crate::remote::devices::Devices;
+pub use crate::remote::profiles::Profiles;
+pub use crate::remote::schedules::Schedules;
+
+/// The display this remote is currently controlling.
+#[derive(Clone, Copy)]
+pub struct SelectedDisplay(pub RwSignal<String>);
+
+/// Every display's status, and the selected one's split up so each part only re-renders
+/// what uses it.
+#[derive(Clone, Copy)]
+pub struct DisplayStatus {
+    pub statuses: ReadSignal<BTreeMap<String, Status>>,
+    pub online: Memo<bool>,
+    pub last_seen: Memo<Option<String>>,
+    /// What the display is playing, while it's online. An offline display's state is only
+    /// its last known one, so there's nothing to control.
+    pub media: Memo<Option<MediaState>>,
+    pub queue: Memo<Queue>,
+    pub sleep: Memo<Option<SleepStatus>>,
+    pub control: Memo<Option<Remote>>,
+    pub activity: Memo<Vec<Activity>>,
+    /// The group the display is playing along with
+    pub group: Memo<Option<String>>
+}
+
+/// This remote, as the server knows it, and renaming it.
+#[derive(Clone, Copy)]
+pub struct ThisRemote {
+    pub remote: Signal<Option<Remote>>,
+    pub rename: Action<SetRemoteName, Result<(), ServerFnError>>
+}
+
+impl ThisRemote {
+    pub fn is(&self, remote: &Remote) -> bool {
+        self.remote.with(|this| this.as_ref().map(|this| this.id == remote.id).unwrap_or(false))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Tab {
+    Playing,
+    Library,
+    Queue,
+    Settings
+}
+
+static TABS : &'static [Tab] = &[Tab::Playing, Tab::Library, Tab::Queue, Tab::Settings];
+
+impl Tab {
+    fn key(&self) -> &'static str {
+        match self {
+            Self::Playing => "playing",
+            Self::Library => "library",
+            Self::Queue => "queue",
+            Self::Settings => "settings"
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Playing => "Now Playing",
+            Self::Library => "Library",
+            Self::Queue => "Queue",
+            Self::Settings => "Settings"
+        }
+    }
+
+    fn icon(&self) -> &'static str {
+        match self {
+            Self::Playing => "play-circle",
+            Self::Library => "folder",
+            Self::Queue => "list",
+            Self::Settings => "tune"
+        }
+    }
+
+    pub fn href(&self) -> String {
+        format!("{}/?tab={}", BASE_PATH, self.key())
+    }
+}
+
+/// The remote's current tab, from `?tab=`. Without one, what's playing if anything is,
+/// and the library otherwise.
+pub fn use_tab(cx: Scope) -> Memo<Tab> {
+    let query = use_query_map(cx);
+    let DisplayStatus { media, .. } = expect_context::<DisplayStatus>(cx);
+
+    create_memo(cx, move |_| {
+        let tab = query.with(|query| query.get("tab").cloned());
+
+        TABS.iter()
+            .copied()
+            .find(|t| Some(t.key()) == tab.as_deref())
+            .unwrap_or_else(|| if media.with(Option::is_some) { Tab::Playing } else { Tab::Library })
+    })
+}
+
+/// The frame around every remote page: navigation, the display picker, the now playing bar
+/// and toasts. The selected display and its status are shared by every page inside.
 #[component]
-pub fn Remote(cx: Scope) -> impl IntoView {
-    let player_state = create_server_signal::<PlayerState>(cx, "player_state");
+pub fn RemoteLayout(cx: Scope) -> impl IntoView {
+    let statuses = create_server_signal::<BTreeMap<String, Status>>(cx, "displays");
+    let selected = create_rw_signal(cx, DEFAULT_DISPLAY.to_string());
+    provide_context(cx, SelectedDisplay(selected));
+    let toasts = provide_toasts(cx);
 
-    // Split out to allow controls to minimize control re-renders
+    let rename = create_server_action::<SetRemoteName>(cx);
+    // Only fetched by the browser, so the cookie unpaired remotes are known by is set by a real response
+    let this_remote = create_local_resource(cx, move || rename.version().get(), move |_| get_remote(cx));
+    let this_remote = ThisRemote {
+        remote: Signal::derive(cx, move || this_remote.read(cx).and_then(|remote| remote.ok())),
+        rename
+    };
+    provide_context(cx, this_remote);
 
-    let is_playing = create_memo(
-        cx,
-        move |_| {
-            match player_state.get() {
-                PlayerState::Media(_) => true,
-                _ => false
+    // Fall back to the first connected display if the selected one has never been seen
+    create_effect(cx, move |_| {
+        statuses.with(|statuses| {
+            if !statuses.contains_key(&selected.get_untracked()) {
+                if let Some(name) = statuses.keys().next() {
+                    selected.set(name.clone());
+                }
             }
-        }
+        })
+    });
+
+    let status = create_memo(
+        cx,
+        move |_| statuses.with(|statuses| statuses.get(&selected.get()).cloned().unwrap_or_default())
     );
+    let online = create_memo(cx, move |_| status.with(|status| status.online));
+    let last_seen = create_memo(cx, move |_| status.with(|status| status.last_seen.clone()));
+    let queue = create_memo(cx, move |_| status.with(|status| status.queue.clone()));
+    let sleep = create_memo(cx, move |_| status.with(|status| status.sleep.clone()));
+    let control = create_memo(cx, move |_| status.with(|status| status.control.clone()));
+    let activity = create_memo(cx, move |_| status.with(|status| status.activity.clone()));
+    let group = create_memo(cx, move |_| status.with(|status| status.group.clone()));
 
-    let media_state = create_memo(
+    let media = create_memo(
         cx,
         move |_| {
-            match player_state.get() {
-                PlayerState::Media(state) => Some(state),
+            status.with(|status| match &status.player {
+                PlayerState::Media(state) if status.online => Some(state.clone()),
                 _ => None
+            })
+        }
+    );
+
+    provide_context(cx, DisplayStatus { statuses, online, last_seen, media, queue, sleep, control, activity, group });
+
+    // Tell this remote what the others are doing, so a pause from across the room isn't a
+    // mystery. Nothing is shown for what was already in the feed when the status arrived, or
+    // when the display changed.
+    create_effect(cx, move |last: Option<(String, bool, Option<u64>)>| {
+        let display = selected.get();
+        let is_known = statuses.with(|statuses| statuses.contains_key(&display));
+        let newest = activity.with(|activity| activity.first().cloned());
+
+        if let (Some((last_display, was_known, last_id)), Some(newest)) = (last, &newest) {
+            let is_new = last_display == display && was_known && last_id != Some(newest.id);
+
+            if is_new && !this_remote.is(&newest.by) {
+                toasts.info(newest.message());
             }
         }
+
+        (display, is_known, newest.map(|newest| newest.id))
+    });
+
+    let names = move || statuses.with(|statuses| statuses.keys().cloned().collect::<Vec<_>>());
+
+    view! { cx,
+        <div class="container-xl remote">
+            <RemoteNav>
+                <Show when=move || { names().len() > 1 } fallback=|_| ()>
+                    <select
+                        class="form-select w-auto"
+                        on:change=move |ev| selected.set(event_target_value(&ev))
+                    >
+                        {move || {
+                            names()
+                                .into_iter()
+                                .map(|name| {
+                                    let is_selected = name == selected.get();
+                                    let (is_online, is_group) = statuses.with(|statuses| {
+                                        statuses
+                                            .get(&name)
+                                            .map(|status| (status.online, !status.members.is_empty()))
+                                            .unwrap_or_default()
+                                    });
+                                    let label = match (is_group, is_online) {
+                                        (true, true) => format!("{} (group)", name),
+                                        (true, false) => format!("{} (group, offline)", name),
+                                        (false, true) => name.clone(),
+                                        (false, false) => format!("{} (offline)", name)
+                                    };
+
+                                    view! { cx, <option value=name selected=is_selected>{label}</option> }
+                                })
+                                .collect::<Vec<_>>()
+                        }}
+                    </select>
+                </Show>
+            </RemoteNav>
+            <Outlet/>
+            <MiniPlayer/>
+            <ToastList/>
+        </div>
+    }
+}
+
+/// The player itself, in tabs, so the library and queue can be looked through while
+/// something plays.
+#[component]
+pub fn Remote(cx: Scope) -> impl IntoView {
+    let SelectedDisplay(selected) = expect_context::<SelectedDisplay>(cx);
+    let DisplayStatus { online, last_seen, media: media_state, queue, sleep, group, .. } = expect_context::<DisplayStatus>(cx);
+    let tab = use_tab(cx);
+
+    // Items end on their way to the next one too, which isn't worth flashing up
+    let is_finished = create_memo(
+        cx,
+        move |_| {
+            let is_last = queue.with(|queue| queue.repeat == Repeat::Off && queue.position + 1 >= queue.order.len());
+            is_last && media_state.with(|state| state.as_ref().map(|state| state.phase == Phase::Ended).unwrap_or(false))
+        }
     );
 
+    let is_playing = create_memo(cx, move |_| media_state.with(Option::is_some));
+
+    let offline_message = move || {
+        if online.get() {
+            return None;
+        }
+
+        Some(match last_seen.get() {
+            Some(time) => format!("{} is offline. Last seen {}.", selected.get(), time),
+            None => format!("{} hasn't connected yet. Open it on the TV to start.", selected.get())
+        })
+    };
+
     let media = create_resource(
         cx,
         || (),
@@ -44,26 +279,122 @@ pub fn Remote(cx: Scope) -> impl IntoView {
     );
 
     view! { cx,
-        <div class="container-xl">
-            <Transition fallback=|| ()>
-                {move || {
-                    if is_playing.get() {
-                        view! { cx, <Media state=media_state/> }.into_view(cx)
-                    } else {
-                        match media.read(cx) {
-                            None => {
-                                view! { cx, <Loading/> }.into_view(cx)
-                            },
-                            Some(Err(_)) => {
-                                view! { cx, <DisplayError error="Failed to load media library"/> }.into_view(cx)
-                            },
-                            Some(Ok(files)) => {
-                                view! { cx, <Browse media_files=files/> }.into_view(cx)
-                            }
+        <div class="d-flex flex-wrap gap-2 mt-3">
+            {move || {
+                TABS.iter()
+                    .map(|t| {
+                        let class = if tab.get() == *t { "btn btn-primary" } else { "btn btn-outline-secondary" };
+
+                        view! { cx,
+                            <A href=t.href() class=class>
+                                <Icon icon=t.icon() class="me-1"/>
+                                {t.label()}
+                            </A>
                         }
+                    })
+                    .collect::<Vec<_>>()
+            }}
+        </div>
+        {move || {
+            offline_message().map(|message| view! { cx,
+                <div class="alert alert-warning mt-3">
+                    <Icon icon="wifi-off" class="me-2"/>
+                    {message}
+                </div>
+            })
+        }}
+        {move || {
+            group.get().map(|group| view! { cx,
+                <div class="alert alert-light mt-3">
+                    {format!(
+                        "{} is playing along with {}. Anything sent to it here takes it out of the group.",
+                        selected.get(),
+                        group
+                    )}
+                </div>
+            })
+        }}
+        <ControlAlert/>
+        // Kept while other tabs are open, so the library stays in the folder it was left in
+        <div class:d-none=move || tab.get() != Tab::Library>
+            <Transition fallback=|| ()>
+                {move || match media.read(cx) {
+                    None => {
+                        view! { cx, <Loading/> }.into_view(cx)
+                    },
+                    Some(Err(e)) => {
+                        view! { cx, <ErrorAlert error=e class="mt-3"/> }.into_view(cx)
+                    },
+                    Some(Ok(libraries)) => {
+                        view! { cx, <Browse libraries=libraries/> }.into_view(cx)
                     }
                 }}
             </Transition>
         </div>
+        {move || match tab.get() {
+            Tab::Playing => {
+                let playing = if is_finished.get() {
+                    view! { cx, <Finished state=media_state queue=queue/> }.into_view(cx)
+                } else if is_playing.get() {
+                    view! { cx, <Media state=media_state queue=queue sleep=sleep/> }.into_view(cx)
+                } else {
+                    view! { cx,
+                        <div class="card shadow mt-5">
+                            <div class="card-body text-center">
+                                <p class="text-body-secondary">"Nothing is playing."</p>
+                                <A href=Tab::Library.href() class="btn btn-primary">
+                                    <Icon icon="folder" class="me-1"/>
+                                    "Browse the library"
+                                </A>
+                            </div>
+                        </div>
+                    }.into_view(cx)
+                };
+
+                view! { cx, {playing}<ActivityFeed/> }.into_view(cx)
+            },
+            Tab::Library => ().into_view(cx),
+            Tab::Queue => view! { cx, <QueueList queue=queue/> }.into_view(cx),
+            Tab::Settings => view! { cx, <Settings/> }.into_view(cx)
+        }}
+    }
+}
+
+#[component]
+fn RemoteNav(
+    cx: Scope,
+    #[prop(optional)]
+    children: Option<Children>
+) -> impl IntoView {
+    let connection = use_connection(cx);
+
+    view! { cx,
+        <nav class="navbar mt-3">
+            <div class="d-flex gap-2">
+                <A href=format!("{}/", BASE_PATH) class="btn btn-link text-decoration-none">
+                    <Icon icon="play-circle" class="me-1"/>
+                    "Player"
+                </A>
+                <A href=format!("{}/schedules", BASE_PATH) class="btn btn-link text-decoration-none">
+                    <Icon icon="time" class="me-1"/>
+                    "Schedules"
+                </A>
+                <A href=format!("{}/devices", BASE_PATH) class="btn btn-link text-decoration-none">
+                    <Icon icon="devices" class="me-1"/>
+                    "Devices"
+                </A>
+                <A href=format!("{}/profiles", BASE_PATH) class="btn btn-link text-decoration-none">
+                    <Icon icon="user" class="me-1"/>
+                    "Profiles"
+                </A>
+            </div>
+            <Show when=move || connection.get() == Connection::Disconnected fallback=|_| ()>
+                <span class="badge text-bg-danger">
+                    <Icon icon="wifi-off" class="me-1"/>
+                    "Disconnected, reconnecting..."
+                </span>
+            </Show>
+            {children.map(|children| children(cx))}
+        </nav>
     }
 }
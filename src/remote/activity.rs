@@ -0,0 +1,90 @@
+use crate::control::{LockControl, ReleaseControl};
+use crate::icons::Icon;
+use crate::remote::{DisplayStatus, SelectedDisplay, ThisRemote};
+use crate::toast::toast_errors;
+use leptos::*;
+
+/// Who has control of the display, when a remote has locked it, with a way to release it.
+#[component]
+pub fn ControlAlert(cx: Scope) -> impl IntoView {
+    let SelectedDisplay(display) = expect_context::<SelectedDisplay>(cx);
+    let DisplayStatus { control, .. } = expect_context::<DisplayStatus>(cx);
+    let this_remote = expect_context::<ThisRemote>(cx);
+    let release = create_server_action::<ReleaseControl>(cx);
+    toast_errors(cx, release);
+
+    move || {
+        control.get().map(|holder| {
+            let (class, message) = if this_remote.is(&holder) {
+                ("alert-light", format!("You have control of {}. Other remotes can't change anything until you release it.", display.get()))
+            } else {
+                ("alert-warning", format!("{} has control of {}.", holder.name, display.get()))
+            };
+
+            view! { cx,
+                <div class=format!("alert {} d-flex align-items-center gap-2 mt-3", class)>
+                    <Icon icon="lock"/>
+                    <span class="flex-grow-1">{message}</span>
+                    <button
+                        class="btn btn-sm btn-outline-secondary"
+                        on:click=move |_| release.dispatch(ReleaseControl { display: display.get_untracked() })
+                    >
+                        <Icon icon="lock-open" class="me-1"/>
+                        "Release"
+                    </button>
+                </div>
+            }
+        })
+    }
+}
+
+/// What remotes have recently done to the display, newest first.
+#[component]
+pub fn ActivityFeed(cx: Scope) -> impl IntoView {
+    let SelectedDisplay(display) = expect_context::<SelectedDisplay>(cx);
+    let DisplayStatus { control, activity, .. } = expect_context::<DisplayStatus>(cx);
+    let this_remote = expect_context::<ThisRemote>(cx);
+    let lock = create_server_action::<LockControl>(cx);
+    toast_errors(cx, lock);
+
+    view! { cx,
+        <div class="card shadow mt-3">
+            <div class="card-body">
+                <div class="d-flex align-items-center mb-3">
+                    <h5 class="flex-grow-1 mb-0">"Activity"</h5>
+                    <Show when=move || control.with(Option::is_none) fallback=|_| ()>
+                        <button
+                            class="btn btn-sm btn-outline-secondary"
+                            on:click=move |_| lock.dispatch(LockControl { display: display.get_untracked() })
+                        >
+                            <Icon icon="lock" class="me-1"/>
+                            "Lock control"
+                        </button>
+                    </Show>
+                </div>
+                {move || {
+                    let activity = activity.get();
+
+                    if activity.is_empty() {
+                        return view! { cx, <p class="text-body-secondary mb-0">"Nothing yet."</p> }.into_view(cx);
+                    }
+
+                    activity
+                        .into_iter()
+                        .map(|entry| {
+                            let name = if this_remote.is(&entry.by) { "You".to_string() } else { entry.by.name.clone() };
+
+                            view! { cx,
+                                <div class="d-flex gap-2 mb-1">
+                                    <span class="text-body-secondary">{entry.time}</span>
+                                    <span>{name}" "{entry.action}</span>
+                                </div>
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .into_view(cx)
+                }}
+            </div>
+        </div>
+    }
+}
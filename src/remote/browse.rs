@@ -1,5 +1,11 @@
-use crate::player::Instruction;
+use crate::icons::Icon;
+use crate::library::Listing;
+use crate::player::{Instruction, MediaKind};
+use crate::profiles::{get_profile_media, ToggleFavorite};
 use crate::remote::fns::Instruct;
+use crate::remote::slideshow::SlideshowControls;
+use crate::remote::SelectedDisplay;
+use crate::toast::toast_errors;
 use itertools::Itertools;
 use leptos::*;
 use std::cell::RefCell;
@@ -8,15 +14,46 @@ use std::rc::{Rc, Weak};
 #[component]
 pub fn Browse(
     cx: Scope,
-    media_files: Vec<String>
+    libraries: Vec<Listing>
 ) -> impl IntoView {
-    let (browser, set_browser) = create_signal(cx, Browser::build(media_files));
+    let (browser, set_browser) = create_signal(cx, Browser::build(libraries));
 
+    let SelectedDisplay(display) = expect_context::<SelectedDisplay>(cx);
     let instruct = create_server_action::<Instruct>(cx);
+    let toggle_favorite = create_server_action::<ToggleFavorite>(cx);
+    toast_errors(cx, instruct);
+    toast_errors(cx, toggle_favorite);
+
+    let profile_media = create_resource(
+        cx,
+        move || toggle_favorite.version().get(),
+        move |_| get_profile_media(cx)
+    );
+
+    // Videos pick up where the current profile left off
+    let play = move |path: String| {
+        let from = profile_media
+            .read(cx)
+            .and_then(|media| media.ok())
+            .and_then(|media| media.resume.get(&path).copied())
+            .unwrap_or(0.0);
+
+        instruct.dispatch(Instruct { display: display.get_untracked(), i: Instruction::Play { path, from } });
+    };
 
     move || { 
+        let media = profile_media.read(cx).and_then(|media| media.ok()).unwrap_or_default();
+
         browser.with(|browser| {
             let focus = browser.focus.as_ref().borrow();
+            let favorites = browser
+                .is_home()
+                .then(|| media.favorites.iter().cloned().collect::<Vec<_>>())
+                .unwrap_or_default();
+            let tracks = focus.files_of(MediaKind::Audio);
+            let photos = focus.files_of(MediaKind::Image);
+            let play_all = tracks.clone();
+            let slideshow_folder = focus.has_images().then(|| focus.path.clone());
 
             view! { cx,
                 <div class="card shadow mt-5">
@@ -39,7 +76,52 @@ pub fn Browse(
                                 })
                                 .collect::<Vec<_>>()
                             }
+                            {(!play_all.is_empty()).then(|| {
+                                view! { cx,
+                                    <button
+                                        class="btn btn-primary float-end"
+                                        on:click=move |_| {
+                                            let i = Instruct {
+                                                display: display.get_untracked(),
+                                                i: Instruction::Queue { items: play_all.clone(), position: 0 }
+                                            };
+                                            instruct.dispatch(i);
+                                        }
+                                    >
+                                        <Icon icon="play" class="me-2"/>
+                                        "Play all"
+                                    </button>
+                                }
+                            })}
                         </div>
+                        {slideshow_folder.map(|folder| view! { cx, <SlideshowControls folder=folder/> })}
+                        {(!favorites.is_empty()).then(|| view! { cx,
+                            <h5 class="mt-2">"Favorites"</h5>
+                            <div class="row">
+                                {favorites
+                                    .into_iter()
+                                    .map(|path| {
+                                        let name = path.rsplit('/').next().unwrap_or_default().to_string();
+
+                                        view! { cx,
+                                            <div class="col-12 col-md-4 col-lg-3 d-grid">
+                                                <button
+                                                    class="btn btn-link text-decoration-none p-0"
+                                                    on:click=move |_| play(path.clone())
+                                                >
+                                                    <div class="alert alert-light fs-5">
+                                                        <Icon icon="star" class="me-3"/>
+                                                        {name}
+                                                    </div>
+                                                </button>
+                                            </div>
+                                        }
+                                    })
+                                    .collect::<Vec<_>>()
+                                }
+                            </div>
+                            <hr/>
+                        })}
                         <div class="row">
                             {focus.children
                                 .iter()
@@ -56,7 +138,7 @@ pub fn Browse(
                                                 }
                                             >
                                                 <div class="alert alert-light fs-4">
-                                                    <i class="bx bx-folder me-3"></i>
+                                                    <Icon icon="folder" class="me-3"/>
                                                     {name}
                                                 </div>
                                             </button>
@@ -72,22 +154,55 @@ pub fn Browse(
                                 .iter()
                                 .map(|file| {
                                     let path = file.path.clone();
+                                    let kind = MediaKind::of(&path);
+                                    let siblings = match kind {
+                                        MediaKind::Audio => tracks.clone(),
+                                        MediaKind::Image => photos.clone(),
+                                        MediaKind::Video => vec![]
+                                    };
+                                    let icon = match kind {
+                                        MediaKind::Audio => "music",
+                                        MediaKind::Image => "image",
+                                        MediaKind::Video => "file"
+                                    };
+                                    let is_favorite = media.favorites.contains(&path);
+                                    let badge = if media.watched.contains(&path) {
+                                        Some(view! { cx, <span class="badge text-bg-secondary ms-2"><Icon icon="check"/></span> })
+                                    } else if media.resume.contains_key(&path) {
+                                        Some(view! { cx, <span class="badge text-bg-primary ms-2">"Resume"</span> })
+                                    } else {
+                                        None
+                                    };
+                                    let favorite_path = path.clone();
+
                                     view! { cx,
-                                        <div class="col-12 col-md-4 col-lg-3 d-grid">
+                                        <div class="col-12 col-md-4 col-lg-3 d-flex align-items-start">
                                             <button
-                                                class="btn btn-link text-decoration-none p-0"
+                                                class="btn btn-link text-decoration-none p-0 flex-grow-1 text-start"
                                                 on:click=move |_| {
-                                                    let i = Instruct {
-                                                        i: Instruction::Play(path.clone(), 0.0)
-                                                    };
-                                                    instruct.dispatch(i);
+                                                    // Audio and photos play through the rest of their folder
+                                                    match siblings.iter().position(|file| file == &path) {
+                                                        Some(ix) => {
+                                                            let i = Instruction::Queue { items: siblings.clone(), position: ix };
+                                                            instruct.dispatch(Instruct { display: display.get_untracked(), i });
+                                                        },
+                                                        None => play(path.clone())
+                                                    }
                                                 }
                                             >
                                                 <div class="alert alert-light fs-5">
-                                                    <i class="bx bx-file me-3"></i>
+                                                    <Icon icon=icon class="me-3"/>
                                                     {file.name.clone()}
+                                                    {badge}
                                                 </div>
                                             </button>
+                                            <button
+                                                class="btn btn-link p-2"
+                                                title="Favorite"
+                                                on:click=move |_| toggle_favorite.dispatch(ToggleFavorite { path: favorite_path.clone() })
+                                            >
+                                                <Icon icon=if is_favorite { "star" } else { "star-outline" }/>
+                                            </button>
                                         </div>
                                     }
                                 })
@@ -108,12 +223,15 @@ struct Browser {
     // Needed to avoid root ref from being dropped since only referenced by weak `parent` field
     #[allow(dead_code)]
     root: DirRef,
+    /// Where browsing starts, either the list of libraries or the only library
+    home: DirRef,
     focus: DirRef
 }
 
 #[derive(Clone, Debug)]
 struct Dir {
     name: String,
+    path: String,
     parent: Weak<RefCell<Dir>>,
     children: Vec<DirRef>,
     files: Vec<MediaFile>
@@ -126,22 +244,47 @@ struct MediaFile {
 }
 
 impl Browser {
-    fn build(paths: Vec<String>) -> Self {
+    /// Each library is a top level folder, shown by name.
+    fn build(libraries: Vec<Listing>) -> Self {
         let root = Rc::new(
             RefCell::new(Dir {
                 name: "Home".to_string(),
+                path: String::new(),
                 parent: Weak::new(),
                 children: vec![],
-                files: Self::files_with_prefix("", &paths)
+                files: vec![]
             })
         );
 
-        let focus = root.clone();
+        root.borrow_mut().children = libraries
+            .into_iter()
+            .map(|library| {
+                let prefix = format!("{}/", library.id);
 
-        root.borrow_mut().children = Self::dirs_with_prefix(&root, "", &paths);
+                let dir = Rc::new(
+                    RefCell::new(Dir {
+                        name: library.name,
+                        path: library.id,
+                        parent: Rc::downgrade(&root),
+                        children: vec![],
+                        files: Self::files_with_prefix(&prefix, &library.files)
+                    })
+                );
+
+                dir.borrow_mut().children = Self::dirs_with_prefix(&dir, &prefix, &library.files);
+                dir
+            })
+            .collect();
+
+        // Skip the list of libraries when there's only one
+        let focus = match root.borrow().children.as_slice() {
+            [library] => library.clone(),
+            _ => root.clone()
+        };
 
         Self {
             root,
+            home: focus.clone(),
             focus
         }
     }
@@ -153,6 +296,10 @@ impl Browser {
         focus_ref.build_breadcrumb(self.focus.clone())
     }
 
+    fn is_home(&self) -> bool {
+        Rc::ptr_eq(&self.home, &self.focus)
+    }
+
     fn set_focus<'a>(&mut self, focus: &'a DirRef) {
         self.focus = focus.clone();
     }
@@ -189,6 +336,7 @@ impl Browser {
                 let dir = Rc::new(
                     RefCell::new(Dir {
                         name: name.to_string(),
+                        path: format!("{}{}", prefix, name),
                         parent: Rc::downgrade(parent),
                         children: vec![],
                         files: Self::files_with_prefix(&new_prefix, paths)
@@ -209,6 +357,19 @@ impl Browser {
 }
 
 impl Dir {
+    fn files_of(&self, kind: MediaKind) -> Vec<String> {
+        self.files
+            .iter()
+            .filter(|file| MediaKind::of(&file.path) == kind)
+            .map(|file| file.path.clone())
+            .collect()
+    }
+
+    fn has_images(&self) -> bool {
+        self.files.iter().any(|file| MediaKind::of(&file.path) == MediaKind::Image)
+            || self.children.iter().any(|dir| dir.as_ref().borrow().has_images())
+    }
+
     fn build_breadcrumb(&self, self_ref: DirRef) -> Vec<(String, DirRef)> {
         let mut items = vec![];
 
@@ -0,0 +1,92 @@
+use crate::devices::{list_devices, DeviceKind, RevokeDevice};
+use crate::error::ErrorAlert;
+use crate::icons::Icon;
+use crate::loading::Loading;
+use leptos::*;
+
+/// Paired devices, for admin devices to review and revoke.
+#[component]
+pub fn Devices(cx: Scope) -> impl IntoView {
+    let revoke = create_server_action::<RevokeDevice>(cx);
+
+    let devices = create_resource(
+        cx,
+        move || revoke.version().get(),
+        move |_| list_devices(cx)
+    );
+
+    view! { cx,
+        <div>
+            <div class="card shadow mt-3">
+                <div class="card-body">
+                    <h4 class="mb-3">"Devices"</h4>
+                    <Transition fallback=|| ()>
+                        {move || match devices.read(cx) {
+                            None => {
+                                view! { cx, <Loading/> }.into_view(cx)
+                            },
+                            Some(Err(e)) => {
+                                view! { cx, <ErrorAlert error=e/> }.into_view(cx)
+                            },
+                            Some(Ok(devices)) if devices.is_empty() => {
+                                view! { cx, <p class="text-body-secondary">"No devices are paired."</p> }.into_view(cx)
+                            },
+                            Some(Ok(devices)) => {
+                                view! { cx,
+                                    <table class="table align-middle">
+                                        <thead>
+                                            <tr>
+                                                <th>"Name"</th>
+                                                <th>"Access"</th>
+                                                <th>"Paired"</th>
+                                                <th>"Last seen"</th>
+                                                <th></th>
+                                            </tr>
+                                        </thead>
+                                        <tbody>
+                                            {devices
+                                                .into_iter()
+                                                .map(|device| {
+                                                    let id = device.id;
+                                                    let access = match device.kind {
+                                                        DeviceKind::Admin => "Admin",
+                                                        DeviceKind::Remote => "Remote",
+                                                        DeviceKind::Display => "Display"
+                                                    };
+
+                                                    view! { cx,
+                                                        <tr>
+                                                            <td>{device.name}</td>
+                                                            <td>{access}</td>
+                                                            <td>{device.paired}</td>
+                                                            <td>{device.last_seen.unwrap_or_default()}</td>
+                                                            <td class="text-end">
+                                                                <button
+                                                                    class="btn btn-sm btn-outline-danger"
+                                                                    on:click=move |_| revoke.dispatch(RevokeDevice { id })
+                                                                >
+                                                                    <Icon icon="trash"/>
+                                                                </button>
+                                                            </td>
+                                                        </tr>
+                                                    }
+                                                })
+                                                .collect::<Vec<_>>()
+                                            }
+                                        </tbody>
+                                    </table>
+                                }.into_view(cx)
+                            }
+                        }}
+                    </Transition>
+                    {move || {
+                        revoke.value()
+                            .get()
+                            .and_then(|result| result.err())
+                            .map(|e| view! { cx, <ErrorAlert error=e class="mt-3"/> })
+                    }}
+                </div>
+            </div>
+        </div>
+    }
+}
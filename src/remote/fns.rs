@@ -1,29 +1,74 @@
 use crate::player::*;
 use leptos::*;
 #[allow(unused_imports)]
-use std::sync::{Arc, RwLock};
+use crate::displays::*;
+use crate::library::*;
 
 #[server(ListMedia, "/api")]
-pub async fn list_media(cx: Scope) -> Result<Vec<String>, ServerFnError> {
-    let MediaRoot(media_root) = expect_context::<MediaRoot>(cx);
-    let root = std::path::Path::new(&media_root);
-
-    let entries = walkdir::WalkDir::new(&root)
-        .into_iter()
-        .filter_map(|file| file.ok())
-        .filter(|e| e.metadata().unwrap().is_file())
-        .map(|e| format!("{}", e.path().strip_prefix(root).unwrap().display()))
-        .collect();
-
-    Ok(entries)
+pub async fn list_media(cx: Scope) -> Result<Vec<Listing>, ServerFnError> {
+    let libraries = crate::profiles::profile_libraries(cx)?;
+    Ok(libraries.listings()?)
 }
 
 #[server(Instruct, "/api")]
-pub async fn instruct(cx: Scope, i: Instruction) -> Result<(), ServerFnError> {
-    let instruction = expect_context::<Arc<RwLock<Option<Instruction>>>>(cx);
+pub async fn instruct(cx: Scope, display: String, i: Instruction) -> Result<(), ServerFnError> {
+    use crate::control::current_remote;
+    use crate::profiles::{current_profile, profile_libraries};
 
-    let mut write = instruction.write().unwrap();
-    *write = Some(i);
+    let libraries = profile_libraries(cx)?;
+    let displays = expect_context::<Displays>(cx);
+    let profile = current_profile(cx);
+    let remote = current_remote(cx);
+    let profile = profile.map(|profile| profile.id);
+
+    displays.instruct_from(&display, &libraries, &remote, i, |session| session.profile = profile)?;
+
+    Ok(())
+}
+
+#[server(Skip, "/api")]
+pub async fn skip(cx: Scope, display: String, offset: i32) -> Result<(), ServerFnError> {
+    use crate::control::current_remote;
+
+    let displays = expect_context::<Displays>(cx);
+    displays.skip(&display, &current_remote(cx), offset)?;
+
+    Ok(())
+}
+
+#[server(SetShuffle, "/api")]
+pub async fn set_shuffle(cx: Scope, display: String, shuffle: bool) -> Result<(), ServerFnError> {
+    use crate::control::current_remote;
+    use rand::seq::SliceRandom;
+
+    let displays = expect_context::<Displays>(cx);
+    let remote = current_remote(cx);
+    let action = if shuffle { "turned shuffle on" } else { "turned shuffle off" };
+
+    displays.control(&display, &remote, action, |session| {
+        if shuffle {
+            session.queue.shuffle_with(|order| order.shuffle(&mut rand::thread_rng()));
+        } else {
+            session.queue.unshuffle();
+        }
+    })?;
+
+    Ok(())
+}
+
+#[server(SetRepeat, "/api")]
+pub async fn set_repeat(cx: Scope, display: String, repeat: Repeat) -> Result<(), ServerFnError> {
+    use crate::control::current_remote;
+
+    let displays = expect_context::<Displays>(cx);
+    let remote = current_remote(cx);
+    let action = match repeat {
+        Repeat::Off => "turned repeat off",
+        Repeat::One => "set repeat to one item",
+        Repeat::All => "set repeat to the whole queue"
+    };
+
+    displays.control(&display, &remote, action, |session| session.queue.repeat = repeat)?;
 
     Ok(())
 }
@@ -1,38 +1,112 @@
-use crate::remote::fns::Instruct;
+use crate::config::get_remote_settings;
+use crate::icons::Icon;
+use crate::party::fns::StartParty;
+use crate::remote::fns::{Instruct, SetRepeat, SetShuffle, Skip};
+use crate::remote::sleep::SleepControls;
+use crate::remote::SelectedDisplay;
 use crate::player::*;
+use crate::socket::BASE_PATH;
+use crate::tags::get_track_info;
+use crate::toast::toast_errors;
 use leptos::*;
 
 #[component]
 pub fn Media(
     cx: Scope,
-    state: Memo<Option<MediaState>>
+    state: Memo<Option<MediaState>>,
+    queue: Memo<Queue>,
+    sleep: Memo<Option<SleepStatus>>
 ) -> impl IntoView {
+    let SelectedDisplay(display) = expect_context::<SelectedDisplay>(cx);
     let instruct = create_server_action::<Instruct>(cx);
+    let skip = create_server_action::<Skip>(cx);
+    let set_shuffle = create_server_action::<SetShuffle>(cx);
+    let set_repeat = create_server_action::<SetRepeat>(cx);
+    let start_party = create_server_action::<StartParty>(cx);
+    toast_errors(cx, instruct);
+    toast_errors(cx, skip);
+    toast_errors(cx, set_shuffle);
+    toast_errors(cx, set_repeat);
+    toast_errors(cx, start_party);
 
-    let path = move || {
-        state.get().map(|state| match state {
-            MediaState::Playing(path, _, _) => path,
-            MediaState::Paused(path, _, _) => path
+    let settings = create_resource(cx, || (), move |_| get_remote_settings(cx));
+    let settings = move || settings.read(cx).and_then(|settings| settings.ok()).unwrap_or_default();
+
+    let shuffle = move || queue.with(|queue| queue.shuffle);
+    let repeat = move || queue.with(|queue| queue.repeat);
+
+    let mode_class = |active: bool| if active { "btn btn-primary" } else { "btn btn-outline-secondary" };
+
+    let path = move || state.with(|state| state.as_ref().map(|state| state.path.clone()));
+    let phase = create_memo(cx, move |_| state.with(|state| state.as_ref().map(|state| state.phase.clone())));
+
+    let audio_path = create_memo(cx, move |_| path().filter(|path| MediaKind::of(path) == MediaKind::Audio));
+
+    let track = create_resource(
+        cx,
+        move || audio_path.get(),
+        move |path| async move {
+            match path {
+                Some(path) => get_track_info(cx, path).await.ok(),
+                None => None
+            }
+        }
+    );
+
+    let title = move || {
+        let info = track.read(cx).flatten().unwrap_or_default();
+        let title = info.title.or_else(path).unwrap_or_default();
+        let subtitle = [info.artist, info.album].into_iter().flatten().collect::<Vec<_>>().join(" - ");
+
+        (title, subtitle)
+    };
+
+    // Photos have a slide timer rather than a seekable timeline
+    let is_seekable = move || path().map(|path| MediaKind::of(&path) != MediaKind::Image).unwrap_or(true);
+
+    let has_queue = move || queue.with(|queue| queue.items.len() > 1);
+
+    let queue_position = move || {
+        queue.with(|queue| format!("{} of {}", queue.position + 1, queue.items.len()))
+    };
+
+    let duration = move || state.with(|state| state.as_ref().map(|state| state.duration.floor()));
+    let time = move || state.with(|state| state.as_ref().map(|state| state.position.floor()));
+
+    // Shares of the duration, as percentages for the timeline
+    let share = move |seconds: f64| {
+        let duration = duration().unwrap_or(0.0);
+        if duration > 0.0 { (seconds / duration * 100.0).clamp(0.0, 100.0) } else { 0.0 }
+    };
+
+    let buffered = move || state.with(|state| state.as_ref().map(|state| state.buffered.clone()).unwrap_or_default());
+
+    let waiting = move || {
+        phase.get().and_then(|phase| match phase {
+            Phase::Loading => Some("Loading..."),
+            Phase::Buffering => Some("Buffering..."),
+            Phase::Seeking => Some("Seeking..."),
+            _ => None
         })
     };
 
-    let duration = move || {
-        state.get().map(|state| match state {
-            MediaState::Playing(_, duration, _) => duration.floor(),
-            MediaState::Paused(_, duration, _) => duration.floor()
+    let error = move || {
+        phase.get().and_then(|phase| match phase {
+            Phase::Error(message) => Some(message),
+            _ => None
         })
     };
 
-    let time = move || {
-        state.get().map(|state| match state {
-            MediaState::Playing(_, _, from) => from.floor(),
-            MediaState::Paused(_, _, at) => at.floor()
+    // The link to share, once a party's been started
+    let party_link = move || {
+        start_party.value().get().and_then(|result| result.ok()).map(|id| {
+            format!("{}{}/party/{}", window().location().origin().unwrap_or_default(), BASE_PATH, id)
         })
     };
 
     let play_from = move |from| {
         if let Some(path) = path() {
-            instruct.dispatch(Instruct { i: Instruction::Play(path, from) } )
+            instruct.dispatch(Instruct { display: display.get_untracked(), i: Instruction::Play { path, from } } )
         }
     };
 
@@ -40,23 +114,52 @@ pub fn Media(
         <div class="card shadow mt-5">
             <div class="card-body">
                 <div class="text-center mb-4">
-                    <h3>{path}</h3>
+                    <h3>{move || title().0}</h3>
+                    <h5 class="text-body-secondary">{move || title().1}</h5>
+                    <Show when=has_queue fallback=|_| ()>
+                        <small class="text-body-tertiary">{queue_position}</small>
+                    </Show>
+                    {move || {
+                        waiting().map(|message| view! { cx,
+                            <div class="text-body-secondary mt-2">
+                                <span class="spinner-border spinner-border-sm me-2"></span>
+                                {message}
+                            </div>
+                        })
+                    }}
                 </div>
+                {move || {
+                    error().map(|message| view! { cx,
+                        <div class="alert alert-danger">{message}</div>
+                    })
+                }}
                 <div class="row d-flex justify-content-center mb-4">
                     <div class="col-auto">
                         <div class="row">
-                            <div class="col-auto">
-                                <button
-                                    class="btn btn-primary"
-                                    on:click=move |_| {
-                                        if let Some(from) = time() {
-                                            play_from(from - 60.0);
+                            <Show when=has_queue fallback=|_| ()>
+                                <div class="col-auto">
+                                    <button
+                                        class="btn btn-primary"
+                                        on:click=move |_| skip.dispatch(Skip { display: display.get_untracked(), offset: -1 })
+                                    >
+                                        <Icon icon="skip-previous"/>
+                                    </button>
+                                </div>
+                            </Show>
+                            <Show when=is_seekable fallback=|_| ()>
+                                <div class="col-auto">
+                                    <button
+                                        class="btn btn-primary"
+                                        on:click=move |_| {
+                                            if let Some(from) = time() {
+                                                play_from((from - settings().skip_back).max(0.0));
+                                            }
                                         }
-                                    }
-                                >
-                                    <i class="bx bx-rewind"></i>
-                                </button>
-                            </div>
+                                    >
+                                        <Icon icon="rewind"/>
+                                    </button>
+                                </div>
+                            </Show>
                             <div class="col-auto">
                                 <button
                                     class="btn btn-primary"
@@ -66,7 +169,7 @@ pub fn Media(
                                         }
                                     }
                                 >
-                                    <i class="bx bx-play"></i>
+                                    <Icon icon="play"/>
                                 </button>
                             </div>
                             <div class="col-auto">
@@ -75,40 +178,170 @@ pub fn Media(
                                     on:click=move |_| {
                                         if let Some(path) = path() {
                                             if let Some(at) = time() {
-                                                instruct.dispatch(Instruct { i: Instruction::Pause(path, at) } )
+                                                instruct.dispatch(Instruct { display: display.get_untracked(), i: Instruction::Pause { path, at } } )
                                             }
                                         }
                                     }
                                 >
-                                    <i class="bx bx-pause"></i>
+                                    <Icon icon="pause"/>
                                 </button>
                             </div>
                             <div class="col-auto">
                                 <button
                                     class="btn btn-primary"
                                     on:click=move |_| {
-                                        instruct.dispatch(Instruct { i: Instruction::Stop } )
+                                        instruct.dispatch(Instruct { display: display.get_untracked(), i: Instruction::Stop } )
                                     }
                                 >
-                                    <i class="bx bx-stop"></i>
+                                    <Icon icon="stop"/>
                                 </button>
                             </div>
-                            <div class="col-auto">
-                                <button
-                                    class="btn btn-primary"
-                                    on:click=move |_| {
-                                        if let Some(from) = time() {
-                                            play_from(from + 60.0);
+                            <Show when=is_seekable fallback=|_| ()>
+                                <div class="col-auto">
+                                    <button
+                                        class="btn btn-primary"
+                                        on:click=move |_| {
+                                            if let Some(from) = time() {
+                                                play_from(from + settings().skip_forward);
+                                            }
                                         }
-                                    }
-                                >
-                                    <i class="bx bx-fast-forward"></i>
-                                </button>
-                            </div>
+                                    >
+                                        <Icon icon="fast-forward"/>
+                                    </button>
+                                </div>
+                            </Show>
+                            <Show when=has_queue fallback=|_| ()>
+                                <div class="col-auto">
+                                    <button
+                                        class="btn btn-primary"
+                                        on:click=move |_| skip.dispatch(Skip { display: display.get_untracked(), offset: 1 })
+                                    >
+                                        <Icon icon="skip-next"/>
+                                    </button>
+                                </div>
+                            </Show>
                         </div>
                     </div>
                 </div>
-                <progress class="w-100" max=duration value=time/>
+                <div class="row d-flex justify-content-center mb-4">
+                    <div class="col-auto">
+                        <button
+                            class=move || mode_class(shuffle())
+                            title="Shuffle"
+                            on:click=move |_| set_shuffle.dispatch(SetShuffle { display: display.get_untracked(), shuffle: !shuffle() })
+                        >
+                            <Icon icon="shuffle"/>
+                        </button>
+                    </div>
+                    <Show when=shuffle fallback=|_| ()>
+                        <div class="col-auto">
+                            <button
+                                class="btn btn-outline-secondary"
+                                title="Reshuffle"
+                                on:click=move |_| set_shuffle.dispatch(SetShuffle { display: display.get_untracked(), shuffle: true })
+                            >
+                                <Icon icon="refresh"/>
+                            </button>
+                        </div>
+                    </Show>
+                    <div class="col-auto">
+                        <button
+                            class=move || mode_class(repeat() != Repeat::Off)
+                            title=move || format!("Repeat: {:?}", repeat())
+                            on:click=move |_| set_repeat.dispatch(SetRepeat { display: display.get_untracked(), repeat: repeat().cycle() })
+                        >
+                            <Icon icon="repeat"/>
+                            <Show when=move || repeat() == Repeat::One fallback=|_| ()>
+                                <small class="ms-1">"1"</small>
+                            </Show>
+                        </button>
+                    </div>
+                </div>
+                <SleepControls status=sleep/>
+                <Show when=is_seekable fallback=|_| ()>
+                    <div class="d-flex align-items-center gap-2 mb-4">
+                        <button
+                            class="btn btn-outline-secondary"
+                            on:click=move |_| {
+                                if let (Some(path), Some(position)) = (path(), time()) {
+                                    start_party.dispatch(StartParty { path, position });
+                                }
+                            }
+                        >
+                            <Icon icon="group" class="me-1"/>
+                            "Watch party"
+                        </button>
+                        {move || {
+                            party_link().map(|link| view! { cx,
+                                <a class="small text-decoration-none" href=link.clone() target="_blank">{link}</a>
+                            })
+                        }}
+                    </div>
+                </Show>
+                <div class="timeline">
+                    {move || {
+                        buffered()
+                            .into_iter()
+                            .map(|(start, end)| {
+                                let style = format!("left: {}%; width: {}%", share(start), share(end) - share(start));
+                                view! { cx, <div class="timeline-buffered" style=style></div> }
+                            })
+                            .collect::<Vec<_>>()
+                    }}
+                    <div class="timeline-played" style=move || format!("width: {}%", share(time().unwrap_or(0.0)))></div>
+                </div>
+            </div>
+        </div>
+    }
+}
+
+/// Shown once the last item has played, to start again or go back to browsing.
+#[component]
+pub fn Finished(
+    cx: Scope,
+    state: Memo<Option<MediaState>>,
+    queue: Memo<Queue>
+) -> impl IntoView {
+    let SelectedDisplay(display) = expect_context::<SelectedDisplay>(cx);
+    let instruct = create_server_action::<Instruct>(cx);
+    toast_errors(cx, instruct);
+
+    let name = move || {
+        state.with(|state| {
+            state
+                .as_ref()
+                .map(|state| state.path.rsplit('/').next().unwrap_or(&state.path).to_string())
+                .unwrap_or_default()
+        })
+    };
+
+    let play_again = move |_| {
+        let mut items = queue.with(|queue| queue.items.clone());
+
+        if items.is_empty() {
+            items.extend(state.with(|state| state.as_ref().map(|state| state.path.clone())));
+        }
+
+        instruct.dispatch(Instruct { display: display.get_untracked(), i: Instruction::Queue { items, position: 0 } });
+    };
+
+    view! { cx,
+        <div class="card shadow mt-5">
+            <div class="card-body text-center">
+                <h3>"Finished"</h3>
+                <h5 class="text-body-secondary mb-4">{name}</h5>
+                <div class="d-flex justify-content-center gap-2">
+                    <button class="btn btn-primary" on:click=play_again>
+                        <Icon icon="refresh" class="me-1"/>
+                        "Play again"
+                    </button>
+                    <button
+                        class="btn btn-outline-secondary"
+                        on:click=move |_| instruct.dispatch(Instruct { display: display.get_untracked(), i: Instruction::Stop })
+                    >
+                        "Back to library"
+                    </button>
+                </div>
             </div>
         </div>
     }
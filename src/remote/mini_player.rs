@@ -0,0 +1,81 @@
+use crate::icons::Icon;
+use crate::player::*;
+use crate::remote::fns::Instruct;
+use crate::remote::{use_tab, DisplayStatus, SelectedDisplay, Tab};
+use crate::toast::toast_errors;
+use leptos::*;
+use leptos_router::{use_location, A};
+
+/// What's playing, along the bottom of every remote page, with play and pause. Hidden on
+/// the now playing tab, which has the full controls.
+#[component]
+pub fn MiniPlayer(cx: Scope) -> impl IntoView {
+    let SelectedDisplay(display) = expect_context::<SelectedDisplay>(cx);
+    let DisplayStatus { media, .. } = expect_context::<DisplayStatus>(cx);
+    let location = use_location(cx);
+    let tab = use_tab(cx);
+    let instruct = create_server_action::<Instruct>(cx);
+    toast_errors(cx, instruct);
+
+    let is_shown = move || {
+        let on_now_playing = location.pathname.with(|path| path == "/") && tab.get() == Tab::Playing;
+        !on_now_playing && media.with(Option::is_some)
+    };
+
+    let name = move || {
+        media.with(|media| {
+            media
+                .as_ref()
+                .map(|media| media.path.rsplit('/').next().unwrap_or(&media.path).to_string())
+                .unwrap_or_default()
+        })
+    };
+
+    let phase = move || media.with(|media| media.as_ref().map(|media| media.phase.clone()));
+    let is_waiting = move || phase().map(|phase| phase.is_waiting()).unwrap_or(false);
+    let is_playing = move || matches!(phase(), Some(Phase::Playing | Phase::Buffering));
+
+    let progress = move || {
+        media.with(|media| match media {
+            Some(media) if media.duration > 0.0 => (media.position / media.duration * 100.0).clamp(0.0, 100.0),
+            _ => 0.0
+        })
+    };
+
+    let toggle = move |_| {
+        let Some(media) = media.get_untracked() else {
+            return;
+        };
+
+        let i = match media.phase {
+            Phase::Playing | Phase::Buffering => Instruction::Pause { path: media.path, at: media.position },
+            Phase::Ended => Instruction::Play { path: media.path, from: 0.0 },
+            _ => Instruction::Play { path: media.path, from: media.position }
+        };
+
+        instruct.dispatch(Instruct { display: display.get_untracked(), i });
+    };
+
+    view! { cx,
+        <Show when=is_shown fallback=|_| ()>
+            <div class="mini-player shadow">
+                <div class="mini-player-progress" style=move || format!("width: {}%", progress())></div>
+                <A href=Tab::Playing.href() class="mini-player-title text-decoration-none">
+                    <Show when=is_waiting fallback=|_| ()>
+                        <span class="spinner-border spinner-border-sm me-2"></span>
+                    </Show>
+                    {name}
+                </A>
+                <button class="btn btn-primary" on:click=toggle>
+                    {move || {
+                        if is_playing() {
+                            view! { cx, <Icon icon="pause"/> }
+                        } else {
+                            view! { cx, <Icon icon="play"/> }
+                        }
+                    }}
+                </button>
+            </div>
+        </Show>
+    }
+}
@@ -0,0 +1,486 @@
+use crate::error::ErrorAlert;
+use crate::icons::Icon;
+use crate::loading::Loading;
+use crate::profiles::*;
+use leptos::*;
+
+/// Household profiles: choosing which this browser uses, adding and deleting them, parental
+/// controls, and the current profile's preferences.
+#[component]
+pub fn Profiles(cx: Scope) -> impl IntoView {
+    let select = create_server_action::<SelectProfile>(cx);
+    let add = create_server_action::<AddProfile>(cx);
+    let delete = create_server_action::<DeleteProfile>(cx);
+    let restrict = create_server_action::<SetProfileRestrictions>(cx);
+    let grant = create_server_action::<GrantExtraTime>(cx);
+
+    let profiles = create_resource(
+        cx,
+        move || (add.version().get(), delete.version().get(), restrict.version().get(), grant.version().get()),
+        move |_| list_profiles(cx)
+    );
+
+    let current = create_resource(
+        cx,
+        move || (select.version().get(), delete.version().get(), restrict.version().get(), grant.version().get()),
+        move |_| get_current_profile(cx)
+    );
+
+    let current_profile = move || current.read(cx).and_then(|current| current.ok()).flatten();
+    let current_id = move || current_profile().map(|profile| profile.id);
+    let is_restricted = move || current_profile().map(|profile| profile.restrictions.is_some()).unwrap_or(false);
+
+    let error = move || {
+        [select.value().get(), delete.value().get()]
+            .into_iter()
+            .flatten()
+            .find_map(|result| result.err())
+            .map(|e| view! { cx, <ErrorAlert error=e class="mt-3"/> })
+    };
+
+    view! { cx,
+        <div>
+            <div class="card shadow mt-3">
+                <div class="card-body">
+                    <h4 class="mb-3">"Profiles"</h4>
+                    <Transition fallback=|| ()>
+                        {move || match profiles.read(cx) {
+                            None => {
+                                view! { cx, <Loading/> }.into_view(cx)
+                            },
+                            Some(Err(e)) => {
+                                view! { cx, <ErrorAlert error=e class="mt-3"/> }.into_view(cx)
+                            },
+                            Some(Ok(profiles)) if profiles.is_empty() => {
+                                view! { cx, <p class="text-body-secondary">"No profiles yet."</p> }.into_view(cx)
+                            },
+                            Some(Ok(profiles)) => {
+                                view! { cx,
+                                    <table class="table align-middle">
+                                        <tbody>
+                                            {profiles
+                                                .into_iter()
+                                                .map(|profile| {
+                                                    let id = profile.id;
+                                                    let (pin, set_pin) = create_signal(cx, String::new());
+                                                    let is_kid = profile.restrictions.is_some();
+                                                    let minutes_left = profile.minutes_left
+                                                        .map(|minutes| format!("{} min left today", minutes));
+
+                                                    view! { cx,
+                                                        <tr>
+                                                            <td>
+                                                                <Icon icon="user" class="me-2"/>
+                                                                {profile.name}
+                                                                <Show when=move || current_id() == Some(id) fallback=|_| ()>
+                                                                    <span class="badge text-bg-primary ms-2">"In use"</span>
+                                                                </Show>
+                                                                {is_kid.then(|| view! { cx, <span class="badge text-bg-secondary ms-2">"Kid"</span> })}
+                                                                {minutes_left.map(|left| view! { cx, <div class="small text-body-secondary">{left}</div> })}
+                                                            </td>
+                                                            <td>
+                                                                {profile.has_pin.then(|| view! { cx,
+                                                                    <input
+                                                                        class="form-control form-control-sm font-monospace"
+                                                                        type="password"
+                                                                        inputmode="numeric"
+                                                                        placeholder="PIN"
+                                                                        prop:value=pin
+                                                                        on:input=move |ev| set_pin.set(event_target_value(&ev))
+                                                                    />
+                                                                })}
+                                                            </td>
+                                                            <td class="text-end">
+                                                                <button
+                                                                    class="btn btn-sm btn-primary me-2"
+                                                                    on:click=move |_| select.dispatch(SelectProfile { id, pin: pin.get() })
+                                                                >
+                                                                    "Use"
+                                                                </button>
+                                                                <Show when=move || !is_restricted() fallback=|_| ()>
+                                                                    <button
+                                                                        class="btn btn-sm btn-outline-danger"
+                                                                        on:click=move |_| delete.dispatch(DeleteProfile { id, pin: pin.get() })
+                                                                    >
+                                                                        <Icon icon="trash"/>
+                                                                    </button>
+                                                                </Show>
+                                                            </td>
+                                                        </tr>
+                                                    }
+                                                })
+                                                .collect::<Vec<_>>()
+                                            }
+                                        </tbody>
+                                    </table>
+                                }.into_view(cx)
+                            }
+                        }}
+                    </Transition>
+                    {error}
+                </div>
+            </div>
+            <Transition fallback=|| ()>
+                {move || {
+                    current_profile().and_then(|profile| {
+                        let minutes_left = profile.minutes_left?;
+                        Some(view! { cx, <ExtraTime id=profile.id minutes_left=minutes_left grant=grant/> })
+                    })
+                }}
+                {move || {
+                    let profiles = profiles.read(cx).and_then(|profiles| profiles.ok()).unwrap_or_default();
+
+                    (!is_restricted()).then(|| view! { cx,
+                        <NewProfile add=add/>
+                        {(!profiles.is_empty()).then(|| view! { cx, <ParentalControls profiles=profiles restrict=restrict/> })}
+                    })
+                }}
+                {move || current_profile().map(|profile| view! { cx, <PreferencesForm profile=profile/> })}
+            </Transition>
+        </div>
+    }
+}
+
+#[component]
+fn NewProfile(
+    cx: Scope,
+    add: Action<AddProfile, Result<(), ServerFnError>>
+) -> impl IntoView {
+    let (name, set_name) = create_signal(cx, String::new());
+    let (pin, set_pin) = create_signal(cx, String::new());
+
+    let error = move || {
+        add.value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|e| view! { cx, <ErrorAlert error=e class="mt-3"/> })
+    };
+
+    view! { cx,
+        <div class="card shadow mt-3">
+            <div class="card-body">
+                <h5 class="mb-3">"New profile"</h5>
+                <div class="row g-3">
+                    <div class="col-12 col-md-6">
+                        <label class="form-label">"Name"</label>
+                        <input
+                            class="form-control"
+                            prop:value=name
+                            on:input=move |ev| set_name.set(event_target_value(&ev))
+                        />
+                    </div>
+                    <div class="col-12 col-md-6">
+                        <label class="form-label">"PIN (optional)"</label>
+                        <input
+                            class="form-control font-monospace"
+                            inputmode="numeric"
+                            autocomplete="off"
+                            prop:value=pin
+                            on:input=move |ev| set_pin.set(event_target_value(&ev))
+                        />
+                    </div>
+                </div>
+                {error}
+                <button
+                    class="btn btn-primary mt-3"
+                    on:click=move |_| add.dispatch(AddProfile { name: name.get(), pin: pin.get() })
+                >
+                    <Icon icon="plus" class="me-2"/>
+                    "Add profile"
+                </button>
+            </div>
+        </div>
+    }
+}
+
+/// Turns a profile into a kid profile, limiting what it can see and for how long.
+#[component]
+fn ParentalControls(
+    cx: Scope,
+    profiles: Vec<Profile>,
+    restrict: Action<SetProfileRestrictions, Result<(), ServerFnError>>
+) -> impl IntoView {
+    let profiles = store_value(cx, profiles);
+
+    let (id, set_id) = create_signal(cx, profiles.with_value(|profiles| profiles[0].id));
+    let restrictions = move || {
+        profiles.with_value(|profiles| {
+            profiles
+                .iter()
+                .find(|profile| profile.id == id.get())
+                .and_then(|profile| profile.restrictions.clone())
+        })
+    };
+
+    let (is_kid, set_is_kid) = create_signal(cx, false);
+    let (folders, set_folders) = create_signal(cx, String::new());
+    let (max_rating, set_max_rating) = create_signal(cx, String::new());
+    let (daily_minutes, set_daily_minutes) = create_signal(cx, String::new());
+
+    // Show the chosen profile's current limits
+    create_effect(cx, move |_| {
+        let restrictions = restrictions();
+
+        set_is_kid.set(restrictions.is_some());
+
+        let restrictions = restrictions.unwrap_or_default();
+        set_folders.set(restrictions.folders.join(", "));
+        set_max_rating.set(restrictions.max_rating.unwrap_or_default());
+        set_daily_minutes.set(restrictions.daily_minutes.map(|minutes| minutes.to_string()).unwrap_or_default());
+    });
+
+    let submit = move |_| {
+        let restrictions = is_kid.get().then(|| Restrictions {
+            folders: folders.get().split(',').map(|folder| folder.trim().to_string()).collect(),
+            max_rating: Some(max_rating.get()).filter(|rating| !rating.is_empty()),
+            daily_minutes: daily_minutes.get().trim().parse().ok()
+        });
+
+        restrict.dispatch(SetProfileRestrictions { id: id.get(), restrictions });
+    };
+
+    let error = move || {
+        restrict.value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|e| view! { cx, <ErrorAlert error=e class="mt-3"/> })
+    };
+
+    view! { cx,
+        <div class="card shadow mt-3">
+            <div class="card-body">
+                <h5 class="mb-3">"Parental controls"</h5>
+                <div class="row g-3">
+                    <div class="col-12 col-md-6">
+                        <label class="form-label">"Profile"</label>
+                        <select
+                            class="form-select"
+                            on:change=move |ev| {
+                                if let Ok(id) = event_target_value(&ev).parse() {
+                                    set_id.set(id);
+                                }
+                            }
+                        >
+                            {profiles.with_value(|profiles| {
+                                profiles
+                                    .iter()
+                                    .map(|profile| view! { cx, <option value=profile.id.to_string()>{profile.name.clone()}</option> })
+                                    .collect::<Vec<_>>()
+                            })}
+                        </select>
+                    </div>
+                    <div class="col-12 col-md-6 d-flex align-items-end">
+                        <div class="form-check form-switch">
+                            <input
+                                class="form-check-input"
+                                type="checkbox"
+                                prop:checked=is_kid
+                                on:change=move |_| set_is_kid.update(|is_kid| *is_kid = !*is_kid)
+                            />
+                            <label class="form-check-label">"Kid profile"</label>
+                        </div>
+                    </div>
+                </div>
+                <Show when=move || is_kid.get() fallback=|_| ()>
+                    <div class="row g-3 mt-1">
+                        <div class="col-12 col-md-6">
+                            <label class="form-label">"Allowed folders"</label>
+                            <input
+                                class="form-control"
+                                placeholder="Everything"
+                                prop:value=folders
+                                on:input=move |ev| set_folders.set(event_target_value(&ev))
+                            />
+                            <div class="form-text">"Libraries or folders, separated by commas, e.g. kids, films/Animation"</div>
+                        </div>
+                        <div class="col-6 col-md-3">
+                            <label class="form-label">"Highest rating"</label>
+                            <select
+                                class="form-select"
+                                on:change=move |ev| set_max_rating.set(event_target_value(&ev))
+                            >
+                                <option value="" selected=move || max_rating.get().is_empty()>"Any"</option>
+                                {RATINGS
+                                    .iter()
+                                    .map(|group| {
+                                        let rating = group[0];
+                                        view! { cx, <option value=rating selected=move || max_rating.get() == rating>{rating}</option> }
+                                    })
+                                    .collect::<Vec<_>>()
+                                }
+                            </select>
+                        </div>
+                        <div class="col-6 col-md-3">
+                            <label class="form-label">"Minutes a day"</label>
+                            <input
+                                class="form-control"
+                                type="number"
+                                min="1"
+                                placeholder="Unlimited"
+                                prop:value=daily_minutes
+                                on:input=move |ev| set_daily_minutes.set(event_target_value(&ev))
+                            />
+                        </div>
+                    </div>
+                </Show>
+                {error}
+                <button class="btn btn-primary mt-3" on:click=submit>"Save"</button>
+            </div>
+        </div>
+    }
+}
+
+/// Viewing time left for a kid profile, and more time for today with an adult PIN.
+#[component]
+fn ExtraTime(
+    cx: Scope,
+    id: u64,
+    minutes_left: u32,
+    grant: Action<GrantExtraTime, Result<(), ServerFnError>>
+) -> impl IntoView {
+    let (pin, set_pin) = create_signal(cx, String::new());
+    let (minutes, set_minutes) = create_signal(cx, String::from("30"));
+
+    let error = move || {
+        grant.value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|e| view! { cx, <ErrorAlert error=e class="mt-3"/> })
+    };
+
+    let submit = move |_| {
+        if let Ok(minutes) = minutes.get().trim().parse() {
+            grant.dispatch(GrantExtraTime { id, pin: pin.get(), minutes });
+        }
+    };
+
+    view! { cx,
+        <div class="card shadow mt-3">
+            <div class="card-body">
+                <h5 class="mb-3">
+                    <Icon icon="time" class="me-2"/>
+                    {match minutes_left {
+                        0 => "No viewing time left today".to_string(),
+                        minutes => format!("{} minutes of viewing left today", minutes)
+                    }}
+                </h5>
+                <div class="row g-3">
+                    <div class="col-6">
+                        <label class="form-label">"Adult PIN"</label>
+                        <input
+                            class="form-control font-monospace"
+                            type="password"
+                            inputmode="numeric"
+                            prop:value=pin
+                            on:input=move |ev| set_pin.set(event_target_value(&ev))
+                        />
+                    </div>
+                    <div class="col-6">
+                        <label class="form-label">"Extra minutes"</label>
+                        <input
+                            class="form-control"
+                            type="number"
+                            min="1"
+                            max="1440"
+                            prop:value=minutes
+                            on:input=move |ev| set_minutes.set(event_target_value(&ev))
+                        />
+                    </div>
+                </div>
+                {error}
+                <button class="btn btn-primary mt-3" on:click=submit>
+                    <Icon icon="plus" class="me-2"/>
+                    "Add time"
+                </button>
+            </div>
+        </div>
+    }
+}
+
+/// Skip lengths and languages for the current profile. Blank fields use the server's defaults.
+#[component]
+pub fn PreferencesForm(
+    cx: Scope,
+    profile: Profile
+) -> impl IntoView {
+    let save = create_server_action::<SetProfileSettings>(cx);
+    let settings = profile.settings;
+
+    let text = |value: Option<f64>| value.map(|value| value.to_string()).unwrap_or_default();
+
+    let (skip_back, set_skip_back) = create_signal(cx, text(settings.skip_back));
+    let (skip_forward, set_skip_forward) = create_signal(cx, text(settings.skip_forward));
+    let (audio, set_audio) = create_signal(cx, settings.audio_language.unwrap_or_default());
+    let (subtitles, set_subtitles) = create_signal(cx, settings.subtitle_language.unwrap_or_default());
+
+    let seconds = |value: String| value.trim().parse::<f64>().ok().filter(|value| *value > 0.0);
+    let language = |value: String| Some(value.trim().to_lowercase()).filter(|value| !value.is_empty());
+
+    let submit = move |_| {
+        save.dispatch(SetProfileSettings {
+            settings: ProfileSettings {
+                skip_back: seconds(skip_back.get()),
+                skip_forward: seconds(skip_forward.get()),
+                audio_language: language(audio.get()),
+                subtitle_language: language(subtitles.get())
+            }
+        });
+    };
+
+    let result = move || {
+        save.value().get().map(|result| match result {
+            Ok(()) => view! { cx, <div class="alert alert-success mt-3">"Saved"</div> }.into_view(cx),
+            Err(e) => view! { cx, <ErrorAlert error=e class="mt-3"/> }.into_view(cx)
+        })
+    };
+
+    view! { cx,
+        <div class="card shadow mt-3 mb-3">
+            <div class="card-body">
+                <h5 class="mb-3">"Preferences for "{profile.name}</h5>
+                <div class="row g-3">
+                    <div class="col-6 col-md-3">
+                        <label class="form-label">"Skip back (seconds)"</label>
+                        <input
+                            class="form-control"
+                            type="number"
+                            min="1"
+                            prop:value=skip_back
+                            on:input=move |ev| set_skip_back.set(event_target_value(&ev))
+                        />
+                    </div>
+                    <div class="col-6 col-md-3">
+                        <label class="form-label">"Skip forward (seconds)"</label>
+                        <input
+                            class="form-control"
+                            type="number"
+                            min="1"
+                            prop:value=skip_forward
+                            on:input=move |ev| set_skip_forward.set(event_target_value(&ev))
+                        />
+                    </div>
+                    <div class="col-6 col-md-3">
+                        <label class="form-label">"Audio language"</label>
+                        <input
+                            class="form-control"
+                            placeholder="en"
+                            prop:value=audio
+                            on:input=move |ev| set_audio.set(event_target_value(&ev))
+                        />
+                    </div>
+                    <div class="col-6 col-md-3">
+                        <label class="form-label">"Subtitle language"</label>
+                        <input
+                            class="form-control"
+                            placeholder="None"
+                            prop:value=subtitles
+                            on:input=move |ev| set_subtitles.set(event_target_value(&ev))
+                        />
+                    </div>
+                </div>
+                {result}
+                <button class="btn btn-primary mt-3" on:click=submit>"Save"</button>
+            </div>
+        </div>
+    }
+}
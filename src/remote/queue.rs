@@ -0,0 +1,74 @@
+use crate::icons::Icon;
+use crate::player::*;
+use crate::remote::fns::Skip;
+use crate::remote::SelectedDisplay;
+use crate::toast::toast_errors;
+use leptos::*;
+
+/// The queue in play order, with the current item marked. Picking another item skips to it.
+#[component]
+pub fn QueueList(
+    cx: Scope,
+    queue: Memo<Queue>
+) -> impl IntoView {
+    let SelectedDisplay(display) = expect_context::<SelectedDisplay>(cx);
+    let skip = create_server_action::<Skip>(cx);
+    toast_errors(cx, skip);
+
+    let items = move || {
+        queue.with(|queue| {
+            queue.order
+                .iter()
+                .filter_map(|ix| queue.items.get(*ix).cloned())
+                .enumerate()
+                .map(|(position, path)| (position, path, position == queue.position))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    view! { cx,
+        <div class="card shadow mt-5">
+            <div class="card-body">
+                {move || {
+                    let items = items();
+
+                    if items.is_empty() {
+                        return view! { cx, <p class="text-body-secondary mb-0">"The queue is empty."</p> }.into_view(cx);
+                    }
+
+                    items
+                        .into_iter()
+                        .map(|(position, path, is_current)| {
+                            let name = path.rsplit('/').next().unwrap_or(&path).to_string();
+                            let icon = match MediaKind::of(&path) {
+                                MediaKind::Audio => "music",
+                                MediaKind::Image => "image",
+                                MediaKind::Video => "file"
+                            };
+                            let class = if is_current { "btn btn-primary text-start" } else { "btn btn-link text-start text-decoration-none" };
+
+                            view! { cx,
+                                <div class="d-grid mb-2">
+                                    <button
+                                        class=class
+                                        on:click=move |_| {
+                                            let offset = position as i32 - queue.with_untracked(|queue| queue.position) as i32;
+
+                                            if offset != 0 {
+                                                skip.dispatch(Skip { display: display.get_untracked(), offset });
+                                            }
+                                        }
+                                    >
+                                        <Icon icon=icon class="me-2"/>
+                                        {name}
+                                    </button>
+                                </div>
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                        .into_view(cx)
+                }}
+            </div>
+        </div>
+    }
+}
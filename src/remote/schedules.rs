@@ -0,0 +1,227 @@
+use crate::displays::{list_displays, DEFAULT_DISPLAY};
+use crate::error::ErrorAlert;
+use crate::icons::Icon;
+use crate::loading::Loading;
+use crate::player::{Instruction, SlideSettings, Slideshow};
+use crate::schedule::{list_schedules, AddSchedule, DeleteSchedule, SetScheduleEnabled};
+use leptos::*;
+
+#[component]
+pub fn Schedules(cx: Scope) -> impl IntoView {
+    let add = create_server_action::<AddSchedule>(cx);
+    let set_enabled = create_server_action::<SetScheduleEnabled>(cx);
+    let delete = create_server_action::<DeleteSchedule>(cx);
+
+    let schedules = create_resource(
+        cx,
+        move || (add.version().get(), set_enabled.version().get(), delete.version().get()),
+        move |_| list_schedules(cx)
+    );
+
+    view! { cx,
+        <div>
+            <div class="card shadow mt-3">
+                <div class="card-body">
+                    <h4 class="mb-3">"Schedules"</h4>
+                    <Transition fallback=|| ()>
+                        {move || match schedules.read(cx) {
+                            None => {
+                                view! { cx, <Loading/> }.into_view(cx)
+                            },
+                            Some(Err(e)) => {
+                                view! { cx, <ErrorAlert error=e class="mt-3"/> }.into_view(cx)
+                            },
+                            Some(Ok(schedules)) if schedules.is_empty() => {
+                                view! { cx, <p class="text-body-secondary">"Nothing scheduled yet."</p> }.into_view(cx)
+                            },
+                            Some(Ok(schedules)) => {
+                                view! { cx,
+                                    <table class="table align-middle">
+                                        <thead>
+                                            <tr>
+                                                <th>"Name"</th>
+                                                <th>"When"</th>
+                                                <th>"Display"</th>
+                                                <th>"Action"</th>
+                                                <th>"Enabled"</th>
+                                                <th></th>
+                                            </tr>
+                                        </thead>
+                                        <tbody>
+                                            {schedules
+                                                .into_iter()
+                                                .map(|schedule| {
+                                                    let id = schedule.id;
+                                                    let enabled = schedule.enabled;
+                                                    let editable = !schedule.from_config;
+
+                                                    view! { cx,
+                                                        <tr>
+                                                            <td>{schedule.name}</td>
+                                                            <td><code>{schedule.cron}</code></td>
+                                                            <td>{schedule.display}</td>
+                                                            <td>{describe(&schedule.action)}</td>
+                                                            <td>
+                                                                <div class="form-check form-switch">
+                                                                    <input
+                                                                        class="form-check-input"
+                                                                        type="checkbox"
+                                                                        prop:checked=enabled
+                                                                        disabled=!editable
+                                                                        title=move || (!editable).then_some("Set in the config file")
+                                                                        on:change=move |_| {
+                                                                            set_enabled.dispatch(SetScheduleEnabled { id, enabled: !enabled })
+                                                                        }
+                                                                    />
+                                                                </div>
+                                                            </td>
+                                                            <td class="text-end">
+                                                                {editable.then(|| view! { cx,
+                                                                    <button
+                                                                        class="btn btn-sm btn-outline-danger"
+                                                                        on:click=move |_| delete.dispatch(DeleteSchedule { id })
+                                                                    >
+                                                                        <Icon icon="trash"/>
+                                                                    </button>
+                                                                })}
+                                                            </td>
+                                                        </tr>
+                                                    }
+                                                })
+                                                .collect::<Vec<_>>()
+                                            }
+                                        </tbody>
+                                    </table>
+                                }.into_view(cx)
+                            }
+                        }}
+                    </Transition>
+                </div>
+            </div>
+            <NewSchedule add=add/>
+        </div>
+    }
+}
+
+#[component]
+fn NewSchedule(
+    cx: Scope,
+    add: Action<AddSchedule, Result<(), ServerFnError>>
+) -> impl IntoView {
+    let displays = create_resource(cx, || (), move |_| list_displays(cx));
+
+    let (name, set_name) = create_signal(cx, String::new());
+    let (cron, set_cron) = create_signal(cx, String::from("0 7 * * 1-5"));
+    let (display, set_display) = create_signal(cx, DEFAULT_DISPLAY.to_string());
+    let (action, set_action) = create_signal(cx, String::from("play"));
+    let (folder, set_folder) = create_signal(cx, String::new());
+
+    let error = move || {
+        add.value()
+            .get()
+            .and_then(|result| result.err())
+            .map(|e| view! { cx, <ErrorAlert error=e class="mt-3"/> })
+    };
+
+    let submit = move |_| {
+        let action = match action.get().as_str() {
+            "stop" => Instruction::Stop,
+            "slideshow" => Instruction::Slideshow(Slideshow {
+                folder: folder.get(),
+                shuffle: true,
+                settings: SlideSettings::default()
+            }),
+            _ => Instruction::PlayFolder { folder: folder.get() }
+        };
+
+        add.dispatch(AddSchedule {
+            name: name.get(),
+            cron: cron.get(),
+            display: display.get(),
+            action
+        });
+    };
+
+    view! { cx,
+        <div class="card shadow mt-3 mb-5">
+            <div class="card-body">
+                <h5 class="mb-3">"New schedule"</h5>
+                <div class="row g-3">
+                    <div class="col-12 col-md-6">
+                        <label class="form-label">"Name"</label>
+                        <input
+                            class="form-control"
+                            placeholder="Morning music"
+                            prop:value=name
+                            on:input=move |ev| set_name.set(event_target_value(&ev))
+                        />
+                    </div>
+                    <div class="col-12 col-md-6">
+                        <label class="form-label">"When"</label>
+                        <input
+                            class="form-control font-monospace"
+                            prop:value=cron
+                            on:input=move |ev| set_cron.set(event_target_value(&ev))
+                        />
+                        <div class="form-text">"minute hour day month weekday, e.g. 0 7 * * 1-5 for 07:00 on weekdays"</div>
+                    </div>
+                    <div class="col-12 col-md-4">
+                        <label class="form-label">"Display"</label>
+                        <input
+                            class="form-control"
+                            list="schedule-displays"
+                            prop:value=display
+                            on:input=move |ev| set_display.set(event_target_value(&ev))
+                        />
+                        <datalist id="schedule-displays">
+                            <Transition fallback=|| ()>
+                                {move || {
+                                    displays
+                                        .read(cx)
+                                        .and_then(|displays| displays.ok())
+                                        .unwrap_or_default()
+                                        .into_iter()
+                                        .map(|name| view! { cx, <option value=name/> })
+                                        .collect::<Vec<_>>()
+                                }}
+                            </Transition>
+                        </datalist>
+                    </div>
+                    <div class="col-12 col-md-4">
+                        <label class="form-label">"Action"</label>
+                        <select class="form-select" on:change=move |ev| set_action.set(event_target_value(&ev))>
+                            <option value="play" selected=true>"Play folder"</option>
+                            <option value="slideshow">"Slideshow"</option>
+                            <option value="stop">"Stop"</option>
+                        </select>
+                    </div>
+                    <div class="col-12 col-md-4">
+                        <label class="form-label">"Folder"</label>
+                        <input
+                            class="form-control"
+                            placeholder="music/Morning"
+                            disabled=move || action.get() == "stop"
+                            prop:value=folder
+                            on:input=move |ev| set_folder.set(event_target_value(&ev))
+                        />
+                    </div>
+                </div>
+                {error}
+                <button class="btn btn-primary mt-3" on:click=submit>
+                    <Icon icon="plus" class="me-2"/>
+                    "Add schedule"
+                </button>
+            </div>
+        </div>
+    }
+}
+
+fn describe(action: &Instruction) -> String {
+    match action {
+        Instruction::Stop => "Stop".to_string(),
+        Instruction::PlayFolder { folder } => format!("Play {}", folder),
+        Instruction::Slideshow(slideshow) => format!("Slideshow of {}", slideshow.folder),
+        Instruction::Play { path, .. } => format!("Play {}", path),
+        other => format!("{:?}", other)
+    }
+}
@@ -0,0 +1,113 @@
+use crate::control::SetRemoteName;
+use crate::error::ErrorAlert;
+use crate::icons::Icon;
+use crate::profiles::get_current_profile;
+use crate::remote::profiles::PreferencesForm;
+use crate::remote::{DisplayStatus, SelectedDisplay, ThisRemote};
+use crate::socket::BASE_PATH;
+use leptos::*;
+use leptos_router::A;
+
+/// This remote's name, which display it controls, and the current profile's preferences.
+#[component]
+pub fn Settings(cx: Scope) -> impl IntoView {
+    let SelectedDisplay(selected) = expect_context::<SelectedDisplay>(cx);
+    let DisplayStatus { statuses, .. } = expect_context::<DisplayStatus>(cx);
+    let ThisRemote { remote, rename } = expect_context::<ThisRemote>(cx);
+
+    let (name, set_name) = create_signal(cx, String::new());
+
+    // Starts from the current name once it's known
+    create_effect(cx, move |_| {
+        if let Some(remote) = remote.get() {
+            set_name.set(remote.name);
+        }
+    });
+
+    let renamed = move || {
+        rename.value().get().map(|result| match result {
+            Ok(()) => view! { cx, <div class="alert alert-success mt-3">"Saved"</div> }.into_view(cx),
+            Err(e) => view! { cx, <ErrorAlert error=e class="mt-3"/> }.into_view(cx)
+        })
+    };
+
+    let profile = create_resource(
+        cx,
+        || (),
+        move |_| get_current_profile(cx)
+    );
+
+    let displays = move || {
+        statuses.with(|statuses| {
+            statuses
+                .iter()
+                .map(|(name, status)| (name.clone(), status.online, !status.members.is_empty()))
+                .collect::<Vec<_>>()
+        })
+    };
+
+    view! { cx,
+        <div class="card shadow mt-3">
+            <div class="card-body">
+                <h5 class="mb-3">"This remote"</h5>
+                <label class="form-label">"Name, as other remotes see it"</label>
+                <div class="d-flex gap-2">
+                    <input
+                        class="form-control"
+                        placeholder="Alex's phone"
+                        prop:value=name
+                        on:input=move |ev| set_name.set(event_target_value(&ev))
+                    />
+                    <button class="btn btn-primary" on:click=move |_| rename.dispatch(SetRemoteName { name: name.get() })>
+                        "Save"
+                    </button>
+                </div>
+                {renamed}
+            </div>
+        </div>
+        <div class="card shadow mt-3">
+            <div class="card-body">
+                <h5 class="mb-3">"Display"</h5>
+                <div class="d-grid gap-2">
+                    {move || {
+                        displays()
+                            .into_iter()
+                            .map(|(name, is_online, is_group)| {
+                                let is_selected = name == selected.get();
+                                let class = if is_selected { "btn btn-primary text-start" } else { "btn btn-outline-secondary text-start" };
+                                let choose = name.clone();
+
+                                view! { cx,
+                                    <button class=class on:click=move |_| selected.set(choose.clone())>
+                                        <Icon icon="devices" class="me-2"/>
+                                        {name}
+                                        {is_group.then(|| view! { cx, <span class="badge text-bg-primary ms-2">"Group"</span> })}
+                                        {(!is_online).then(|| view! { cx, <span class="badge text-bg-secondary ms-2">"Offline"</span> })}
+                                    </button>
+                                }
+                            })
+                            .collect::<Vec<_>>()
+                    }}
+                </div>
+            </div>
+        </div>
+        <Transition fallback=|| ()>
+            {move || match profile.read(cx) {
+                None => ().into_view(cx),
+                Some(Err(e)) => view! { cx, <ErrorAlert error=e class="mt-3"/> }.into_view(cx),
+                Some(Ok(Some(profile))) => view! { cx, <PreferencesForm profile=profile/> }.into_view(cx),
+                Some(Ok(None)) => view! { cx,
+                    <div class="card shadow mt-3">
+                        <div class="card-body">
+                            <p class="text-body-secondary">"Choose a profile to set skip lengths and languages."</p>
+                            <A href=format!("{}/profiles", BASE_PATH) class="btn btn-primary">
+                                <Icon icon="user" class="me-1"/>
+                                "Profiles"
+                            </A>
+                        </div>
+                    </div>
+                }.into_view(cx)
+            }}
+        </Transition>
+    }
+}
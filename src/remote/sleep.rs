@@ -0,0 +1,99 @@
+use crate::icons::Icon;
+use crate::player::*;
+use crate::remote::SelectedDisplay;
+use crate::sleep::{CancelSleepTimer, SetSleepTimer};
+use crate::toast::toast_errors;
+use leptos::*;
+
+static MINUTES : &'static [u32] = &[15, 30, 60, 90];
+
+#[component]
+pub fn SleepControls(
+    cx: Scope,
+    status: Memo<Option<SleepStatus>>
+) -> impl IntoView {
+    let SelectedDisplay(display) = expect_context::<SelectedDisplay>(cx);
+    let set_timer = create_server_action::<SetSleepTimer>(cx);
+    let cancel_timer = create_server_action::<CancelSleepTimer>(cx);
+    toast_errors(cx, set_timer);
+    toast_errors(cx, cancel_timer);
+
+    let (fade, set_fade) = create_signal(cx, true);
+
+    let start = move |mode| {
+        set_timer.dispatch(SetSleepTimer {
+            display: display.get_untracked(),
+            mode,
+            fade: fade.get_untracked()
+        })
+    };
+
+    move || match status.get() {
+        Some(status) => {
+            let description = match (status.mode, status.remaining) {
+                (SleepMode::Minutes(_), Some(remaining)) => {
+                    format!("Stopping in {}:{:02}", remaining / 60, remaining % 60)
+                },
+                (SleepMode::AfterQueue, _) => "Stopping after the queue".to_string(),
+                _ => "Stopping after this item".to_string()
+            };
+
+            view! { cx,
+                <div class="d-flex justify-content-center align-items-center mb-4">
+                    <Icon icon="moon" class="me-2"/>
+                    <span class="me-3">{description}</span>
+                    <button
+                        class="btn btn-sm btn-outline-secondary"
+                        on:click=move |_| cancel_timer.dispatch(CancelSleepTimer { display: display.get_untracked() })
+                    >
+                        "Cancel"
+                    </button>
+                </div>
+            }.into_view(cx)
+        },
+        None => {
+            view! { cx,
+                <div class="d-flex flex-wrap justify-content-center align-items-center gap-2 mb-4">
+                    <Icon icon="moon"/>
+                    {MINUTES
+                        .iter()
+                        .map(|minutes| {
+                            let minutes = *minutes;
+                            view! { cx,
+                                <button
+                                    class="btn btn-sm btn-outline-secondary"
+                                    on:click=move |_| start(SleepMode::Minutes(minutes))
+                                >
+                                    {format!("{}m", minutes)}
+                                </button>
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                    }
+                    <button
+                        class="btn btn-sm btn-outline-secondary"
+                        on:click=move |_| start(SleepMode::AfterItem)
+                    >
+                        "End of item"
+                    </button>
+                    <button
+                        class="btn btn-sm btn-outline-secondary"
+                        on:click=move |_| start(SleepMode::AfterQueue)
+                    >
+                        "End of queue"
+                    </button>
+                    <div class="form-check form-switch ms-2">
+                        <input
+                            class="form-check-input"
+                            type="checkbox"
+                            id="sleep-fade"
+                            prop:checked=fade
+                            on:change=move |ev| set_fade.set(event_target_checked(&ev))
+                        />
+                        <label class="form-check-label" for="sleep-fade">"Fade out"</label>
+                    </div>
+                </div>
+            }.into_view(cx)
+        }
+    }
+}
@@ -0,0 +1,96 @@
+use crate::icons::Icon;
+use crate::player::*;
+use crate::remote::fns::Instruct;
+use crate::remote::SelectedDisplay;
+use crate::toast::toast_errors;
+use leptos::*;
+
+static INTERVALS : &'static [u32] = &[5, 10, 20, 30, 60];
+
+#[component]
+pub fn SlideshowControls(
+    cx: Scope,
+    folder: String
+) -> impl IntoView {
+    let SelectedDisplay(display) = expect_context::<SelectedDisplay>(cx);
+    let instruct = create_server_action::<Instruct>(cx);
+    toast_errors(cx, instruct);
+
+    let (interval, set_seconds) = create_signal(cx, SlideSettings::default().interval);
+    let (shuffle, set_shuffle) = create_signal(cx, false);
+    let (transition, set_transition) = create_signal(cx, SlideSettings::default().transition);
+
+    view! { cx,
+        <div class="row g-2 align-items-center mb-3">
+            <div class="col-auto">
+                <select
+                    class="form-select"
+                    on:change=move |ev| {
+                        if let Ok(seconds) = event_target_value(&ev).parse::<f64>() {
+                            set_seconds.set(seconds);
+                        }
+                    }
+                >
+                    {INTERVALS
+                        .iter()
+                        .map(|seconds| {
+                            let selected = *seconds as f64 == interval.get_untracked();
+                            view! { cx,
+                                <option value=seconds.to_string() selected=selected>{format!("{}s", seconds)}</option>
+                            }
+                        })
+                        .collect::<Vec<_>>()
+                    }
+                </select>
+            </div>
+            <div class="col-auto">
+                <select
+                    class="form-select"
+                    on:change=move |ev| {
+                        let transition = match event_target_value(&ev).as_str() {
+                            "none" => Transition::None,
+                            "slide" => Transition::Slide,
+                            _ => Transition::Fade
+                        };
+                        set_transition.set(transition);
+                    }
+                >
+                    <option value="fade" selected=move || transition.get() == Transition::Fade>"Fade"</option>
+                    <option value="slide" selected=move || transition.get() == Transition::Slide>"Slide"</option>
+                    <option value="none" selected=move || transition.get() == Transition::None>"No transition"</option>
+                </select>
+            </div>
+            <div class="col-auto">
+                <div class="form-check form-switch">
+                    <input
+                        class="form-check-input"
+                        type="checkbox"
+                        id="slideshow-shuffle"
+                        prop:checked=shuffle
+                        on:change=move |ev| set_shuffle.set(event_target_checked(&ev))
+                    />
+                    <label class="form-check-label" for="slideshow-shuffle">"Shuffle"</label>
+                </div>
+            </div>
+            <div class="col-auto">
+                <button
+                    class="btn btn-primary"
+                    on:click=move |_| {
+                        let slideshow = Slideshow {
+                            folder: folder.clone(),
+                            shuffle: shuffle.get(),
+                            settings: SlideSettings {
+                                interval: interval.get(),
+                                transition: transition.get()
+                            }
+                        };
+                        instruct.dispatch(Instruct { display: display.get_untracked(), i: Instruction::Slideshow(slideshow) });
+                    }
+                >
+                    <Icon icon="slideshow" class="me-2"/>
+                    "Slideshow"
+                </button>
+            </div>
+        </div>
+    }
+}
@@ -0,0 +1,437 @@
+use cfg_if::cfg_if;
+#[allow(unused_imports)]
+use crate::error::PlayerError;
+use crate::player::*;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+
+static WEEKDAYS : &'static [&'static str] = &["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+static MONTHS : &'static [&'static str] = &["JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC"];
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Schedule {
+    pub id: u64,
+    pub name: String,
+    pub enabled: bool,
+    /// Cron expression: minute, hour, day of month, month, day of week
+    pub cron: String,
+    pub display: String,
+    pub action: Instruction,
+    /// Set in the config file, so it can't be changed from a remote
+    #[serde(default)]
+    pub from_config: bool
+}
+
+/// A parsed five field cron expression, with each field as a bitmask of allowed values.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cron {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    any_day: bool,
+    any_weekday: bool
+}
+
+impl Cron {
+    pub fn parse(expr: &str) -> Result<Self, String> {
+        let fields = expr.split_whitespace().collect::<Vec<_>>();
+
+        let &[minutes, hours, days, months, weekdays] = fields.as_slice() else {
+            return Err(format!("Expected 5 fields (minute hour day month weekday), found {}", fields.len()));
+        };
+
+        // Sunday may be written as 0 or 7
+        let weekday_mask = parse_field(weekdays, 0, 7, WEEKDAYS, 0)?;
+        let weekday_mask = (weekday_mask | (weekday_mask >> 7)) & 0x7f;
+
+        Ok(Self {
+            minutes: parse_field(minutes, 0, 59, &[], 0)?,
+            hours: parse_field(hours, 0, 23, &[], 0)?,
+            days: parse_field(days, 1, 31, &[], 0)?,
+            months: parse_field(months, 1, 12, MONTHS, 1)?,
+            weekdays: weekday_mask,
+            // Like cron, `*/2` still counts as any day, so only the other field restricts the date
+            any_day: days.starts_with('*'),
+            any_weekday: weekdays.starts_with('*')
+        })
+    }
+
+    /// `weekday` counts from Sunday = 0.
+    pub fn matches(&self, minute: u32, hour: u32, day: u32, month: u32, weekday: u32) -> bool {
+        let bit = |mask: u64, value: u32| mask & (1 << value) != 0;
+
+        let day_matches = bit(self.days, day);
+        let weekday_matches = bit(self.weekdays, weekday);
+
+        // As in cron, a restricted day of month and day of week match if either does
+        let date_matches = match (self.any_day, self.any_weekday) {
+            (false, false) => day_matches || weekday_matches,
+            _ => day_matches && weekday_matches
+        };
+
+        bit(self.minutes, minute) && bit(self.hours, hour) && bit(self.months, month) && date_matches
+    }
+}
+
+/// Parses a comma separated list of `*`, values, ranges and `/step`s into a bitmask.
+/// `names` are accepted in place of numbers, counting from `names_from`.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str], names_from: u32) -> Result<u64, String> {
+    let value = |s: &str| -> Result<u32, String> {
+        let named = names
+            .iter()
+            .position(|name| name.eq_ignore_ascii_case(s))
+            .map(|ix| ix as u32 + names_from);
+
+        let value = match named {
+            Some(value) => value,
+            None => s.parse::<u32>().map_err(|_| format!("Invalid value '{}' in '{}'", s, field))?
+        };
+
+        if value < min || value > max {
+            return Err(format!("Value {} in '{}' is outside {}-{}", value, field, min, max));
+        }
+
+        Ok(value)
+    };
+
+    let mut mask = 0;
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => {
+                let step = step.parse::<u32>().ok().filter(|step| *step > 0);
+                (range, step.ok_or_else(|| format!("Invalid step in '{}'", field))?)
+            },
+            None => (part, 1)
+        };
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (value(start)?, value(end)?),
+                None if step > 1 => (value(range)?, max),
+                None => (value(range)?, value(range)?)
+            }
+        };
+
+        if start > end {
+            return Err(format!("Range '{}' runs backwards", range));
+        }
+
+        for v in (start..=end).step_by(step as usize) {
+            mask |= 1 << v;
+        }
+    }
+
+    Ok(mask)
+}
+
+#[server(ListSchedules, "/api")]
+pub async fn list_schedules(cx: Scope) -> Result<Vec<Schedule>, ServerFnError> {
+    let schedules = expect_context::<Schedules>(cx);
+    Ok(schedules.list())
+}
+
+#[server(AddSchedule, "/api")]
+pub async fn add_schedule(
+    cx: Scope,
+    name: String,
+    cron: String,
+    display: String,
+    action: Instruction
+) -> Result<(), ServerFnError> {
+    crate::profiles::require_unrestricted(cx)?;
+
+    let schedules = expect_context::<Schedules>(cx);
+
+    Cron::parse(&cron).map_err(PlayerError::InvalidInput)?;
+    action.check().map_err(PlayerError::InvalidInstruction)?;
+
+    schedules
+        .update(|schedules| {
+            let id = schedules.iter().map(|s| s.id).max().unwrap_or(0) + 1;
+
+            schedules.push(Schedule {
+                id,
+                name,
+                enabled: true,
+                cron,
+                display,
+                action,
+                from_config: false
+            });
+        })
+        .map_err(|e| PlayerError::from(e).into())
+}
+
+#[server(SetScheduleEnabled, "/api")]
+pub async fn set_schedule_enabled(cx: Scope, id: u64, enabled: bool) -> Result<(), ServerFnError> {
+    crate::profiles::require_unrestricted(cx)?;
+
+    let schedules = expect_context::<Schedules>(cx);
+    schedules.check_editable(id)?;
+
+    schedules
+        .update(|schedules| {
+            if let Some(schedule) = schedules.iter_mut().find(|s| s.id == id) {
+                schedule.enabled = enabled;
+            }
+        })
+        .map_err(|e| PlayerError::from(e).into())
+}
+
+#[server(DeleteSchedule, "/api")]
+pub async fn delete_schedule(cx: Scope, id: u64) -> Result<(), ServerFnError> {
+    crate::profiles::require_unrestricted(cx)?;
+
+    let schedules = expect_context::<Schedules>(cx);
+    schedules.check_editable(id)?;
+
+    schedules
+        .update(|schedules| schedules.retain(|s| s.id != id))
+        .map_err(|e| PlayerError::from(e).into())
+}
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use chrono::{Datelike, Local, Timelike};
+    use crate::config::ScheduleConfig;
+    use crate::displays::Displays;
+    use crate::library::Libraries;
+    use crate::store::{self, Migration};
+    use serde_json::{Map, Value};
+    use std::path::PathBuf;
+    use std::sync::{Arc, PoisonError, RwLock};
+    use std::time::Duration;
+
+    static MIGRATIONS : &'static [Migration] = &[store::unversioned, instruction_fields];
+
+    /// Field names given to each instruction's values in version 2, in their old order.
+    static INSTRUCTION_FIELDS : &'static [(&'static str, &'static [&'static str])] = &[
+        ("Play", &["path", "from"]),
+        ("Pause", &["path", "at"]),
+        ("Queue", &["items", "position"]),
+        ("PlayFolder", &["folder"]),
+        ("Show", &["path", "settings"]),
+        ("Volume", &["volume"])
+    ];
+
+    /// Schedules from the config file, followed by those added from remotes, which are
+    /// persisted as JSON whenever they change.
+    #[derive(Clone, Debug)]
+    pub struct Schedules {
+        path: PathBuf,
+        configured: Arc<Vec<Schedule>>,
+        schedules: Arc<RwLock<Vec<Schedule>>>
+    }
+
+    impl Schedules {
+        /// Loads schedules from `path`, starting empty if it doesn't exist yet, alongside those
+        /// in the config.
+        pub fn load(path: PathBuf, configured: &[ScheduleConfig]) -> Result<Self, String> {
+            let schedules = store::load(&path, MIGRATIONS)?;
+
+            // Counting down from the top, so they can't clash with those added from remotes
+            let configured = configured
+                .iter()
+                .enumerate()
+                .map(|(ix, schedule)| Schedule {
+                    id: u64::MAX - ix as u64,
+                    name: schedule.name.clone(),
+                    enabled: true,
+                    cron: schedule.cron.clone(),
+                    display: schedule.display.clone(),
+                    action: schedule.action.clone(),
+                    from_config: true
+                })
+                .collect();
+
+            Ok(Self {
+                path,
+                configured: Arc::new(configured),
+                schedules: Arc::new(RwLock::new(schedules))
+            })
+        }
+
+        pub fn list(&self) -> Vec<Schedule> {
+            let schedules = self.schedules.read().unwrap_or_else(PoisonError::into_inner);
+            self.configured.iter().chain(schedules.iter()).cloned().collect()
+        }
+
+        /// Refuses changes to schedules from the config file.
+        pub fn check_editable(&self, id: u64) -> Result<(), PlayerError> {
+            match self.configured.iter().find(|schedule| schedule.id == id) {
+                Some(schedule) => Err(PlayerError::Forbidden(format!("{} is set in the config file", schedule.name))),
+                None => Ok(())
+            }
+        }
+
+        pub fn update(&self, f: impl FnOnce(&mut Vec<Schedule>)) -> std::io::Result<()> {
+            let mut schedules = self.schedules.write().unwrap_or_else(PoisonError::into_inner);
+            f(&mut schedules);
+
+            store::save(&self.path, MIGRATIONS.len(), &*schedules)
+        }
+    }
+
+    /// Version 2 gave instructions named fields, so `{"Play": ["a.mkv", 0.0]}` became
+    /// `{"Play": {"path": "a.mkv", "from": 0.0}}`.
+    fn instruction_fields(mut data: Value) -> Result<Value, String> {
+        let schedules = data
+            .as_array_mut()
+            .ok_or_else(|| "Expected a list of schedules".to_string())?;
+
+        for schedule in schedules {
+            let Some(action) = schedule.get_mut("action").and_then(Value::as_object_mut) else {
+                continue;
+            };
+
+            for (variant, names) in INSTRUCTION_FIELDS {
+                let Some(old) = action.get_mut(*variant) else {
+                    continue;
+                };
+
+                let values = match old.take() {
+                    Value::Array(values) if names.len() > 1 => values,
+                    value => vec![value]
+                };
+
+                let fields = names
+                    .iter()
+                    .map(|name| name.to_string())
+                    .zip(values)
+                    .collect::<Map<_, _>>();
+
+                *old = Value::Object(fields);
+            }
+        }
+
+        Ok(data)
+    }
+
+    /// Wakes at the start of every minute and sends each matching, enabled schedule's action to its display.
+    pub async fn run(schedules: Schedules, displays: Displays, libraries: Libraries) {
+        let mut last_run = None;
+
+        loop {
+            let now = Local::now();
+            let wait = 60 - now.second() as u64;
+            tokio::time::sleep(Duration::from_secs(wait) + Duration::from_millis(100)).await;
+
+            let now = Local::now();
+            let minute = now.format("%Y-%m-%d %H:%M").to_string();
+
+            // Guard against waking twice within the same minute
+            if last_run.as_ref() == Some(&minute) {
+                continue;
+            }
+            last_run = Some(minute);
+
+            for schedule in schedules.list().into_iter().filter(|s| s.enabled) {
+                let due = Cron::parse(&schedule.cron)
+                    .map(|cron| {
+                        cron.matches(
+                            now.minute(),
+                            now.hour(),
+                            now.day(),
+                            now.month(),
+                            now.weekday().num_days_from_sunday()
+                        )
+                    })
+                    .unwrap_or(false);
+
+                if due {
+                    log::info!("Running schedule '{}' on {}", schedule.name, schedule.display);
+                    let by = Remote {
+                        id: format!("schedule-{}", schedule.id),
+                        name: format!("The {} schedule", schedule.name)
+                    };
+                    let result = displays.instruct(&schedule.display, &libraries, &by, schedule.action);
+
+                    if let Err(e) = result {
+                        log::warn!("Schedule '{}' failed: {}", schedule.name, e);
+                    }
+                }
+            }
+        }
+    }
+
+    //
+    //
+    //
+}}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Whether `cron` matches 2024-01-01 (a Monday) at `hour:minute`, or a later `day` in January.
+    fn matches(cron: &str, minute: u32, hour: u32, day: u32) -> bool {
+        let weekday = day % 7;
+        Cron::parse(cron).unwrap().matches(minute, hour, day, 1, weekday)
+    }
+
+    #[test]
+    fn parses_values_ranges_lists_and_steps() {
+        assert!(matches("30 7 * * *", 30, 7, 1));
+        assert!(!matches("30 7 * * *", 31, 7, 1));
+        assert!(matches("0 9-17 * * *", 0, 17, 1));
+        assert!(!matches("0 9-17 * * *", 0, 18, 1));
+        assert!(matches("0,15,30,45 * * * *", 45, 3, 1));
+        assert!(matches("*/20 * * * *", 40, 3, 1));
+        assert!(!matches("*/20 * * * *", 50, 3, 1));
+        assert!(matches("5/20 * * * *", 25, 3, 1));
+    }
+
+    #[test]
+    fn parses_names() {
+        assert!(matches("0 7 * JAN MON-FRI", 0, 7, 1));
+        assert!(!matches("0 7 * FEB *", 0, 7, 1));
+        assert!(!matches("0 7 * * sat,sun", 0, 7, 1));
+    }
+
+    #[test]
+    fn takes_sunday_as_0_or_7() {
+        assert!(matches("0 7 * * 0", 0, 7, 7));
+        assert!(matches("0 7 * * 7", 0, 7, 7));
+        assert!(!matches("0 7 * * 7", 0, 7, 8));
+    }
+
+    #[test]
+    fn matches_either_restricted_day_field() {
+        // The 16th, and every Monday
+        assert!(matches("0 7 16 * MON", 0, 7, 16));
+        assert!(matches("0 7 16 * MON", 0, 7, 8));
+        assert!(!matches("0 7 16 * MON", 0, 7, 9));
+    }
+
+    #[test]
+    fn treats_stepped_stars_as_unrestricted() {
+        // Odd days, but only on Mondays
+        assert!(matches("0 7 */2 * MON", 0, 7, 1));
+        assert!(!matches("0 7 */2 * MON", 0, 7, 8));
+        assert!(!matches("0 7 */2 * MON", 0, 7, 3));
+
+        // Sundays, Tuesdays and so on, but only in the first week
+        assert!(matches("0 7 1-7 * */2", 0, 7, 2));
+        assert!(!matches("0 7 1-7 * */2", 0, 7, 1));
+        assert!(!matches("0 7 1-7 * */2", 0, 7, 9));
+    }
+
+    #[test]
+    fn rejects_bad_expressions() {
+        assert!(Cron::parse("* * * *").is_err());
+        assert!(Cron::parse("60 * * * *").is_err());
+        assert!(Cron::parse("* 24 * * *").is_err());
+        assert!(Cron::parse("* * 0 * *").is_err());
+        assert!(Cron::parse("* * * 13 *").is_err());
+        assert!(Cron::parse("*/0 * * * *").is_err());
+        assert!(Cron::parse("30-10 * * * *").is_err());
+        assert!(Cron::parse("* * * * FUNDAY").is_err());
+    }
+}
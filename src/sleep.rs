@@ -0,0 +1,113 @@
+use crate::player::*;
+use leptos::*;
+#[allow(unused_imports)]
+use crate::displays::Displays;
+
+#[server(SetSleepTimer, "/api")]
+pub async fn set_sleep_timer(cx: Scope, display: String, mode: SleepMode, fade: bool) -> Result<(), ServerFnError> {
+    use crate::control::current_remote;
+
+    let displays = expect_context::<Displays>(cx);
+    let remote = current_remote(cx);
+    let action = match mode {
+        SleepMode::Minutes(minutes) => format!("set a sleep timer for {} minutes", minutes),
+        SleepMode::AfterItem => "set playback to stop after this item".to_string(),
+        SleepMode::AfterQueue => "set playback to stop after the queue".to_string()
+    };
+
+    displays.control(&display, &remote, action, |session| session.sleep = Some(SleepTimer::new(mode, fade)))?;
+
+    Ok(())
+}
+
+#[server(CancelSleepTimer, "/api")]
+pub async fn cancel_sleep_timer(cx: Scope, display: String) -> Result<(), ServerFnError> {
+    use crate::control::current_remote;
+
+    let displays = expect_context::<Displays>(cx);
+    let remote = current_remote(cx);
+
+    displays.control(&display, &remote, "cancelled the sleep timer", |session| {
+        // Undo any fade already in progress
+        if session.sleep.take().map(|timer| timer.fading()).unwrap_or(false) {
+            session.instructions.push_back(Instruction::Volume { volume: 1.0 });
+        }
+    })?;
+
+    Ok(())
+}
+
+cfg_if::cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use std::time::{Duration, Instant};
+
+    static FADE_SECONDS : f64 = 60.0;
+
+    /// A server-side sleep timer, checked by `run` whether or not a remote is connected.
+    /// Item and queue based timers are handled by the display's `advance` call instead.
+    #[derive(Clone, Debug)]
+    pub struct SleepTimer {
+        pub mode: SleepMode,
+        pub fade: bool,
+        deadline: Option<Instant>
+    }
+
+    impl SleepTimer {
+        pub fn new(mode: SleepMode, fade: bool) -> Self {
+            let deadline = match mode {
+                SleepMode::Minutes(minutes) => Some(Instant::now() + Duration::from_secs(minutes as u64 * 60)),
+                _ => None
+            };
+
+            Self {
+                mode,
+                fade,
+                deadline
+            }
+        }
+
+        pub fn remaining(&self) -> Option<Duration> {
+            self.deadline.map(|deadline| deadline.saturating_duration_since(Instant::now()))
+        }
+
+        pub fn fading(&self) -> bool {
+            self.fade && self.remaining().map(|r| r.as_secs_f64() < FADE_SECONDS).unwrap_or(false)
+        }
+
+        pub fn status(&self) -> SleepStatus {
+            SleepStatus {
+                mode: self.mode,
+                fade: self.fade,
+                remaining: self.remaining().map(|r| r.as_secs())
+            }
+        }
+    }
+
+    /// Checks every display's sleep timer once a second, fading out and stopping playback when it runs out.
+    pub async fn run(displays: Displays) {
+        loop {
+            tokio::time::sleep(Duration::from_secs(1)).await;
+
+            displays.for_each(|_, session| {
+                let Some(remaining) = session.sleep.as_ref().and_then(|timer| timer.remaining()) else {
+                    return;
+                };
+
+                if remaining.is_zero() {
+                    session.queue.clear();
+                    session.instructions.push_back(Instruction::Stop);
+                    session.sleep = None;
+                } else if session.sleep.as_ref().map(|timer| timer.fading()).unwrap_or(false) {
+                    session.instructions.push_back(Instruction::Volume { volume: remaining.as_secs_f64() / FADE_SECONDS });
+                }
+            });
+        }
+    }
+
+    //
+    //
+    //
+}}
@@ -0,0 +1,259 @@
+use crate::player::PROTOCOL_VERSION;
+use leptos::*;
+use serde::{Deserialize, Serialize};
+use std::cell::RefCell;
+use std::rc::Rc;
+
+/// Path prefix the app is served under, e.g. `/player` behind a reverse proxy. Set at build time.
+pub static BASE_PATH : &'static str = match option_env!("PLAYER_BASE_PATH") {
+    Some(path) => path,
+    None => ""
+};
+
+/// The service worker's cache of the app itself, as named in `sw.js`.
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+static SHELL_CACHE : &'static str = "player-shell-v2";
+
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+static INITIAL_BACKOFF_MS : u64 = 500;
+#[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+static MAX_BACKOFF_MS : u64 = 30_000;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Connection {
+    Connecting,
+    Connected,
+    Disconnected
+}
+
+/// Client side of the server signal websocket. Mirrors `leptos_server_signal`'s updates,
+/// but reconnects with backoff when the socket drops.
+#[derive(Clone)]
+pub struct ServerSignals {
+    connection: RwSignal<Connection>,
+    handlers: Rc<RefCell<Vec<Handler>>>
+}
+
+struct Handler {
+    name: String,
+    reset: Box<dyn Fn()>,
+    patch: Box<dyn Fn(&json_patch::Patch)>
+}
+
+/// The server's first message on every socket. Pages built for another protocol version
+/// reload when they see it, and the server closes their socket.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct Hello {
+    pub protocol: u32
+}
+
+/// Matches the JSON of `leptos_server_signal::ServerSignalUpdate`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+struct SignalUpdate {
+    name: String,
+    patch: json_patch::Patch
+}
+
+pub fn provide_server_signals(cx: Scope) {
+    let signals = ServerSignals {
+        connection: create_rw_signal(cx, Connection::Connecting),
+        handlers: Rc::new(RefCell::new(vec![]))
+    };
+
+    provide_context(cx, signals.clone());
+
+    #[cfg(target_arch = "wasm32")]
+    connect(signals, socket_url(), Rc::new(Reconnect::default()));
+}
+
+pub fn use_connection(cx: Scope) -> Signal<Connection> {
+    match use_context::<ServerSignals>(cx) {
+        Some(signals) => signals.connection.into(),
+        None => Signal::derive(cx, || Connection::Connecting)
+    }
+}
+
+/// Creates a signal which is controlled by the server, starting at `T::default()`.
+pub fn create_server_signal<T>(cx: Scope, name: &str) -> ReadSignal<T>
+where
+    T: Default + Serialize + for<'de> Deserialize<'de> + 'static
+{
+    let (get, set) = create_signal(cx, T::default());
+
+    let Some(signals) = use_context::<ServerSignals>(cx) else {
+        log::error!("server signal '{}' used without provide_server_signals", name);
+        return get;
+    };
+
+    let doc = Rc::new(RefCell::new(serde_json::to_value(T::default()).unwrap_or_default()));
+    let reset_doc = doc.clone();
+
+    signals.handlers.borrow_mut().push(Handler {
+        name: name.to_string(),
+        // Each new connection starts diffing from the default value again
+        reset: Box::new(move || {
+            *reset_doc.borrow_mut() = serde_json::to_value(T::default()).unwrap_or_default();
+        }),
+        patch: Box::new(move |patch| {
+            let mut doc = doc.borrow_mut();
+
+            if json_patch::patch(&mut doc, patch).is_ok() {
+                if let Ok(value) = serde_json::from_value(doc.clone()) {
+                    set.set(value);
+                }
+            }
+        })
+    });
+
+    get
+}
+
+impl ServerSignals {
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    fn dispatch(&self, message: &str) {
+        if let Ok(hello) = serde_json::from_str::<Hello>(message) {
+            if hello.protocol != PROTOCOL_VERSION {
+                reload_for_update();
+            }
+
+            return;
+        }
+
+        let Ok(update) = serde_json::from_str::<SignalUpdate>(message) else {
+            return;
+        };
+
+        for handler in self.handlers.borrow().iter().filter(|h| h.name == update.name) {
+            (handler.patch)(&update.patch);
+        }
+    }
+
+    #[cfg_attr(not(target_arch = "wasm32"), allow(dead_code))]
+    fn reset(&self) {
+        for handler in self.handlers.borrow().iter() {
+            (handler.reset)();
+        }
+    }
+}
+
+/// The websocket URL for the page's own origin, so remotes on other devices reach this server.
+#[cfg(target_arch = "wasm32")]
+fn socket_url() -> String {
+    let location = window().location();
+    let protocol = location.protocol().unwrap_or_default();
+    let host = location.host().unwrap_or_default();
+    let scheme = if protocol == "https:" { "wss" } else { "ws" };
+
+    format!("{}://{}{}/ws?protocol={}", scheme, host, BASE_PATH.trim_end_matches('/'), PROTOCOL_VERSION)
+}
+
+/// Reloads the page to pick up a new build, first dropping the service worker's copy of
+/// the old one so it isn't served again.
+pub fn reload_for_update() {
+    #[cfg(target_arch = "wasm32")]
+    {
+        use wasm_bindgen::{closure::Closure, JsValue};
+
+        let reload = || {
+            let _ = window().location().reload();
+        };
+
+        let caches = window().caches();
+
+        match caches {
+            Ok(caches) if !caches.is_undefined() => {
+                let then = Closure::once(move |_: JsValue| reload());
+                let _ = caches.delete(SHELL_CACHE).then(&then);
+                then.forget();
+            },
+            _ => reload()
+        }
+    }
+}
+
+/// What carries over from one socket to the next: how long to wait before trying again, and
+/// the current socket's handlers, dropped once a new socket replaces it.
+#[cfg(target_arch = "wasm32")]
+struct Reconnect {
+    backoff: std::cell::Cell<u64>,
+    handlers: RefCell<Option<SocketHandlers>>
+}
+
+#[cfg(target_arch = "wasm32")]
+type SocketHandlers = (
+    wasm_bindgen::closure::Closure<dyn FnMut()>,
+    wasm_bindgen::closure::Closure<dyn FnMut(web_sys::MessageEvent)>
+);
+
+#[cfg(target_arch = "wasm32")]
+impl Default for Reconnect {
+    fn default() -> Self {
+        Self {
+            backoff: std::cell::Cell::new(INITIAL_BACKOFF_MS),
+            handlers: RefCell::new(None)
+        }
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn connect(signals: ServerSignals, url: String, state: Rc<Reconnect>) {
+    use wasm_bindgen::{closure::Closure, JsCast};
+    use web_sys::{MessageEvent, WebSocket};
+
+    let reconnect = {
+        let signals = signals.clone();
+        let url = url.clone();
+        let state = state.clone();
+
+        move || {
+            signals.connection.set(Connection::Disconnected);
+
+            let delay = state.backoff.get();
+            state.backoff.set((delay * 2).min(MAX_BACKOFF_MS));
+
+            set_timeout(
+                move || connect(signals, url, state),
+                std::time::Duration::from_millis(delay)
+            );
+        }
+    };
+
+    let ws = match WebSocket::new(&url) {
+        Ok(ws) => ws,
+        Err(_) => {
+            reconnect();
+            return;
+        }
+    };
+
+    let onopen = {
+        let signals = signals.clone();
+        let state = state.clone();
+
+        Closure::<dyn FnMut()>::new(move || {
+            state.backoff.set(INITIAL_BACKOFF_MS);
+            signals.reset();
+            signals.connection.set(Connection::Connected);
+        })
+    };
+
+    let onmessage = {
+        let signals = signals.clone();
+
+        Closure::<dyn FnMut(MessageEvent)>::new(move |event: MessageEvent| {
+            if let Some(message) = event.data().as_string() {
+                signals.dispatch(&message);
+            }
+        })
+    };
+
+    // Every socket closes exactly once, even if it never opened, so this one frees itself
+    let onclose = Closure::once_into_js(reconnect);
+
+    ws.set_onopen(Some(onopen.as_ref().unchecked_ref()));
+    ws.set_onmessage(Some(onmessage.as_ref().unchecked_ref()));
+    ws.set_onclose(Some(onclose.unchecked_ref()));
+
+    // The last socket has closed by now, so nothing calls its handlers any more
+    state.handlers.replace(Some((onopen, onmessage)));
+}
@@ -0,0 +1,192 @@
+use cfg_if::cfg_if;
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use serde::{de::DeserializeOwned, Deserialize, Serialize};
+    use serde_json::Value;
+    use std::path::{Path, PathBuf};
+
+    /// Upgrades a store's data from one schema version to the next.
+    pub type Migration = fn(Value) -> Result<Value, String>;
+
+    /// How each store file is written: its data along with the schema version it follows.
+    #[derive(Deserialize, Serialize)]
+    struct Versioned<T> {
+        version: usize,
+        data: T
+    }
+
+    /// Files written before stores were versioned hold their data directly, which is unchanged
+    /// as version 1.
+    pub fn unversioned(data: Value) -> Result<Value, String> {
+        Ok(data)
+    }
+
+    /// Loads a store written at any schema version up to the current one, `migrations.len()`,
+    /// running the migrations it needs and rewriting it at the current version. The file
+    /// from before migrating is kept alongside as `<name>.v<version>.bak`. Missing files give
+    /// the default.
+    pub fn load<T>(path: &Path, migrations: &[Migration]) -> Result<T, String>
+    where
+        T: Default + DeserializeOwned + Serialize
+    {
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(T::default()),
+            Err(e) => return Err(format!("Couldn't read {}: {}", path.display(), e))
+        };
+
+        let value = serde_json::from_str::<Value>(&json)
+            .map_err(|e| format!("Invalid JSON in {}: {}", path.display(), e))?;
+
+        let (version, mut data) = match serde_json::from_value::<Versioned<Value>>(value.clone()) {
+            Ok(versioned) => (versioned.version, versioned.data),
+            Err(_) => (0, value)
+        };
+
+        let current = migrations.len();
+
+        if version > current {
+            return Err(format!(
+                "{} is at version {}, but this build only understands up to {}",
+                path.display(),
+                version,
+                current
+            ));
+        }
+
+        for (from, migrate) in migrations.iter().enumerate().skip(version) {
+            data = migrate(data)
+                .map_err(|e| format!("Couldn't migrate {} from version {}: {}", path.display(), from, e))?;
+        }
+
+        let data = serde_json::from_value::<T>(data)
+            .map_err(|e| format!("Invalid data in {}: {}", path.display(), e))?;
+
+        if version < current {
+            let backup = backup_path(path, version);
+
+            std::fs::copy(path, &backup)
+                .and_then(|_| save(path, current, &data))
+                .map_err(|e| format!("Couldn't upgrade {}: {}", path.display(), e))?;
+
+            log::warn!("Upgraded {} from version {} to {}", path.display(), version, current);
+        }
+
+        Ok(data)
+    }
+
+    /// Writes a store at schema `version`, replacing the old file in one step so a crash
+    /// mid-write can't leave it half written.
+    pub fn save<T: Serialize>(path: &Path, version: usize, data: &T) -> std::io::Result<()> {
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let json = serde_json::to_string_pretty(&Versioned { version, data })?;
+        let temp = path.with_extension("json.tmp");
+
+        std::fs::write(&temp, json)?;
+        std::fs::rename(&temp, path)
+    }
+
+    fn backup_path(path: &Path, version: usize) -> PathBuf {
+        let name = path.file_stem().map(|stem| stem.to_string_lossy().to_string()).unwrap_or_default();
+        path.with_file_name(format!("{}.v{}.bak", name, version))
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+        use serde_json::json;
+
+        static MIGRATIONS : &'static [Migration] = &[unversioned, wrap_names];
+
+        /// Version 2 turned a list of names into objects.
+        fn wrap_names(data: Value) -> Result<Value, String> {
+            let names = data.as_array().ok_or_else(|| "Expected a list".to_string())?;
+            Ok(Value::Array(names.iter().map(|name| json!({ "name": name })).collect()))
+        }
+
+        #[derive(Debug, Default, Deserialize, PartialEq, Serialize)]
+        struct Named {
+            name: String
+        }
+
+        /// A fresh directory for one test's files.
+        fn temp_dir(test: &str) -> PathBuf {
+            let dir = std::env::temp_dir().join(format!("player-store-{}-{}", test, std::process::id()));
+            let _ = std::fs::remove_dir_all(&dir);
+            std::fs::create_dir_all(&dir).unwrap();
+            dir
+        }
+
+        fn read(path: &Path) -> Value {
+            serde_json::from_str(&std::fs::read_to_string(path).unwrap()).unwrap()
+        }
+
+        #[test]
+        fn missing_files_give_the_default() {
+            let path = temp_dir("missing").join("names.json");
+            assert_eq!(load::<Vec<Named>>(&path, MIGRATIONS), Ok(vec![]));
+        }
+
+        #[test]
+        fn migrates_unversioned_files_and_keeps_a_backup() {
+            let dir = temp_dir("unversioned");
+            let path = dir.join("names.json");
+            std::fs::write(&path, r#"["tv", "kitchen"]"#).unwrap();
+
+            let names = load::<Vec<Named>>(&path, MIGRATIONS).unwrap();
+
+            assert_eq!(names, vec![Named { name: "tv".to_string() }, Named { name: "kitchen".to_string() }]);
+            assert_eq!(read(&path), json!({ "version": 2, "data": [{ "name": "tv" }, { "name": "kitchen" }] }));
+            assert_eq!(read(&dir.join("names.v0.bak")), json!(["tv", "kitchen"]));
+        }
+
+        #[test]
+        fn only_runs_the_migrations_needed() {
+            let dir = temp_dir("partial");
+            let path = dir.join("names.json");
+            std::fs::write(&path, r#"{"version": 1, "data": ["tv"]}"#).unwrap();
+
+            assert_eq!(load::<Vec<Named>>(&path, MIGRATIONS), Ok(vec![Named { name: "tv".to_string() }]));
+            assert!(dir.join("names.v1.bak").is_file());
+            assert!(!dir.join("names.v0.bak").exists());
+        }
+
+        #[test]
+        fn leaves_current_files_alone() {
+            let dir = temp_dir("current");
+            let path = dir.join("names.json");
+            save(&path, MIGRATIONS.len(), &vec![Named { name: "tv".to_string() }]).unwrap();
+
+            assert_eq!(load::<Vec<Named>>(&path, MIGRATIONS), Ok(vec![Named { name: "tv".to_string() }]));
+            assert!(!dir.join("names.v2.bak").exists());
+        }
+
+        #[test]
+        fn refuses_files_from_newer_builds() {
+            let path = temp_dir("newer").join("names.json");
+            std::fs::write(&path, r#"{"version": 3, "data": []}"#).unwrap();
+
+            assert!(load::<Vec<Named>>(&path, MIGRATIONS).is_err());
+        }
+
+        #[test]
+        fn reports_failed_migrations() {
+            let path = temp_dir("failed").join("names.json");
+            std::fs::write(&path, r#"{"version": 1, "data": {"tv": true}}"#).unwrap();
+
+            let error = load::<Vec<Named>>(&path, MIGRATIONS).unwrap_err();
+            assert!(error.contains("Expected a list"), "{}", error);
+        }
+    }
+
+    //
+    //
+    //
+}}
@@ -0,0 +1,283 @@
+use cfg_if::cfg_if;
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use crate::config::SyncConfig;
+    use crate::displays::{Displays, Session};
+    use crate::player::*;
+    use std::time::{Duration, Instant};
+
+    static SYNC_INTERVAL : Duration = Duration::from_millis(500);
+    /// How long a display is left alone after being told to load or seek, before its
+    /// position means anything
+    static LOAD_SETTLE : Duration = Duration::from_secs(3);
+    static SEEK_SETTLE : Duration = Duration::from_secs(1);
+    /// How much faster or slower a drifting display plays while it catches up. Small
+    /// enough that nobody hears the pitch change.
+    static RATE_NUDGE : f64 = 0.05;
+
+    /// How a display playing along with a group is being kept in step.
+    #[derive(Clone, Debug)]
+    pub struct Follower {
+        /// The playback rate it was last told to use
+        rate: f64,
+        settle_until: Option<Instant>
+    }
+
+    impl Default for Follower {
+        fn default() -> Self {
+            Self {
+                rate: 1.0,
+                settle_until: None
+            }
+        }
+    }
+
+    impl Follower {
+        fn is_settling(&self) -> bool {
+            self.settle_until.map(|until| Instant::now() < until).unwrap_or(false)
+        }
+
+        fn settle(&mut self, time: Duration) {
+            self.settle_until = Some(Instant::now() + time);
+        }
+
+        /// Stops following, giving the instruction that puts its rate back to normal if needed.
+        pub fn leave(&mut self) -> Option<Instruction> {
+            let rate = std::mem::replace(&mut self.rate, 1.0);
+            self.settle_until = None;
+
+            (rate != 1.0).then_some(Instruction::Rate { rate: 1.0 })
+        }
+    }
+
+    /// Has a follower load something, which also puts its rate back to normal.
+    fn load(follower: &mut Session, i: Instruction) {
+        follower.instructions.push_back(i);
+        follower.following.rate = 1.0;
+        follower.following.settle(LOAD_SETTLE);
+    }
+
+    /// What a group's leader is doing, for its followers to copy.
+    #[derive(Clone, Debug)]
+    pub struct Lead {
+        pub player: PlayerState,
+        pub queue: Queue,
+        /// Where the leader should be by now, allowing for the time since it reported
+        pub position: f64
+    }
+
+    /// Brings a follower into line with its group's leader: loading what the leader has,
+    /// playing or pausing with it, and correcting drift once both are playing.
+    pub fn follow(lead: &Lead, follower: &mut Session, settings: SyncConfig) {
+        // Anything still to be done would be undone by what's sent now
+        if !follower.is_online() || !follower.instructions.is_empty() || follower.following.is_settling() {
+            return;
+        }
+
+        follower.queue = lead.queue.clone();
+
+        let PlayerState::Media(leader) = &lead.player else {
+            if follower.player != PlayerState::Idle {
+                load(follower, Instruction::Stop);
+            }
+
+            return;
+        };
+
+        let leader_playing = matches!(leader.phase, Phase::Playing | Phase::Buffering);
+        let path = leader.path.clone();
+
+        let instruction = if MediaKind::of(&path) == MediaKind::Image {
+            Instruction::Show { path, settings: lead.queue.slides }
+        } else if leader_playing {
+            Instruction::Play { path, from: lead.position }
+        } else {
+            Instruction::Pause { path, at: lead.position }
+        };
+
+        let current = match &follower.player {
+            PlayerState::Media(media) if media.path == leader.path => media.clone(),
+            _ => {
+                load(follower, instruction);
+                return;
+            }
+        };
+
+        if MediaKind::of(&current.path) == MediaKind::Image || current.phase.is_waiting() {
+            return;
+        }
+
+        let follower_playing = matches!(current.phase, Phase::Playing | Phase::Buffering);
+
+        if leader_playing != follower_playing {
+            load(follower, instruction);
+            return;
+        }
+
+        if !leader_playing {
+            return;
+        }
+
+        let drift = follower.position_now(follower.following.rate).unwrap_or(current.position) - lead.position;
+
+        let rate = if drift.abs() > settings.seek_after {
+            follower.instructions.push_back(Instruction::Seek { at: lead.position });
+            follower.following.settle(SEEK_SETTLE);
+            1.0
+        } else if drift.abs() > settings.tolerance {
+            1.0 - drift.signum() * RATE_NUDGE
+        } else if drift.abs() < settings.tolerance / 2.0 {
+            1.0
+        } else {
+            follower.following.rate
+        };
+
+        if rate != follower.following.rate {
+            follower.following.rate = rate;
+            follower.instructions.push_back(Instruction::Rate { rate });
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        fn media(path: &str, phase: Phase, position: f64) -> PlayerState {
+            PlayerState::Media(MediaState {
+                path: path.to_string(),
+                phase,
+                duration: 600.0,
+                position,
+                buffered: vec![]
+            })
+        }
+
+        fn lead(player: PlayerState, position: f64) -> Lead {
+            Lead {
+                player,
+                queue: Queue::default(),
+                position
+            }
+        }
+
+        /// An online display that has just reported `player`.
+        fn follower(player: PlayerState) -> Session {
+            let mut session = Session::default();
+            session.seen();
+            session.report(player);
+            session
+        }
+
+        /// What `follow` has the follower do.
+        fn instructions(lead: &Lead, follower: &mut Session) -> Vec<Instruction> {
+            follow(lead, follower, SyncConfig::default());
+            follower.instructions.drain(..).collect()
+        }
+
+        #[test]
+        fn leaves_offline_displays_alone() {
+            let mut offline = Session::default();
+            let lead = lead(media("films/Up.mkv", Phase::Playing, 10.0), 10.0);
+
+            assert_eq!(instructions(&lead, &mut offline), vec![]);
+        }
+
+        #[test]
+        fn loads_what_the_leader_has() {
+            let playing = lead(media("films/Up.mkv", Phase::Playing, 10.0), 10.0);
+            let paused = lead(media("films/Up.mkv", Phase::Paused, 10.0), 10.0);
+
+            assert_eq!(
+                instructions(&playing, &mut follower(PlayerState::Idle)),
+                vec![Instruction::Play { path: "films/Up.mkv".to_string(), from: 10.0 }]
+            );
+            assert_eq!(
+                instructions(&paused, &mut follower(media("films/Cars.mkv", Phase::Playing, 5.0))),
+                vec![Instruction::Pause { path: "films/Up.mkv".to_string(), at: 10.0 }]
+            );
+        }
+
+        #[test]
+        fn waits_for_a_load_to_settle() {
+            let lead = lead(media("films/Up.mkv", Phase::Playing, 10.0), 10.0);
+            let mut follower = follower(PlayerState::Idle);
+
+            assert_eq!(instructions(&lead, &mut follower).len(), 1);
+            assert_eq!(instructions(&lead, &mut follower), vec![]);
+        }
+
+        #[test]
+        fn stops_with_the_leader() {
+            let lead = lead(PlayerState::Idle, 0.0);
+
+            assert_eq!(instructions(&lead, &mut follower(media("films/Up.mkv", Phase::Playing, 10.0))), vec![Instruction::Stop]);
+            assert_eq!(instructions(&lead, &mut follower(PlayerState::Idle)), vec![]);
+        }
+
+        #[test]
+        fn plays_and_pauses_with_the_leader() {
+            let playing = lead(media("films/Up.mkv", Phase::Playing, 10.0), 10.0);
+            let paused = lead(media("films/Up.mkv", Phase::Paused, 10.0), 10.0);
+
+            assert_eq!(
+                instructions(&playing, &mut follower(media("films/Up.mkv", Phase::Paused, 10.0))),
+                vec![Instruction::Play { path: "films/Up.mkv".to_string(), from: 10.0 }]
+            );
+            assert_eq!(
+                instructions(&paused, &mut follower(media("films/Up.mkv", Phase::Playing, 10.0))),
+                vec![Instruction::Pause { path: "films/Up.mkv".to_string(), at: 10.0 }]
+            );
+            assert_eq!(instructions(&paused, &mut follower(media("films/Up.mkv", Phase::Paused, 12.0))), vec![]);
+        }
+
+        #[test]
+        fn leaves_loading_followers_to_finish() {
+            let lead = lead(media("films/Up.mkv", Phase::Playing, 10.0), 10.0);
+            assert_eq!(instructions(&lead, &mut follower(media("films/Up.mkv", Phase::Buffering, 3.0))), vec![]);
+        }
+
+        #[test]
+        fn nudges_small_drift_and_seeks_past_large() {
+            let lead = lead(media("films/Up.mkv", Phase::Playing, 100.0), 100.0);
+
+            assert_eq!(instructions(&lead, &mut follower(media("films/Up.mkv", Phase::Playing, 100.1))), vec![]);
+
+            let ahead = instructions(&lead, &mut follower(media("films/Up.mkv", Phase::Playing, 100.5)));
+            assert!(matches!(ahead.as_slice(), [Instruction::Rate { rate }] if *rate < 1.0), "{:?}", ahead);
+
+            let behind = instructions(&lead, &mut follower(media("films/Up.mkv", Phase::Playing, 99.5)));
+            assert!(matches!(behind.as_slice(), [Instruction::Rate { rate }] if *rate > 1.0), "{:?}", behind);
+
+            let lost = instructions(&lead, &mut follower(media("films/Up.mkv", Phase::Playing, 90.0)));
+            assert_eq!(lost, vec![Instruction::Seek { at: 100.0 }]);
+        }
+
+        #[test]
+        fn returns_to_normal_speed_once_caught_up() {
+            let lead = lead(media("films/Up.mkv", Phase::Playing, 100.0), 100.0);
+            let mut follower = follower(media("films/Up.mkv", Phase::Playing, 100.5));
+
+            assert_eq!(instructions(&lead, &mut follower).len(), 1);
+
+            follower.report(media("films/Up.mkv", Phase::Playing, 100.0));
+            assert_eq!(instructions(&lead, &mut follower), vec![Instruction::Rate { rate: 1.0 }]);
+            assert_eq!(follower.following.leave(), None);
+        }
+    }
+
+    /// Keeps every group's followers with their leader, a couple of times a second.
+    pub async fn run(displays: Displays, settings: SyncConfig) {
+        loop {
+            tokio::time::sleep(SYNC_INTERVAL).await;
+            displays.sync(|lead, follower| follow(lead, follower, settings));
+        }
+    }
+
+    //
+    //
+    //
+}}
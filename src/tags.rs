@@ -0,0 +1,59 @@
+use cfg_if::cfg_if;
+use crate::player::*;
+use leptos::*;
+#[allow(unused_imports)]
+use crate::library::*;
+
+pub static ART_ROOT : &'static str = "/art/";
+
+#[server(GetTrackInfo, "/api")]
+pub async fn get_track_info(cx: Scope, path: String) -> Result<TrackInfo, ServerFnError> {
+    let libraries = crate::profiles::profile_libraries(cx)?;
+    let path = libraries.media(&path, MediaKind::Audio)?;
+
+    Ok(read_track_info(&path))
+}
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use lofty::{Accessor, TaggedFile, TaggedFileExt};
+    use std::path::Path;
+
+    /// Reads ID3/Vorbis/MP4 tags, falling back to an empty `TrackInfo` for untagged files.
+    pub fn read_track_info(path: &Path) -> TrackInfo {
+        let Some(tagged) = read_tagged(path) else {
+            return TrackInfo::default();
+        };
+
+        let Some(tag) = tagged.primary_tag().or_else(|| tagged.first_tag()) else {
+            return TrackInfo::default();
+        };
+
+        TrackInfo {
+            title: tag.title().map(|s| s.to_string()),
+            artist: tag.artist().map(|s| s.to_string()),
+            album: tag.album().map(|s| s.to_string()),
+            has_art: !tag.pictures().is_empty()
+        }
+    }
+
+    /// Returns the mime type and data of the first embedded picture.
+    pub fn read_album_art(path: &Path) -> Option<(String, Vec<u8>)> {
+        let tagged = read_tagged(path)?;
+        let tag = tagged.primary_tag().or_else(|| tagged.first_tag())?;
+        let picture = tag.pictures().first()?;
+
+        Some((picture.mime_type().as_str().to_string(), picture.data().to_vec()))
+    }
+
+    fn read_tagged(path: &Path) -> Option<TaggedFile> {
+        lofty::read_from_path(path).ok()
+    }
+
+    //
+    //
+    //
+}}
@@ -0,0 +1,137 @@
+use cfg_if::cfg_if;
+
+cfg_if! { if #[cfg(feature = "ssr")] {
+    //
+    //
+    //
+
+    use axum::{
+        http::{header::HOST, uri::Authority, HeaderMap, StatusCode, Uri},
+        response::{IntoResponse, Redirect, Response},
+        Router
+    };
+    use axum_server::tls_rustls::RustlsConfig;
+    use crate::config::Config;
+    use std::net::{IpAddr, SocketAddr};
+    use std::path::PathBuf;
+
+    /// The certificate to serve, from the configured files or the self-signed one in
+    /// `data_dir/tls`, which is generated the first time it's needed.
+    pub async fn load(config: &Config) -> Result<RustlsConfig, String> {
+        let (cert, key) = match (&config.tls.cert, &config.tls.key) {
+            (Some(cert), Some(key)) => (cert.clone(), key.clone()),
+            _ => self_signed(config)?
+        };
+
+        RustlsConfig::from_pem_file(&cert, &key)
+            .await
+            .map_err(|e| format!("Couldn't load certificate {}: {}", cert.display(), e))
+    }
+
+    fn self_signed(config: &Config) -> Result<(PathBuf, PathBuf), String> {
+        let dir = config.data_dir.join("tls");
+        let cert = dir.join("cert.pem");
+        let key = dir.join("key.pem");
+
+        if cert.is_file() && key.is_file() {
+            return Ok((cert, key));
+        }
+
+        let mut names = vec!["localhost".to_string()];
+        names.extend(config.tls.hostnames.iter().cloned());
+
+        if let Some(address) = config.server.address.filter(|address| !address.is_unspecified()) {
+            names.push(address.to_string());
+        }
+
+        let generated = rcgen::generate_simple_self_signed(names)
+            .map_err(|e| format!("Couldn't generate a certificate: {}", e))?;
+        let cert_pem = generated
+            .serialize_pem()
+            .map_err(|e| format!("Couldn't generate a certificate: {}", e))?;
+
+        std::fs::create_dir_all(&dir).map_err(|e| format!("Couldn't create {}: {}", dir.display(), e))?;
+        write(&cert, cert_pem, false)?;
+        write(&key, generated.serialize_private_key_pem(), true)?;
+
+        log::warn!("Generated a self-signed certificate in {}", dir.display());
+        Ok((cert, key))
+    }
+
+    /// Writes a PEM file, readable only by its owner if it's `private`.
+    fn write(path: &PathBuf, pem: String, private: bool) -> Result<(), String> {
+        use std::io::Write;
+
+        let mut options = std::fs::OpenOptions::new();
+        options.write(true).create(true).truncate(true);
+
+        #[cfg(unix)]
+        if private {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(0o600);
+        }
+
+        let error = |e: std::io::Error| format!("Couldn't write {}: {}", path.display(), e);
+        let mut file = options.open(path).map_err(error)?;
+
+        // The mode only applies to new files, so tighten any left over from before
+        #[cfg(unix)]
+        if private {
+            use std::os::unix::fs::PermissionsExt;
+            file.set_permissions(std::fs::Permissions::from_mode(0o600)).map_err(error)?;
+        }
+
+        #[cfg(not(unix))]
+        let _ = private;
+
+        file.write_all(pem.as_bytes()).map_err(error)
+    }
+
+    /// Listens for plain HTTP on `port`, sending every request to the same place over HTTPS.
+    pub async fn redirect_to_https(address: IpAddr, port: u16, https_port: u16) {
+        let app = Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move {
+            redirect(&headers, &uri, https_port)
+        });
+
+        // Failing to bind, say to port 80 without permission, shouldn't take HTTPS down with it
+        let server = match axum::Server::try_bind(&SocketAddr::new(address, port)) {
+            Ok(server) => server,
+            Err(e) => {
+                log::error!("Couldn't listen on port {} to redirect to HTTPS: {}", port, e);
+                return;
+            }
+        };
+
+        let result = server
+            .serve(app.into_make_service())
+            .await;
+
+        if let Err(e) = result {
+            log::error!("HTTP redirect on port {} stopped: {}", port, e);
+        }
+    }
+
+    fn redirect(headers: &HeaderMap, uri: &Uri, https_port: u16) -> Response {
+        let host = headers
+            .get(HOST)
+            .and_then(|host| host.to_str().ok())
+            .and_then(|host| host.parse::<Authority>().ok());
+
+        let Some(host) = host else {
+            return StatusCode::BAD_REQUEST.into_response();
+        };
+
+        let port = match https_port {
+            443 => String::new(),
+            port => format!(":{}", port)
+        };
+
+        let path = uri.path_and_query().map(|p| p.as_str()).unwrap_or("/");
+
+        Redirect::permanent(&format!("https://{}{}{}", host.host(), port, path)).into_response()
+    }
+
+    //
+    //
+    //
+}}
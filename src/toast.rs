@@ -0,0 +1,103 @@
+use crate::error::PlayerError;
+use crate::icons::Icon;
+use leptos::*;
+#[allow(unused_imports)]
+use std::time::Duration;
+
+/// How long a toast stays up unless dismissed.
+#[allow(dead_code)]
+static TOAST_DURATION : Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Toast {
+    pub id: u64,
+    pub message: String,
+    pub is_error: bool
+}
+
+/// Short lived messages shown over the page, for failures that don't belong to any one form
+/// and for what other remotes are doing.
+#[derive(Clone, Copy)]
+pub struct Toasts {
+    toasts: RwSignal<Vec<Toast>>,
+    next_id: StoredValue<u64>
+}
+
+impl Toasts {
+    pub fn error(&self, error: ServerFnError) {
+        self.push(PlayerError::from(error).to_string(), true);
+    }
+
+    pub fn info(&self, message: String) {
+        self.push(message, false);
+    }
+
+    fn push(&self, message: String, is_error: bool) {
+        let id = self.next_id.get_value();
+        self.next_id.set_value(id + 1);
+
+        self.toasts.update(|toasts| toasts.push(Toast { id, message, is_error }));
+
+        #[cfg(not(feature = "ssr"))]
+        {
+            let toasts = *self;
+            set_timeout(move || toasts.dismiss(id), TOAST_DURATION);
+        }
+    }
+
+    pub fn dismiss(&self, id: u64) {
+        self.toasts.update(|toasts| toasts.retain(|toast| toast.id != id));
+    }
+}
+
+pub fn provide_toasts(cx: Scope) -> Toasts {
+    let toasts = Toasts {
+        toasts: create_rw_signal(cx, Vec::new()),
+        next_id: store_value(cx, 0)
+    };
+
+    provide_context(cx, toasts);
+    toasts
+}
+
+/// Shows a toast whenever `action` fails.
+pub fn toast_errors<I: 'static, O: 'static>(cx: Scope, action: Action<I, Result<O, ServerFnError>>) {
+    let toasts = expect_context::<Toasts>(cx);
+
+    create_effect(cx, move |_| {
+        action.value().with(|value| {
+            if let Some(Err(e)) = value {
+                toasts.error(e.clone());
+            }
+        })
+    });
+}
+
+#[component]
+pub fn ToastList(cx: Scope) -> impl IntoView {
+    let toasts = expect_context::<Toasts>(cx);
+
+    view! { cx,
+        <div class="toasts">
+            {move || {
+                toasts.toasts
+                    .get()
+                    .into_iter()
+                    .map(|toast| {
+                        let id = toast.id;
+                        let class = if toast.is_error { "alert-danger" } else { "alert-info" };
+
+                        view! { cx,
+                            <div class=format!("toast alert {} d-flex align-items-start", class)>
+                                <span class="flex-grow-1">{toast.message}</span>
+                                <button class="btn btn-link p-0 ms-2" on:click=move |_| toasts.dismiss(id)>
+                                    <Icon icon="close"/>
+                                </button>
+                            </div>
+                        }
+                    })
+                    .collect::<Vec<_>>()
+            }}
+        </div>
+    }
+}